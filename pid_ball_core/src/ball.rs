@@ -0,0 +1,77 @@
+use crate::default::*;
+use core::time::Duration;
+
+#[derive(Debug, Clone)]
+pub struct Ball {
+    pub pos: f32,
+    pub vel: f32,
+    /// Magnitude limit of the velocity like a viscous terminal velocity, infinity disables it
+    pub max_vel: f32,
+    /// Mass [kg], which turns the force into the acceleration
+    pub mass: f32,
+    /// Position and velocity after a reset
+    pub initial_pos: f32,
+    pub initial_vel: f32,
+}
+
+impl Default for Ball {
+    fn default() -> Self {
+        Self {
+            pos: BALL_POS,
+            vel: BALL_VEL,
+            max_vel: MAX_VELOCITY,
+            mass: MASS,
+            initial_pos: BALL_POS,
+            initial_vel: BALL_VEL,
+        }
+    }
+}
+
+impl Ball {
+    pub fn reset(&mut self) {
+        self.pos = self.initial_pos;
+        self.vel = self.initial_vel;
+    }
+    pub fn step(&mut self, force: f32, delta_time: Duration) {
+        let dt = delta_time.as_secs_f32();
+        let delta_vel = 0.5 * dt * force / self.mass;
+        self.vel = (self.vel + delta_vel).clamp(-self.max_vel, self.max_vel);
+        self.pos += self.vel * dt;
+        self.vel = (self.vel + delta_vel).clamp(-self.max_vel, self.max_vel);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn heavier_balls_accelerate_slower() {
+        let mut light = Ball::default();
+        let mut heavy = Ball {
+            mass: 2.0,
+            ..Default::default()
+        };
+        light.step(1.0, Duration::from_secs(1));
+        heavy.step(1.0, Duration::from_secs(1));
+        assert_eq!(light.vel, 1.0);
+        assert_eq!(heavy.vel, 0.5);
+    }
+
+    #[test]
+    fn velocity_is_clamped() {
+        let mut ball = Ball {
+            max_vel: 2.0,
+            ..Default::default()
+        };
+        for _ in 0..1_000 {
+            ball.step(1e6, Duration::from_millis(10));
+            assert!(ball.vel <= 2.0, "{}", ball.vel);
+        }
+        assert_eq!(ball.vel, 2.0);
+        for _ in 0..1_000 {
+            ball.step(-1e6, Duration::from_millis(10));
+            assert!(ball.vel >= -2.0, "{}", ball.vel);
+        }
+    }
+}
@@ -0,0 +1,74 @@
+use crate::default::*;
+use crate::Controller;
+use core::time::Duration;
+
+/// Two-point controller that switches between `+force` and `-force`
+///
+/// It pushes up once the ball is more than half the hysteresis below the target and
+/// down once it is more than half the hysteresis above, keeping its output in between.
+#[derive(Clone)]
+pub struct BangBang {
+    /// Width of the band around the target without switching [m]
+    pub hysteresis: f32,
+    pub force: f32,
+
+    /// Pushing up, `None` before the first update
+    up: Option<bool>,
+}
+
+impl Default for BangBang {
+    fn default() -> Self {
+        Self {
+            hysteresis: BANG_BANG_HYSTERESIS,
+            force: BANG_BANG_FORCE,
+            up: None,
+        }
+    }
+}
+
+impl Controller for BangBang {
+    fn update(&mut self, pos: f32, target: f32, _saturated: bool, _sample_time: Duration) -> f32 {
+        let error = target - pos;
+        let half = 0.5 * self.hysteresis;
+        let up = if error > half {
+            true
+        } else if error < -half {
+            false
+        } else {
+            // Starting within the band by the side of the target
+            self.up.unwrap_or(error >= 0.0)
+        };
+        self.up = Some(up);
+        if up {
+            self.force
+        } else {
+            -self.force
+        }
+    }
+
+    fn reset(&mut self) {
+        self.up = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DT: Duration = Duration::from_millis(1);
+
+    #[test]
+    fn switches_with_hysteresis() {
+        let mut bang_bang = BangBang {
+            hysteresis: 0.1,
+            force: 2.0,
+            ..Default::default()
+        };
+        let outputs = [0.0, 0.04, 0.06, 0.04, -0.04, -0.06, -0.04]
+            .map(|pos| bang_bang.update(pos, 0.0, false, DT));
+        assert_eq!(outputs, [2.0, 2.0, -2.0, -2.0, -2.0, 2.0, 2.0]);
+
+        bang_bang.reset();
+        assert_eq!(bang_bang.update(0.04, 0.0, false, DT), -2.0);
+    }
+}
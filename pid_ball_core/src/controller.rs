@@ -0,0 +1,28 @@
+use core::time::Duration;
+
+/// Control law that turns the measured position of the ball into the requested force
+pub trait Controller {
+    /// Updates the controller with the measured position and returns its output
+    ///
+    /// `saturated` tells whether the actuator had to clip the output of the previous update.
+    fn update(&mut self, pos: f32, target: f32, saturated: bool, sample_time: Duration) -> f32;
+
+    /// Hands over the velocity of a dedicated sensor for the next update [m/s]
+    ///
+    /// Control laws without a use for it ignore it.
+    fn measure_velocity(&mut self, _vel: f32) {}
+
+    /// Forgets everything from before, e.g. integral and previous measurements
+    fn reset(&mut self);
+
+    /// Proportional, integral and derivative term of the last update, zero for other laws
+    fn terms(&self) -> [f32; 3] {
+        [0.0; 3]
+    }
+
+    /// Anti-windup with the saturated and unsaturated force, see [`crate::Pid::back_calculate`]
+    fn back_calculate(&mut self, _saturated: f32, _unsaturated: f32) {}
+
+    /// Starts the integral at `output`, if the control law has one
+    fn preload(&mut self, _output: f32) {}
+}
@@ -0,0 +1,36 @@
+use crate::{DerivativeMode, DerivativeSource, DerivativeStart, IntegralMode, ProportionalMode};
+
+pub const KP: f32 = 150.0;
+pub const KI: f32 = 1.0;
+pub const KD: f32 = 20.0;
+pub const KT: f32 = 0.0; // Back-calculation anti-windup is off
+pub const KFF: f32 = 0.0; // No feed-forward
+pub const P_MODE: ProportionalMode = ProportionalMode::OnError;
+pub const D_MODE: DerivativeMode = DerivativeMode::OnMeasurement;
+pub const D_FILTER_TAU: f32 = 0.0; // s, unfiltered
+pub const D_START: DerivativeStart = DerivativeStart::Skip;
+pub const D_SOURCE: DerivativeSource = DerivativeSource::FromPosition;
+pub const I_MODE: IntegralMode = IntegralMode::PerSample; // Legacy, keeps the default gains
+pub const CONDITIONAL_INTEGRATION: bool = false; // Integrates while saturated
+pub const I_LIMIT: Option<f32> = None; // Unbounded integral
+pub const I_BAND: Option<f32> = None; // Integrates at any error
+pub const OUTPUT_LIMIT: Option<f32> = None; // Unclamped command
+pub const TARGET: f32 = 0.6;
+pub const LEAD_LAG_GAIN: f32 = 150.0;
+pub const LEAD_TIME: f32 = 0.13; // s, like KD / KP
+pub const LAG_TIME: f32 = 0.01; // s
+pub const LEAD_LAG_BIAS: f32 = 0.0;
+pub const BANG_BANG_FORCE: f32 = 20.0; // N
+pub const BANG_BANG_HYSTERESIS: f32 = 0.01; // m
+pub const DEAD_ZONE: f32 = 0.0; // m
+pub const BALL_POS: f32 = 0.5;
+pub const BALL_VEL: f32 = 0.0;
+pub const MASS: f32 = 1.0; // kg
+pub const MAX_VELOCITY: f32 = f32::INFINITY; // No limit
+pub const IND_POS: f32 = 1.0;
+pub const MAX_FORCE: f32 = 50.0;
+pub const INITIAL_FORCE: f32 = 0.0;
+pub const MAX_FORCE_RATE: f32 = 200.0;
+pub const ACTUATOR_GAIN: f32 = 1.0;
+pub const DEADBAND: f32 = 0.0; // N, every command takes effect
+pub const DEAD_TIME: f32 = 0.0; // s, commands take effect immediately
@@ -0,0 +1,289 @@
+use crate::abs;
+use crate::default::*;
+#[cfg(feature = "alloc")]
+use alloc::collections::VecDeque;
+use core::time::Duration;
+
+#[derive(Clone)]
+pub struct Inductor {
+    pub pos: f32,
+    force: f32,
+    /// Requested force before the rate and magnitude limits
+    command: f32,
+    /// Force right after a reset, e.g. to cancel the gravitation
+    pub initial_force: f32,
+    /// Limits of the upward (positive) and the downward (negative) force, both positive
+    ///
+    /// An electromagnet can only pull the ball towards itself, so it has no downward force.
+    pub max_force_up: f32,
+    pub max_force_down: f32,
+    pub max_force_rate: f32,
+    /// Force per unit of controller output
+    pub gain: f32,
+    /// Requested forces of a smaller magnitude count as zero
+    ///
+    /// The deadband applies to the requested force, before the rate limit. So a command
+    /// inside the deadband ramps the actual force down to zero instead of stopping it
+    /// wherever it happens to be.
+    pub deadband: f32,
+    /// Transport delay between a request and its effect, before the rate limit
+    #[cfg(feature = "alloc")]
+    pub dead_time: Duration,
+    /// Requests with the time of [`Inductor::set_force`] that haven't taken effect yet
+    #[cfg(feature = "alloc")]
+    pending: VecDeque<(Duration, f32)>,
+    /// The limits have clipped the force on the last [`Inductor::set_force`]
+    magnitude_limited: bool,
+    rate_limited: bool,
+}
+
+impl Default for Inductor {
+    fn default() -> Self {
+        Self {
+            pos: IND_POS,
+            force: INITIAL_FORCE,
+            command: INITIAL_FORCE,
+            initial_force: INITIAL_FORCE,
+            max_force_up: MAX_FORCE,
+            max_force_down: MAX_FORCE,
+            max_force_rate: MAX_FORCE_RATE,
+            gain: ACTUATOR_GAIN,
+            deadband: DEADBAND,
+            #[cfg(feature = "alloc")]
+            dead_time: Duration::from_secs_f32(DEAD_TIME),
+            #[cfg(feature = "alloc")]
+            pending: VecDeque::new(),
+            magnitude_limited: false,
+            rate_limited: false,
+        }
+    }
+}
+
+impl Inductor {
+    pub fn reset(&mut self) {
+        self.pos = IND_POS;
+        self.force = self.initial_force;
+        self.command = self.initial_force;
+        self.magnitude_limited = false;
+        self.rate_limited = false;
+        #[cfg(feature = "alloc")]
+        self.pending.clear();
+    }
+
+    /// Sets the force immediately, bypassing the dead time and the rate limit but not the
+    /// magnitude limit
+    pub fn preload(&mut self, force: f32) {
+        self.force = force.clamp(self.min_force(), self.max_force_up);
+        self.command = force;
+        #[cfg(feature = "alloc")]
+        self.pending.clear();
+    }
+
+    pub fn force(&self) -> f32 {
+        self.force
+    }
+
+    /// Requested force that has taken effect on the last [`Inductor::set_force`], after the
+    /// dead time but before the rate and magnitude limits
+    pub fn command(&self) -> f32 {
+        self.command
+    }
+
+    /// Requests `force` at the time `now`
+    ///
+    /// Returns whether the rate or the magnitude limit clipped the requested force.
+    pub fn set_force(&mut self, force: f32, now: Duration, sampling_time: Duration) -> bool {
+        let dt = sampling_time.as_secs_f32();
+        let force = force * self.gain;
+        let force = if abs(force) < self.deadband {
+            0.0
+        } else {
+            force
+        };
+        // The last request that has taken effect holds until the next one does
+        let force = self.delay(force, now).unwrap_or(self.command);
+        self.command = force;
+        let delta = force - self.force;
+        let delta_rate = delta / dt;
+        self.rate_limited = abs(delta_rate) > self.max_force_rate;
+        let delta = if self.rate_limited {
+            let signum = if delta < 0.0 { -1.0 } else { 1.0 };
+            self.max_force_rate * signum * dt
+        } else {
+            delta
+        };
+        let unclamped = self.force + delta;
+        self.force = unclamped.clamp(self.min_force(), self.max_force_up);
+        self.magnitude_limited = self.force != unclamped;
+        self.rate_limited || self.magnitude_limited
+    }
+
+    /// Latest request that is at least `dead_time` old, `None` if there is no new one
+    #[cfg(feature = "alloc")]
+    fn delay(&mut self, force: f32, now: Duration) -> Option<f32> {
+        self.pending.push_back((now, force));
+        let mut effective = None;
+        while let Some(&(time, force)) = self.pending.front() {
+            if now.saturating_sub(time) < self.dead_time {
+                break;
+            }
+            effective = Some(force);
+            self.pending.pop_front();
+        }
+        effective
+    }
+
+    #[cfg(not(feature = "alloc"))]
+    fn delay(&mut self, force: f32, _now: Duration) -> Option<f32> {
+        Some(force)
+    }
+
+    /// The magnitude limit has clipped the force on the last [`Inductor::set_force`]
+    pub fn magnitude_limited(&self) -> bool {
+        self.magnitude_limited
+    }
+
+    /// The rate limit has clipped the force on the last [`Inductor::set_force`]
+    pub fn rate_limited(&self) -> bool {
+        self.rate_limited
+    }
+
+    /// The requested force is beyond the magnitude limit
+    pub fn saturated(&self) -> bool {
+        self.command > self.max_force_up || self.command < self.min_force()
+    }
+
+    fn min_force(&self) -> f32 {
+        -self.max_force_down
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DT: Duration = Duration::from_millis(10);
+
+    #[test]
+    fn limits() {
+        let limited = || Inductor {
+            max_force_up: 1.0,
+            max_force_down: 1.0,
+            max_force_rate: 50.0,
+            ..Default::default()
+        };
+        // Requested force, whether it's clipped, magnitude limited and rate limited
+        let cases = [
+            (0.5, false, false, false),
+            (1.5, true, true, false),
+            (-0.5, false, false, false),
+            (-1.5, true, true, false),
+        ];
+        for (force, clipped, magnitude, rate) in cases {
+            let mut inductor = limited();
+            inductor.max_force_rate = f32::INFINITY;
+            inductor.preload(0.0);
+            assert_eq!(
+                inductor.set_force(force, Duration::ZERO, DT),
+                clipped,
+                "{force}"
+            );
+            assert_eq!(inductor.magnitude_limited(), magnitude, "{force}");
+            assert_eq!(inductor.rate_limited(), rate, "{force}");
+        }
+
+        // At most 0.5 N per step
+        let mut inductor = limited();
+        inductor.preload(0.0);
+        assert!(inductor.set_force(0.8, Duration::ZERO, DT));
+        assert!(inductor.rate_limited() && !inductor.magnitude_limited());
+        assert!(inductor.set_force(-1.0, Duration::ZERO, DT));
+        assert!(inductor.rate_limited() && !inductor.magnitude_limited());
+
+        // Limited by both at once
+        let mut inductor = limited();
+        inductor.max_force_rate = 150.0;
+        inductor.preload(0.0);
+        assert!(inductor.set_force(5.0, Duration::ZERO, DT));
+        assert!(inductor.rate_limited() && inductor.magnitude_limited());
+        assert_eq!(inductor.force(), 1.0);
+
+        inductor.reset();
+        assert!(!inductor.rate_limited() && !inductor.magnitude_limited());
+    }
+
+    #[test]
+    fn asymmetric_limits() {
+        let mut inductor = Inductor {
+            max_force_up: 2.0,
+            max_force_down: 0.0,
+            ..Default::default()
+        };
+        for command in [-100.0, 1.0, -0.1, f32::MIN, 100.0, -5.0, 0.0, -1e-6] {
+            inductor.set_force(command, Duration::ZERO, DT);
+            assert!(inductor.force() >= 0.0, "{command}: {}", inductor.force());
+            assert!(inductor.force() <= 2.0, "{command}: {}", inductor.force());
+        }
+        inductor.preload(-3.0);
+        assert_eq!(inductor.force(), 0.0);
+        assert!(inductor.saturated());
+
+        inductor.max_force_down = 1.0;
+        inductor.max_force_rate = f32::INFINITY;
+        inductor.set_force(-3.0, Duration::ZERO, DT);
+        assert_eq!(inductor.force(), -1.0);
+        inductor.set_force(3.0, Duration::ZERO, DT);
+        assert_eq!(inductor.force(), 2.0);
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn dead_time() {
+        let mut inductor = Inductor {
+            dead_time: 3 * DT,
+            max_force_rate: f32::INFINITY,
+            ..Default::default()
+        };
+        inductor.set_force(0.0, Duration::ZERO, DT);
+        // Requested at 10 ms, so it takes effect at 40 ms
+        for step in 1..=3 {
+            inductor.set_force(5.0, step * DT, DT);
+            assert_eq!(inductor.force(), 0.0, "{step}");
+        }
+        inductor.set_force(5.0, 4 * DT, DT);
+        assert_eq!(inductor.force(), 5.0);
+
+        // Passes through without dead time
+        inductor.dead_time = Duration::ZERO;
+        inductor.set_force(-2.0, 5 * DT, DT);
+        assert_eq!(inductor.force(), -2.0);
+    }
+
+    #[test]
+    fn deadband() {
+        let mut inductor = Inductor {
+            deadband: 1.0,
+            max_force_rate: f32::INFINITY,
+            ..Default::default()
+        };
+        inductor.set_force(0.9, Duration::ZERO, DT);
+        assert_eq!(inductor.force(), 0.0);
+        inductor.set_force(1.1, Duration::ZERO, DT);
+        assert_eq!(inductor.force(), 1.1);
+        inductor.set_force(-0.9, Duration::ZERO, DT);
+        assert_eq!(inductor.force(), 0.0);
+    }
+
+    #[test]
+    fn deadband_ramps_down_with_the_rate_limit() {
+        let mut inductor = Inductor {
+            deadband: 1.0,
+            max_force_rate: 100.0,
+            ..Default::default()
+        };
+        inductor.preload(5.0);
+        inductor.set_force(0.5, Duration::ZERO, DT);
+        assert_eq!(inductor.command(), 0.0);
+        assert_eq!(inductor.force(), 4.0);
+    }
+}
@@ -0,0 +1,81 @@
+use crate::default::*;
+use crate::Controller;
+use core::time::Duration;
+
+/// Lead-lag compensator `gain * (1 + s * lead) / (1 + s * lag)` acting on the error,
+/// plus a constant bias as it has no integral to carry the weight of the ball
+#[derive(Clone)]
+pub struct LeadLag {
+    pub gain: f32,
+    /// Time constant of the zero [s]
+    pub lead: f32,
+    /// Time constant of the pole [s]
+    pub lag: f32,
+    pub bias: f32,
+
+    prev_error: Option<f32>,
+    /// Output of the compensator without the bias
+    output: f32,
+}
+
+impl Default for LeadLag {
+    fn default() -> Self {
+        Self {
+            gain: LEAD_LAG_GAIN,
+            lead: LEAD_TIME,
+            lag: LAG_TIME,
+            bias: LEAD_LAG_BIAS,
+            prev_error: None,
+            output: 0.0,
+        }
+    }
+}
+
+impl Controller for LeadLag {
+    /// Discretized with the backward Euler method
+    fn update(&mut self, pos: f32, target: f32, _saturated: bool, sample_time: Duration) -> f32 {
+        let dt = sample_time.as_secs_f32();
+        let error = target - pos;
+        let prev_error = self.prev_error.unwrap_or(error);
+        self.output = (self.lag * self.output
+            + self.gain * (error * dt + self.lead * (error - prev_error)))
+            / (self.lag + dt);
+        self.prev_error = Some(error);
+        self.output + self.bias
+    }
+
+    fn reset(&mut self) {
+        self.prev_error = None;
+        self.output = 0.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::abs;
+
+    const DT: Duration = Duration::from_millis(1);
+
+    #[test]
+    fn settles_at_the_static_gain() {
+        let mut lead_lag = LeadLag {
+            bias: 1.0,
+            ..Default::default()
+        };
+        let mut output = 0.0;
+        for _ in 0..10_000 {
+            output = lead_lag.update(0.0, 0.1, false, DT);
+        }
+        let expected = 0.1 * LEAD_LAG_GAIN + 1.0;
+        assert!(abs(output - expected) < 1e-3, "{output} != {expected}");
+    }
+
+    #[test]
+    fn lead_reacts_to_error_steps() {
+        let mut lead_lag = LeadLag::default();
+        lead_lag.update(0.0, 0.0, false, DT);
+        let kick = lead_lag.update(0.0, 0.1, false, DT);
+        assert!(kick > 0.1 * LEAD_LAG_GAIN, "no lead in {kick}");
+    }
+}
@@ -0,0 +1,39 @@
+//! Numerical core of the floating ball: controllers, ball and inductor.
+//!
+//! The crate is `no_std` and free of randomness so it can run on a microcontroller.
+//! Sensor noise is the business of the caller, which hands the (noisy) measurement
+//! to [`Pid::update`].
+//!
+//! The dead time of the [`Inductor`] queues the pending commands and needs the `alloc`
+//! feature, which is off by default.
+#![no_std]
+#![warn(clippy::all, rust_2018_idioms)]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+mod ball;
+mod bang_bang;
+mod controller;
+pub mod default;
+mod inductor;
+mod lead_lag;
+mod pid;
+
+pub use ball::Ball;
+pub use bang_bang::BangBang;
+pub use controller::Controller;
+pub use inductor::Inductor;
+pub use lead_lag::LeadLag;
+pub use pid::{
+    DerivativeMode, DerivativeSource, DerivativeStart, IntegralMode, Pid, ProportionalMode,
+};
+
+/// `f32::abs` is not available in `core`
+fn abs(val: f32) -> f32 {
+    if val < 0.0 {
+        -val
+    } else {
+        val
+    }
+}
@@ -0,0 +1,599 @@
+use crate::abs;
+use crate::default::*;
+use crate::Controller;
+use core::time::Duration;
+
+impl Default for Pid {
+    fn default() -> Self {
+        Self {
+            p: 0.0,
+            i: 0.0,
+            d: 0.0,
+            kp: KP,
+            ki: KI,
+            kd: KD,
+            kt: KT,
+            kff: KFF,
+            prev_pos: None,
+            prev_error: None,
+            measured_vel: None,
+            target: TARGET,
+            p_mode: P_MODE,
+            d_mode: D_MODE,
+            d_filter_tau: D_FILTER_TAU,
+            d_start: D_START,
+            d_source: D_SOURCE,
+            dead_zone: DEAD_ZONE,
+            i_mode: I_MODE,
+            i_limit: I_LIMIT,
+            i_band: I_BAND,
+            output_limit: OUTPUT_LIMIT,
+            conditional: CONDITIONAL_INTEGRATION,
+        }
+    }
+}
+
+/// Signal the proportional term acts on
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ProportionalMode {
+    /// `kp * (target - pos)`
+    OnError,
+    /// `-kp * pos`, so setpoint changes only act through the integral (I-PD)
+    OnMeasurement,
+}
+
+impl ProportionalMode {
+    pub const ALL: [ProportionalMode; 2] =
+        [ProportionalMode::OnError, ProportionalMode::OnMeasurement];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ProportionalMode::OnError => "Error",
+            ProportionalMode::OnMeasurement => "Measurement",
+        }
+    }
+}
+
+/// Signal the derivative term acts on
+///
+/// Both are positive when the ball approaches the target from below and they agree while
+/// the target is constant, as `error - prev_error = prev_pos - pos`.
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DerivativeMode {
+    /// `kd * (error - prev_error) / dt`, so a setpoint step causes a derivative kick
+    OnError,
+    /// `kd * (prev_pos - pos) / dt`, which ignores setpoint changes
+    OnMeasurement,
+}
+
+impl DerivativeMode {
+    pub const ALL: [DerivativeMode; 2] = [DerivativeMode::OnError, DerivativeMode::OnMeasurement];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            DerivativeMode::OnError => "Error",
+            DerivativeMode::OnMeasurement => "Measurement",
+        }
+    }
+}
+
+/// How the derivative starts when there is no previous measurement (e.g. after a reset)
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DerivativeStart {
+    /// The first sample only records the measurement and leaves `d` as it is.
+    /// After [`Pid::reset`] that is zero, otherwise the last value is held for a sample
+    Skip,
+    /// The previous measurement is seeded with the first one, so `d` is computed as zero
+    Seed,
+}
+
+impl DerivativeStart {
+    pub const ALL: [DerivativeStart; 2] = [DerivativeStart::Skip, DerivativeStart::Seed];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            DerivativeStart::Skip => "Skip",
+            DerivativeStart::Seed => "Seed",
+        }
+    }
+}
+
+/// Measurement the derivative term is computed from
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DerivativeSource {
+    /// Differences of the measured positions, which amplify the position noise by `1 / dt`
+    FromPosition,
+    /// Velocity of a dedicated sensor, see [`Pid::measure_velocity`]. Like
+    /// [`DerivativeMode::OnMeasurement`] it ignores setpoint changes
+    FromVelocitySensor,
+}
+
+impl DerivativeSource {
+    pub const ALL: [DerivativeSource; 2] = [
+        DerivativeSource::FromPosition,
+        DerivativeSource::FromVelocitySensor,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            DerivativeSource::FromPosition => "Position",
+            DerivativeSource::FromVelocitySensor => "Velocity sensor",
+        }
+    }
+}
+
+/// Unit of the integral gain
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum IntegralMode {
+    /// `i += ki * error` per update, so the integral action depends on the sampling rate
+    PerSample,
+    /// `i += ki * error * dt`, independent of the sampling rate
+    PerSecond,
+}
+
+impl IntegralMode {
+    pub const ALL: [IntegralMode; 2] = [IntegralMode::PerSample, IntegralMode::PerSecond];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            IntegralMode::PerSample => "Per sample",
+            IntegralMode::PerSecond => "Per second",
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Pid {
+    p: f32,
+    i: f32,
+    d: f32,
+
+    pub kp: f32,
+    pub ki: f32,
+    pub kd: f32,
+    /// Tracking gain of the back-calculation anti-windup
+    pub kt: f32,
+    /// Constant feed-forward added to the output, e.g. to carry the weight of the ball.
+    /// Being a setting, it survives [`Pid::reset`]
+    pub kff: f32,
+
+    prev_pos: Option<f32>,
+    prev_error: Option<f32>,
+    /// Latest velocity of the velocity sensor, `None` until the first measurement
+    measured_vel: Option<f32>,
+    pub target: f32,
+    pub p_mode: ProportionalMode,
+    pub d_mode: DerivativeMode,
+    /// Time constant [s] of the low-pass on the derivative term, zero disables it
+    pub d_filter_tau: f32,
+    pub d_start: DerivativeStart,
+    /// Without a velocity measurement the derivative falls back to the positions
+    pub d_source: DerivativeSource,
+    /// Errors up to this magnitude count as zero, so the controller rests near the target
+    pub dead_zone: f32,
+    pub i_mode: IntegralMode,
+    /// The integral is clamped to `[-i_limit, i_limit]`, `None` leaves it unbounded
+    pub i_limit: Option<f32>,
+    /// Integral separation: the integral only accumulates while the error is within
+    /// `[-i_band, i_band]` and holds its value outside. `None` integrates at any error
+    pub i_band: Option<f32>,
+    /// The command is clamped to `[-output_limit, output_limit]`, before and independent of
+    /// the limits of the actuator. `None` leaves it unclamped
+    pub output_limit: Option<f32>,
+    /// Conditional integration: the integral is frozen while the actuator is saturated
+    pub conditional: bool,
+}
+
+impl Pid {
+    pub fn reset(&mut self) {
+        self.p = 0.0;
+        self.i = 0.0;
+        self.d = 0.0;
+        self.prev_pos = None;
+        self.prev_error = None;
+        self.measured_vel = None;
+    }
+
+    /// Velocity of the dedicated sensor for the next update [m/s]
+    pub fn measure_velocity(&mut self, vel: f32) {
+        self.measured_vel = Some(vel);
+    }
+
+    /// Starts the integral at `i`, e.g. to hold a known load right away
+    pub fn preload(&mut self, i: f32) {
+        self.i = i;
+    }
+
+    /// Updates the controller with the measured position of the ball
+    ///
+    /// `saturated` tells whether the actuator had to clip the previous output, which
+    /// freezes the integral with conditional integration.
+    pub fn update(&mut self, pos: f32, saturated: bool, sample_time: Duration) {
+        let dt = sample_time.as_secs_f32();
+        let raw_error = self.target - pos;
+        let error = self.dead_zoned(raw_error);
+        self.p = match self.p_mode {
+            ProportionalMode::OnError => self.kp * error,
+            ProportionalMode::OnMeasurement => -self.kp * pos,
+        };
+        let in_band = self.i_band.map_or(true, |band| abs(error) <= band);
+        if !(self.conditional && saturated) && in_band {
+            self.i += match self.i_mode {
+                IntegralMode::PerSample => self.ki * error,
+                IntegralMode::PerSecond => self.ki * error * dt,
+            };
+            self.limit_integral();
+        }
+        // Change of the signal per sample, positive when the ball approaches from below
+        let delta = match (self.d_source, self.measured_vel) {
+            (DerivativeSource::FromVelocitySensor, Some(vel)) => Some(-vel * dt),
+            _ => {
+                let delta = match self.d_mode {
+                    DerivativeMode::OnError => self.prev_error.map(|prev| raw_error - prev),
+                    DerivativeMode::OnMeasurement => self.prev_pos.map(|prev| prev - pos),
+                };
+                match self.d_start {
+                    DerivativeStart::Skip => delta,
+                    DerivativeStart::Seed => delta.or(Some(0.0)),
+                }
+            }
+        };
+        // Without time passing there is no rate of change, only a division by zero
+        if let Some(delta) = delta.filter(|_| dt > 0.0) {
+            // Exponential moving average, which passes the raw derivative for `tau = 0`
+            let alpha = dt / (self.d_filter_tau + dt);
+            self.d = alpha * self.kd * delta / dt + (1.0 - alpha) * self.d;
+        }
+        self.prev_pos = Some(pos);
+        self.prev_error = Some(raw_error);
+    }
+
+    /// Changes the proportional gain without a jump of the output (bumpless transfer)
+    ///
+    /// The proportional term takes the new gain right away and the integral absorbs the
+    /// difference, which then fades as the integral keeps integrating.
+    pub fn set_kp(&mut self, kp: f32) {
+        let p = match (self.p_mode, self.prev_error, self.prev_pos) {
+            (ProportionalMode::OnError, Some(raw_error), _) => kp * self.dead_zoned(raw_error),
+            (ProportionalMode::OnMeasurement, _, Some(pos)) => -kp * pos,
+            _ => 0.0, // Not updated yet
+        };
+        self.i += self.p - p;
+        self.p = p;
+        self.kp = kp;
+    }
+
+    /// Changes the integral gain without a jump of the output (bumpless transfer)
+    ///
+    /// The integral term accumulates the gain of every update, so it stays as it is and
+    /// only the further integration uses the new gain.
+    pub fn set_ki(&mut self, ki: f32) {
+        self.ki = ki;
+    }
+
+    /// Changes the derivative gain without a jump of the output (bumpless transfer)
+    pub fn set_kd(&mut self, kd: f32) {
+        // The filtered derivative is linear in the gain, unless it has been zero
+        let d = if self.kd == 0.0 {
+            0.0
+        } else {
+            self.d * kd / self.kd
+        };
+        self.i += self.d - d;
+        self.d = d;
+        self.kd = kd;
+    }
+
+    fn dead_zoned(&self, error: f32) -> f32 {
+        if abs(error) <= self.dead_zone {
+            0.0
+        } else {
+            error
+        }
+    }
+
+    /// Back-calculation anti-windup: corrects the integral by `kt * (saturated - unsaturated)`
+    ///
+    /// Like the integral itself the correction is applied once per update.
+    pub fn back_calculate(&mut self, saturated: f32, unsaturated: f32) {
+        self.i += self.kt * (saturated - unsaturated);
+        self.limit_integral();
+    }
+
+    fn limit_integral(&mut self) {
+        if let Some(limit) = self.i_limit {
+            self.i = self.i.clamp(-limit, limit);
+        }
+    }
+
+    /// Proportional term of the last update
+    pub fn p(&self) -> f32 {
+        self.p
+    }
+
+    /// Integral term of the last update
+    pub fn i(&self) -> f32 {
+        self.i
+    }
+
+    /// Derivative term of the last update
+    pub fn d(&self) -> f32 {
+        self.d
+    }
+
+    /// Command of the last update, including the feed-forward
+    pub fn total(&self) -> f32 {
+        let total = self.p + self.i + self.d + self.kff;
+        match self.output_limit {
+            Some(limit) => total.clamp(-limit, limit),
+            None => total,
+        }
+    }
+}
+
+impl Controller for Pid {
+    fn update(&mut self, pos: f32, target: f32, saturated: bool, sample_time: Duration) -> f32 {
+        self.target = target;
+        Pid::update(self, pos, saturated, sample_time);
+        self.total()
+    }
+
+    fn measure_velocity(&mut self, vel: f32) {
+        Pid::measure_velocity(self, vel);
+    }
+
+    fn reset(&mut self) {
+        Pid::reset(self);
+    }
+
+    fn terms(&self) -> [f32; 3] {
+        [self.p, self.i, self.d]
+    }
+
+    fn back_calculate(&mut self, saturated: f32, unsaturated: f32) {
+        Pid::back_calculate(self, saturated, unsaturated);
+    }
+
+    fn preload(&mut self, output: f32) {
+        // The feed-forward already provides part of the output
+        Pid::preload(self, output - self.kff);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DT: Duration = Duration::from_millis(10);
+
+    #[test]
+    fn feed_forward_survives_reset() {
+        let mut pid = Pid {
+            kff: 5.0,
+            ..Default::default()
+        };
+        pid.update(pid.target, false, DT);
+        assert_eq!(pid.total(), 5.0);
+        pid.reset();
+        assert_eq!(pid.kff, 5.0);
+        Controller::preload(&mut pid, 8.0);
+        assert_eq!(pid.i, 3.0);
+    }
+
+    #[test]
+    fn bumpless_gain_changes() {
+        let mut pid = Pid::default();
+        pid.update(0.5, false, DT);
+        pid.update(0.45, false, DT);
+        for change in [Pid::set_kp as fn(&mut Pid, f32), Pid::set_ki, Pid::set_kd] {
+            let total = pid.total();
+            change(&mut pid, 3.0);
+            assert!((pid.total() - total).abs() < 1e-3, "{}", pid.total());
+        }
+        assert_eq!([pid.kp, pid.ki, pid.kd], [3.0; 3]);
+        assert_eq!(pid.p, 3.0 * (pid.target - 0.45));
+    }
+
+    #[test]
+    fn seeded_derivative_starts_at_zero() {
+        let mut pid = Pid {
+            d_start: DerivativeStart::Seed,
+            ..Default::default()
+        };
+        pid.update(0.5, false, DT);
+        assert_eq!(pid.d, 0.0);
+        pid.update(0.4, false, DT);
+        assert!(pid.d > 0.0);
+
+        pid.reset();
+        pid.update(0.1, false, DT);
+        assert_eq!(pid.d, 0.0, "derivative must not be stale after reset");
+        assert_eq!(pid.prev_pos, Some(0.1));
+    }
+
+    #[test]
+    fn skipped_derivative_holds_first_sample() {
+        let mut pid = Pid::default();
+        pid.update(0.5, false, DT);
+        assert_eq!(pid.d, 0.0);
+        pid.update(0.4, false, DT);
+        let d = pid.d;
+        assert!(d > 0.0);
+
+        pid.prev_pos = None;
+        pid.update(0.1, false, DT);
+        assert_eq!(pid.d, d);
+    }
+
+    #[test]
+    fn integral_limit() {
+        let mut pid = Pid {
+            i_limit: Some(2.0),
+            ..Default::default()
+        };
+        for _ in 0..100 {
+            pid.update(0.0, false, DT);
+            assert!(pid.i <= 2.0);
+        }
+        assert_eq!(pid.i, 2.0);
+
+        pid.reset();
+        assert_eq!(pid.i, 0.0);
+        assert_eq!(pid.i_limit, Some(2.0), "reset must keep the limit");
+    }
+
+    #[test]
+    fn output_limit() {
+        let mut pid = Pid {
+            kp: 1e6,
+            ki: 1e6,
+            kd: 1e6,
+            output_limit: Some(3.0),
+            ..Default::default()
+        };
+        for pos in [0.0, 2.0, -5.0, 0.5, 100.0, 0.0] {
+            let command = Controller::update(&mut pid, pos, TARGET, false, DT);
+            assert!(command.abs() <= 3.0, "{command}");
+        }
+        assert_eq!(Controller::update(&mut pid, -100.0, TARGET, false, DT), 3.0);
+        assert!(pid.p() > 3.0, "terms must stay unclamped");
+
+        pid.output_limit = None;
+        assert!(pid.total() > 3.0);
+    }
+
+    #[test]
+    fn zero_sample_time() {
+        let mut pid = Pid {
+            i_mode: IntegralMode::PerSecond,
+            ..Default::default()
+        };
+        for pos in [0.5, 0.4, 0.3] {
+            let output = Controller::update(&mut pid, pos, TARGET, false, Duration::ZERO);
+            assert!(output.is_finite(), "{output}");
+        }
+        assert_eq!(pid.d, 0.0);
+        assert_eq!(pid.i, 0.0);
+        assert_eq!(pid.prev_pos, Some(0.3));
+    }
+
+    #[test]
+    fn integral_separation() {
+        let mut pid = Pid {
+            i_band: Some(0.05),
+            ..Default::default()
+        };
+        // A large step only integrates once the ball is within the band
+        for pos in [0.0, 0.2, 0.4, 0.5] {
+            pid.update(pos, false, DT);
+            assert_eq!(pid.i, 0.0);
+        }
+        pid.update(TARGET - 0.04, false, DT);
+        let i = pid.i;
+        assert!(i > 0.0);
+        // Leaving the band holds the integral
+        pid.update(0.3, false, DT);
+        assert_eq!(pid.i, i);
+    }
+
+    #[test]
+    fn conditional_integration_freezes_while_saturated() {
+        let mut pid = Pid {
+            conditional: true,
+            ..Default::default()
+        };
+        pid.update(0.0, false, DT);
+        let i = pid.i;
+        assert!(i > 0.0);
+        pid.update(0.0, true, DT);
+        assert_eq!(pid.i, i);
+        assert!(pid.p > 0.0, "proportional term must still update");
+    }
+
+    #[test]
+    fn derivative_kick_only_on_error() {
+        for d_mode in DerivativeMode::ALL {
+            let mut pid = Pid {
+                d_mode,
+                ..Default::default()
+            };
+            pid.update(0.5, false, DT);
+            pid.update(0.5, false, DT);
+            assert_eq!(pid.d, 0.0);
+            pid.target += 0.1;
+            pid.update(0.5, false, DT);
+            match d_mode {
+                DerivativeMode::OnError => assert!(pid.d > 0.0, "no kick"),
+                DerivativeMode::OnMeasurement => assert_eq!(pid.d, 0.0),
+            }
+            // Both agree while the target is constant
+            pid.update(0.4, false, DT);
+            assert!(
+                (pid.d - KD * 0.1 / DT.as_secs_f32()).abs() < 1e-2,
+                "{}",
+                pid.d
+            );
+        }
+    }
+
+    #[test]
+    fn derivative_from_velocity_sensor() {
+        let mut pid = Pid {
+            d_source: DerivativeSource::FromVelocitySensor,
+            ..Default::default()
+        };
+        // Falls back to the positions without a measurement
+        pid.update(0.5, false, DT);
+        pid.update(0.4, false, DT);
+        assert!((pid.d - KD * 0.1 / DT.as_secs_f32()).abs() < 1e-2);
+
+        // The measured velocity applies from the first sample after a reset
+        pid.reset();
+        pid.measure_velocity(-2.0);
+        pid.update(0.5, false, DT);
+        assert!((pid.d - KD * 2.0).abs() < 1e-3, "{}", pid.d);
+        pid.target += 0.1;
+        pid.update(0.5, false, DT);
+        assert!((pid.d - KD * 2.0).abs() < 1e-3, "no kick on setpoint steps");
+    }
+
+    #[test]
+    fn derivative_filter() {
+        let measurements = [0.5, 0.4, 0.5, 0.4, 0.5];
+        let run = |d_filter_tau| {
+            let mut pid = Pid {
+                d_filter_tau,
+                ..Default::default()
+            };
+            let mut peak: f32 = 0.0;
+            for pos in measurements {
+                pid.update(pos, false, DT);
+                peak = peak.max(abs(pid.d));
+            }
+            pid.reset();
+            assert_eq!(pid.d, 0.0, "reset must clear the filter");
+            peak
+        };
+        assert_eq!(run(0.0), KD * 0.1 / DT.as_secs_f32());
+        assert!(run(0.1) < run(0.0) / 5.0);
+    }
+
+    #[test]
+    fn dead_zone_ignores_small_errors() {
+        let mut pid = Pid {
+            dead_zone: 0.01,
+            ..Default::default()
+        };
+        pid.update(TARGET - 0.005, false, DT);
+        assert_eq!(pid.p, 0.0);
+        assert_eq!(pid.i, 0.0);
+        pid.update(TARGET - 0.02, false, DT);
+        assert!(pid.p > 0.0);
+        assert!(pid.i > 0.0);
+    }
+}
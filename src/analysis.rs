@@ -0,0 +1,149 @@
+use crate::default::*;
+use crate::sim::{Message, Simulation, TargetProfile};
+use std::f64::consts::TAU;
+use web_time::Duration;
+
+/// Frequencies [Hz] of the analysis, spaced logarithmically up to a quarter of the sampling rate
+pub fn frequencies(sampling_rate: u32) -> Vec<f32> {
+    let max = BODE_MAX_FREQ.min(sampling_rate as f32 / 4.0);
+    let (from, to) = (BODE_MIN_FREQ.log10(), max.log10());
+    (0..BODE_POINTS)
+        .map(|i| 10f32.powf(from + (to - from) * i as f32 / (BODE_POINTS - 1) as f32))
+        .collect()
+}
+
+/// Frequency response of the closed loop from the target to the position of the ball
+///
+/// `sim` follows a sine around its current target, starting from its current state at every
+/// frequency [Hz]. Returns the frequency, the gain [dB] and the phase [°] for each of them,
+/// with the phase unwrapped along the frequencies.
+pub fn bode(sim: &mut Simulation, freqs: &[f32], sampling_time: Duration) -> Vec<(f32, f32, f32)> {
+    // Without noise, so only the target makes the ball move
+    let center = sim.step(0, sampling_time).target;
+    for msg in [
+        Message::Noise(0.0),
+        Message::VelocityNoise(0.0),
+        Message::ProcessNoise(0.0),
+        Message::OutlierProbability(0.0),
+        Message::Reference(false),
+        Message::HoldBall(false),
+        Message::Profile(Vec::new()),
+        Message::TargetProfile(TargetProfile::Constant(center)),
+    ] {
+        sim.config(msg);
+    }
+    let start = sim.clone();
+
+    let mut prev_phase = 0.0;
+    freqs
+        .iter()
+        .map(|&freq| {
+            *sim = start.clone();
+            let (gain, phase) = excite(freq, sampling_time, |input| {
+                sim.config(Message::Target(center + BODE_AMPLITUDE * input));
+                (sim.step(1, sampling_time).pos - center) / BODE_AMPLITUDE
+            });
+            // The phase closest to the one of the previous frequency
+            let phase = phase - 360.0 * ((phase - prev_phase) / 360.0).round();
+            if phase.is_finite() {
+                prev_phase = phase;
+            }
+            (freq, 20.0 * gain.log10(), phase)
+        })
+        .collect()
+}
+
+/// Gain and phase [°] of `system` at `freq` [Hz]
+///
+/// `system` maps the input `sin(2π·freq·t)` at every sample to its output one sampling time
+/// later, like a step of the simulation. The first cycles, but at least `BODE_SETTLE_TIME`,
+/// are discarded as transient. The next `BODE_CYCLES` are correlated with the frequency,
+/// which is a single bin of the discrete Fourier transform.
+fn excite(freq: f32, sampling_time: Duration, mut system: impl FnMut(f32) -> f32) -> (f32, f32) {
+    let dt = sampling_time.as_secs_f64();
+    let period = 1.0 / freq as f64;
+    let settle = BODE_SETTLE_TIME.max(BODE_SETTLE_CYCLES as f32 * period as f32) as f64;
+    let settle = (settle / dt).round() as usize;
+    let samples = ((BODE_CYCLES as f64 * period / dt).round() as usize).max(1);
+
+    // Real and imaginary part of the transforms
+    let omega = TAU * freq as f64;
+    let mut input = [0.0; 2];
+    let mut output = [0.0; 2];
+    let correlate = |sums: &mut [f64; 2], val: f64, t: f64| {
+        let (sin, cos) = (omega * t).sin_cos();
+        sums[0] += val * cos;
+        sums[1] -= val * sin;
+    };
+    for k in 0..settle + samples {
+        let t = k as f64 * dt;
+        let val = (omega * t).sin();
+        let response = system(val as f32) as f64;
+        if k >= settle {
+            correlate(&mut input, val, t);
+            correlate(&mut output, response, t + dt);
+        }
+    }
+
+    let gain = output[0].hypot(output[1]) / input[0].hypot(input[1]);
+    let phase = (output[1].atan2(output[0]) - input[1].atan2(input[0])).to_degrees();
+    // Within (-180°, 180°]
+    let phase = phase - 360.0 * ((phase - 180.0) / 360.0).ceil();
+    (gain as f32, phase as f32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn first_order_low_pass() {
+        let sampling_time = Duration::from_millis(1);
+        let tau = 0.1;
+        // Exact discretization of `tau·y' = u - y` with the input held over a sample
+        let decay = (-sampling_time.as_secs_f32() / tau).exp();
+        for freq in [0.2, 1.0, 1.6, 5.0] {
+            let mut y = 0.0;
+            let (gain, phase) = excite(freq, sampling_time, |u| {
+                y = decay * y + (1.0 - decay) * u;
+                y
+            });
+            let omega_tau = std::f32::consts::TAU * freq * tau;
+            let expected_gain = 1.0 / (1.0 + omega_tau.powi(2)).sqrt();
+            let expected_phase = -omega_tau.atan().to_degrees();
+            assert!(
+                (gain / expected_gain - 1.0).abs() < 0.01,
+                "{freq} Hz: {gain}"
+            );
+            assert!((phase - expected_phase).abs() < 2.0, "{freq} Hz: {phase}°");
+        }
+    }
+
+    #[test]
+    fn closed_loop_follows_slow_targets() {
+        let sampling_time = Duration::from_secs_f32(1.0 / SAMPLING_RATE as f32);
+        let mut sim = Simulation::from_seed(42);
+        sim.config(Message::Reset);
+        sim.run(Duration::from_secs(5), sampling_time);
+        let freqs = [0.1, 1.0, 10.0];
+        let response = bode(&mut sim, &freqs, sampling_time);
+        let (_, gain, phase) = response[0];
+        assert!(
+            gain.abs() < 1.0 && phase.abs() < 10.0,
+            "{gain} dB, {phase}°"
+        );
+        // Lags ever more behind faster targets, until it hardly follows
+        assert!(response.windows(2).all(|pair| pair[1].2 < pair[0].2));
+        assert!(response[2].1 < -10.0, "{response:?}");
+    }
+
+    #[test]
+    fn frequencies_are_logarithmic() {
+        let freqs = frequencies(SAMPLING_RATE);
+        assert_eq!(freqs.len(), BODE_POINTS);
+        assert!((freqs[0] - BODE_MIN_FREQ).abs() < 1e-6);
+        assert!(freqs[BODE_POINTS - 1] <= SAMPLING_RATE as f32 / 4.0 + 1e-3);
+        let ratio = freqs[1] / freqs[0];
+        assert!(freqs.windows(2).all(|f| (f[1] / f[0] - ratio).abs() < 1e-3));
+    }
+}
@@ -1,50 +1,139 @@
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::ops::RangeInclusive;
 use web_time::{Duration, Instant};
 
-use crate::sim::{Message, Simulation};
+use crate::analysis::{bode, frequencies};
+use crate::config::Config;
+use crate::events::EventLog;
+use crate::metrics::{detect_instability, step_metrics, StepMetrics};
+use crate::monte_carlo::{MonteCarlo, Summary};
+use crate::ring_buffer::RingBuffer;
+use crate::scenario::Scenario;
+use crate::screenshot;
+use crate::sim::{
+    ControllerKind, CouplingModel, Data, DerivativeMode, DerivativeSource, DerivativeStart,
+    IntegralMode, Message, NoiseKind, ProportionalMode, Setpoint, SimSnapshot, Simulation,
+    TargetProfile,
+};
+use crate::stats::RunningStats;
+use crate::stepper::{Pace, Stepper};
+use crate::tune::{ziegler_nichols, PidGains, Preset};
 use eframe::egui;
-use egui::{Color32, DragValue, Pos2, Vec2};
-use egui_plot::{Corner, HLine, Legend, Line, Plot, PlotPoints};
+use egui::{Align2, Color32, DragValue, Pos2, Vec2};
+use egui_plot::{
+    uniform_grid_spacer, Corner, GridInput, HLine, Legend, Line, LineStyle, Plot, PlotPoint,
+    PlotPoints, PlotUi, Points, Text,
+};
 
 use crate::default::*;
 
-struct Time {
-    /// Point in time when the GUI has started
-    gui: Instant,
-    /// Duration of time that has elapsed in the simulation
-    sim: Duration,
+/// Keeps a flag raised for `LIMIT_LATCH` seconds, so a short pulse is visible
+#[derive(Default)]
+struct Latch {
+    raised: Option<Instant>,
 }
 
-impl Default for Time {
-    fn default() -> Self {
-        Self {
-            gui: Instant::now(),
-            sim: Duration::ZERO,
+impl Latch {
+    fn update(&mut self, active: bool) {
+        if active {
+            self.raised = Some(Instant::now());
         }
     }
-}
-
-impl Time {
-    /// Calculates the number of steps the simulation should step forward
-    fn step(&mut self, sampling_time: Duration) -> u32 {
-        let sim_dt = self.gui.elapsed() - self.sim; // Delta of real time and GUI time
-        let steps = sim_dt.as_secs_f32().div_euclid(sampling_time.as_secs_f32()); // Whole number
-        self.sim += sampling_time.mul_f32(steps);
 
-        steps as u32
+    fn active(&self) -> bool {
+        self.raised
+            .is_some_and(|raised| raised.elapsed().as_secs_f32() < LIMIT_LATCH)
     }
 }
 
+/// Label that lights up while `active`
+fn indicator(ui: &mut egui::Ui, text: &str, active: bool) -> egui::Response {
+    let text = egui::RichText::new(text).monospace().strong();
+    let text = if active {
+        text.color(Color32::WHITE).background_color(Color32::RED)
+    } else {
+        text.weak()
+    };
+    ui.label(text)
+}
+
 struct Input {
+    controller: Cache<ControllerKind>,
+    lead_lag_gain: Cache<f32>,
+    lead_time: Cache<f32>,
+    lag_time: Cache<f32>,
+    lead_lag_bias: Cache<f32>,
+    bang_bang_force: Cache<f32>,
+    bang_bang_hysteresis: Cache<f32>,
     kp: Cache<f32>,
     ki: Cache<f32>,
     kd: Cache<f32>,
+    kt: Cache<f32>,
+    kff: Cache<f32>,
+    conditional: Cache<bool>,
+    /// `None` for no limit
+    i_limit: Cache<Option<f32>>,
+    /// `None` integrates at any error
+    i_band: Cache<Option<f32>>,
+    /// `None` leaves the command unclamped
+    output_limit: Cache<Option<f32>>,
+    p_mode: Cache<ProportionalMode>,
+    d_filter_tau: Cache<f32>,
+    d_mode: Cache<DerivativeMode>,
+    d_start: Cache<DerivativeStart>,
+    d_source: Cache<DerivativeSource>,
+    i_mode: Cache<IntegralMode>,
     target: Cache<f32>,
+    target_profile: Cache<TargetProfile>,
+    dead_zone: Cache<f32>,
     sampling_rate: Cache<u32>,
+    sub_steps: Cache<u32>,
+    control_divider: Cache<u32>,
     noise: Cache<f32>,
-    gravitation: Cache<f32>,
-    max_force: Cache<f32>,
+    noise_kind: Cache<NoiseKind>,
+    vel_noise: Cache<f32>,
+    /// Seed of reproducible noise, `None` for a random one
+    noise_seed: Cache<Option<u64>>,
+    /// Every restart repeats the noise of the current run
+    freeze_noise: Cache<bool>,
+    sensor_tau: Cache<f32>,
+    sensor_delay: Cache<f32>,
+    sensor_resolution: Cache<f32>,
+    outlier_probability: Cache<f32>,
+    outlier_magnitude: Cache<f32>,
+    median_window: Cache<u32>,
+    init_pos: Cache<f32>,
+    init_vel: Cache<f32>,
+    random_start: Cache<bool>,
+    pos_spread: Cache<f32>,
+    vel_spread: Cache<f32>,
+    gravity: Cache<f32>,
+    process_noise: Cache<f32>,
+    coupling: Cache<CouplingModel>,
+    softening: Cache<f32>,
+    mass: Cache<f32>,
+    floor: Cache<f32>,
+    ceiling: Cache<f32>,
+    restitution: Cache<f32>,
+    /// `None` for no limit
+    max_velocity: Cache<Option<f32>>,
+    max_force_up: Cache<f32>,
+    max_force_down: Cache<f32>,
     max_force_rate: Cache<f32>,
+    initial_force: Cache<f32>,
+    actuator_gain: Cache<f32>,
+    deadband: Cache<f32>,
+    dead_time: Cache<f32>,
+    invert_output: Cache<bool>,
+    output_cutoff: Cache<f32>,
+    engage_delay: Cache<f32>,
     hold_ball: Cache<bool>,
+    smooth_release: Cache<bool>,
+    preload: Cache<bool>,
+    follow_target: Cache<bool>,
+    follow_rate: Cache<f32>,
+    reference: Cache<bool>,
 }
 
 struct Cache<T: PartialEq + Clone> {
@@ -76,313 +165,4100 @@ impl<T: PartialEq + Clone> Cache<T> {
     fn get(&self) -> T {
         self.val.clone()
     }
+
+    /// Takes over a value that has changed elsewhere, without reporting it as changed
+    fn sync(&mut self, val: T) {
+        self.val = val.clone();
+        self.prev = val;
+    }
 }
 
 impl Default for Input {
     fn default() -> Self {
         Self {
+            controller: Cache::new(CONTROLLER),
+            lead_lag_gain: Cache::new(LEAD_LAG_GAIN),
+            lead_time: Cache::new(LEAD_TIME),
+            lag_time: Cache::new(LAG_TIME),
+            lead_lag_bias: Cache::new(LEAD_LAG_BIAS),
+            bang_bang_force: Cache::new(BANG_BANG_FORCE),
+            bang_bang_hysteresis: Cache::new(BANG_BANG_HYSTERESIS),
             kp: Cache::new(KP),
             ki: Cache::new(KI),
             kd: Cache::new(KD),
+            kt: Cache::new(KT),
+            kff: Cache::new(KFF),
+            conditional: Cache::new(CONDITIONAL_INTEGRATION),
+            i_limit: Cache::new(I_LIMIT),
+            i_band: Cache::new(I_BAND),
+            output_limit: Cache::new(OUTPUT_LIMIT),
+            p_mode: Cache::new(P_MODE),
+            d_filter_tau: Cache::new(D_FILTER_TAU),
+            d_mode: Cache::new(D_MODE),
+            d_start: Cache::new(D_START),
+            d_source: Cache::new(D_SOURCE),
+            i_mode: Cache::new(I_MODE),
             target: Cache::new(TARGET),
+            target_profile: Cache::new(TargetProfile::Constant(TARGET)),
+            dead_zone: Cache::new(DEAD_ZONE),
             sampling_rate: Cache::new(SAMPLING_RATE),
+            sub_steps: Cache::new(SUB_STEPS),
+            control_divider: Cache::new(CONTROL_DIVIDER),
             noise: Cache::new(NOISE),
-            gravitation: Cache::new(GRAVITATION),
-            max_force: Cache::new(MAX_FORCE),
+            noise_kind: Cache::new(NOISE_KIND),
+            vel_noise: Cache::new(VEL_NOISE),
+            noise_seed: Cache::new(SEEDED_NOISE.then_some(NOISE_SEED)),
+            freeze_noise: Cache::new(FREEZE_NOISE),
+            sensor_tau: Cache::new(SENSOR_TAU),
+            sensor_delay: Cache::new(SENSOR_DELAY),
+            sensor_resolution: Cache::new(SENSOR_RESOLUTION),
+            outlier_probability: Cache::new(OUTLIER_PROBABILITY),
+            outlier_magnitude: Cache::new(OUTLIER_MAGNITUDE),
+            median_window: Cache::new(MEDIAN_WINDOW as u32),
+            init_pos: Cache::new(BALL_POS),
+            init_vel: Cache::new(BALL_VEL),
+            random_start: Cache::new(RANDOM_START),
+            pos_spread: Cache::new(POS_SPREAD),
+            vel_spread: Cache::new(VEL_SPREAD),
+            gravity: Cache::new(GRAVITY),
+            process_noise: Cache::new(PROCESS_NOISE),
+            coupling: Cache::new(COUPLING),
+            softening: Cache::new(FORCE_SOFTENING),
+            mass: Cache::new(MASS),
+            floor: Cache::new(FLOOR),
+            ceiling: Cache::new(CEILING),
+            restitution: Cache::new(RESTITUTION),
+            max_velocity: Cache::new(None),
+            max_force_up: Cache::new(MAX_FORCE),
+            max_force_down: Cache::new(MAX_FORCE),
             max_force_rate: Cache::new(MAX_FORCE_RATE),
+            initial_force: Cache::new(INITIAL_FORCE),
+            actuator_gain: Cache::new(ACTUATOR_GAIN),
+            deadband: Cache::new(DEADBAND),
+            dead_time: Cache::new(DEAD_TIME),
+            invert_output: Cache::new(INVERT_OUTPUT),
+            output_cutoff: Cache::new(OUTPUT_CUTOFF),
+            engage_delay: Cache::new(ENGAGE_DELAY),
             hold_ball: Cache::new(HOLD_BALL),
+            smooth_release: Cache::new(SMOOTH_RELEASE),
+            preload: Cache::new(PRELOAD),
+            follow_target: Cache::new(FOLLOW_TARGET),
+            follow_rate: Cache::new(FOLLOW_RATE),
+            reference: Cache::new(REFERENCE),
         }
     }
 }
 
 impl Input {
-    fn update(&mut self, sim: &mut Simulation) {
+    /// Takes over `gains` with the integral gain per second, converted to the integral mode
+    fn set_gains(&mut self, gains: &PidGains, sampling_time: Duration) {
+        self.kp.val = gains.kp;
+        self.ki.val = match self.i_mode.get() {
+            IntegralMode::PerSample => gains.ki * sampling_time.as_secs_f32(),
+            IntegralMode::PerSecond => gains.ki,
+        };
+        self.kd.val = gains.kd;
+    }
+
+    /// Takes over the configuration, which reaches the simulation with the next update
+    fn apply(&mut self, config: &Config) {
+        self.controller.val = config.controller;
+        self.lead_lag_gain.val = config.lead_lag_gain;
+        self.lead_time.val = config.lead_time;
+        self.lag_time.val = config.lag_time;
+        self.lead_lag_bias.val = config.lead_lag_bias;
+        self.bang_bang_force.val = config.bang_bang_force;
+        self.bang_bang_hysteresis.val = config.bang_bang_hysteresis;
+        self.kp.val = config.kp;
+        self.ki.val = config.ki;
+        self.kd.val = config.kd;
+        self.kt.val = config.kt;
+        self.kff.val = config.kff;
+        self.conditional.val = config.conditional_integration;
+        self.i_limit.val = config.i_limit;
+        self.i_band.val = config.i_band;
+        self.output_limit.val = config.output_limit;
+        self.p_mode.val = config.p_mode;
+        self.d_filter_tau.val = config.d_filter_tau;
+        self.d_mode.val = config.d_mode;
+        self.d_start.val = config.d_start;
+        self.d_source.val = config.d_source;
+        self.i_mode.val = config.i_mode;
+        self.preload.val = config.preload;
+        self.target.val = config.target;
+        self.target_profile.val = config.target_profile;
+        self.dead_zone.val = config.dead_zone;
+        self.sampling_rate.val = config.sampling_rate.max(1);
+        self.sub_steps.val = config.sub_steps;
+        self.control_divider.val = config.control_divider;
+        self.noise.val = config.noise;
+        self.noise_kind.val = config.noise_kind;
+        self.vel_noise.val = config.vel_noise;
+        self.sensor_tau.val = config.sensor_tau;
+        self.sensor_delay.val = config.sensor_delay;
+        self.sensor_resolution.val = config.sensor_resolution;
+        self.outlier_probability.val = config.outlier_probability;
+        self.outlier_magnitude.val = config.outlier_magnitude;
+        self.median_window.val = config.median_window;
+        self.init_pos.val = config.init_pos;
+        self.init_vel.val = config.init_vel;
+        self.random_start.val = config.random_start;
+        self.pos_spread.val = config.pos_spread;
+        self.vel_spread.val = config.vel_spread;
+        self.gravity.val = config.gravity;
+        self.process_noise.val = config.process_noise;
+        self.coupling.val = config.coupling;
+        self.softening.val = config.softening;
+        self.mass.val = config.mass;
+        self.floor.val = config.floor;
+        self.ceiling.val = config.ceiling;
+        self.restitution.val = config.restitution;
+        self.max_velocity.val = config.max_velocity;
+        self.max_force_up.val = config.max_force_up;
+        self.max_force_down.val = config.max_force_down;
+        self.max_force_rate.val = config.max_force_rate;
+        self.initial_force.val = config.initial_force;
+        self.actuator_gain.val = config.actuator_gain;
+        self.deadband.val = config.deadband;
+        self.dead_time.val = config.dead_time;
+        self.invert_output.val = config.invert_output;
+        self.output_cutoff.val = config.output_cutoff;
+        self.engage_delay.val = config.engage_delay;
+        self.follow_target.val = config.follow_target;
+        self.follow_rate.val = config.follow_rate;
+    }
+
+    fn config(&self) -> Config {
+        Config {
+            controller: self.controller.get(),
+            lead_lag_gain: self.lead_lag_gain.get(),
+            lead_time: self.lead_time.get(),
+            lag_time: self.lag_time.get(),
+            lead_lag_bias: self.lead_lag_bias.get(),
+            bang_bang_force: self.bang_bang_force.get(),
+            bang_bang_hysteresis: self.bang_bang_hysteresis.get(),
+            kp: self.kp.get(),
+            ki: self.ki.get(),
+            kd: self.kd.get(),
+            kt: self.kt.get(),
+            kff: self.kff.get(),
+            conditional_integration: self.conditional.get(),
+            i_limit: self.i_limit.get(),
+            i_band: self.i_band.get(),
+            output_limit: self.output_limit.get(),
+            p_mode: self.p_mode.get(),
+            d_filter_tau: self.d_filter_tau.get(),
+            d_mode: self.d_mode.get(),
+            d_start: self.d_start.get(),
+            d_source: self.d_source.get(),
+            i_mode: self.i_mode.get(),
+            preload: self.preload.get(),
+            target: self.target.get(),
+            target_profile: self.target_profile.get(),
+            dead_zone: self.dead_zone.get(),
+            sampling_rate: self.sampling_rate.get(),
+            sub_steps: self.sub_steps.get(),
+            control_divider: self.control_divider.get(),
+            noise: self.noise.get(),
+            noise_kind: self.noise_kind.get(),
+            vel_noise: self.vel_noise.get(),
+            sensor_tau: self.sensor_tau.get(),
+            sensor_delay: self.sensor_delay.get(),
+            sensor_resolution: self.sensor_resolution.get(),
+            outlier_probability: self.outlier_probability.get(),
+            outlier_magnitude: self.outlier_magnitude.get(),
+            median_window: self.median_window.get(),
+            init_pos: self.init_pos.get(),
+            init_vel: self.init_vel.get(),
+            random_start: self.random_start.get(),
+            pos_spread: self.pos_spread.get(),
+            vel_spread: self.vel_spread.get(),
+            gravity: self.gravity.get(),
+            gravitation: None,
+            process_noise: self.process_noise.get(),
+            coupling: self.coupling.get(),
+            softening: self.softening.get(),
+            mass: self.mass.get(),
+            floor: self.floor.get(),
+            ceiling: self.ceiling.get(),
+            restitution: self.restitution.get(),
+            max_velocity: self.max_velocity.get(),
+            max_force_up: self.max_force_up.get(),
+            max_force_down: self.max_force_down.get(),
+            max_force_rate: self.max_force_rate.get(),
+            initial_force: self.initial_force.get(),
+            actuator_gain: self.actuator_gain.get(),
+            pull_only: None,
+            deadband: self.deadband.get(),
+            dead_time: self.dead_time.get(),
+            invert_output: self.invert_output.get(),
+            output_cutoff: self.output_cutoff.get(),
+            engage_delay: self.engage_delay.get(),
+            follow_target: self.follow_target.get(),
+            follow_rate: self.follow_rate.get(),
+        }
+    }
+
+    /// Sends the changed values to `sim`
+    fn update(&mut self, sim: &mut Stepper) {
+        self.send(|msg| sim.config(msg));
+    }
+
+    /// Passes a message for every changed value to `send`
+    fn send(&mut self, mut send: impl FnMut(Message)) {
+        // Control law
+        if let Some(val) = self.controller.changed() {
+            send(Message::ControllerKind(val));
+        }
+        if let Some(val) = self.lead_lag_gain.changed() {
+            send(Message::LeadLagGain(val));
+        }
+        if let Some(val) = self.lead_time.changed() {
+            send(Message::LeadTime(val));
+        }
+        if let Some(val) = self.lag_time.changed() {
+            send(Message::LagTime(val));
+        }
+        if let Some(val) = self.lead_lag_bias.changed() {
+            send(Message::LeadLagBias(val));
+        }
+        if let Some(val) = self.bang_bang_force.changed() {
+            send(Message::BangBangForce(val));
+        }
+        if let Some(val) = self.bang_bang_hysteresis.changed() {
+            send(Message::BangBangHysteresis(val));
+        }
+
         // PID constants
         if let Some(val) = self.kp.changed() {
-            sim.config(Message::Kp(val));
+            send(Message::Kp(val));
         }
         if let Some(val) = self.ki.changed() {
-            sim.config(Message::Ki(val));
+            send(Message::Ki(val));
         }
         if let Some(val) = self.kd.changed() {
-            sim.config(Message::Kd(val));
+            send(Message::Kd(val));
+        }
+        if let Some(val) = self.kff.changed() {
+            send(Message::FeedForward(val));
+        }
+        if let Some(val) = self.kt.changed() {
+            send(Message::Kt(val));
+        }
+        if let Some(val) = self.conditional.changed() {
+            send(Message::ConditionalIntegration(val));
+        }
+        if let Some(val) = self.i_limit.changed() {
+            send(Message::IntegralLimit(val.unwrap_or(f32::INFINITY)));
+        }
+        if let Some(val) = self.i_band.changed() {
+            send(Message::IntegralBand(val.unwrap_or(f32::INFINITY)));
+        }
+        if let Some(val) = self.output_limit.changed() {
+            send(Message::OutputLimit(val.unwrap_or(f32::INFINITY)));
+        }
+        if let Some(val) = self.p_mode.changed() {
+            send(Message::ProportionalMode(val));
+        }
+        if let Some(val) = self.d_filter_tau.changed() {
+            send(Message::DFilter(val));
+        }
+        if let Some(val) = self.d_mode.changed() {
+            send(Message::DerivativeMode(val));
+        }
+        if let Some(val) = self.d_start.changed() {
+            send(Message::DerivativeStart(val));
+        }
+        if let Some(val) = self.d_source.changed() {
+            send(Message::DerivativeSource(val));
+        }
+        if let Some(val) = self.i_mode.changed() {
+            send(Message::IntegralMode(val));
+        }
+        if let Some(val) = self.preload.changed() {
+            send(Message::Preload(val));
         }
 
-        // PID Target
+        // PID Target, the profile first as a constant one sets the target
+        if let Some(val) = self.target_profile.changed() {
+            send(Message::TargetProfile(val));
+        }
         if let Some(val) = self.target.changed() {
-            sim.config(Message::Target(val));
+            send(Message::Target(val));
+            if let TargetProfile::Constant(_) = self.target_profile.get() {
+                self.target_profile.sync(TargetProfile::Constant(val));
+            }
+        }
+        if let Some(val) = self.dead_zone.changed() {
+            send(Message::ErrorDeadZone(val));
+        }
+
+        // Physics sub-steps
+        if let Some(val) = self.sub_steps.changed() {
+            send(Message::SubSteps(val));
+        }
+
+        // Controller rate
+        if let Some(val) = self.control_divider.changed() {
+            send(Message::ControlDivider(val));
         }
 
         // Sensor noise
         if let Some(val) = self.noise.changed() {
-            sim.config(Message::Noise(val));
+            send(Message::Noise(val));
+        }
+        if let Some(val) = self.noise_kind.changed() {
+            send(Message::NoiseKind(val));
+        }
+        if let Some(val) = self.vel_noise.changed() {
+            send(Message::VelocityNoise(val));
+        }
+        if let Some(val) = self.noise_seed.changed() {
+            send(Message::Seed(val));
+        }
+        if let Some(val) = self.freeze_noise.changed() {
+            send(Message::FreezeNoise(val));
+        }
+        if let Some(val) = self.sensor_tau.changed() {
+            send(Message::SensorTau(val));
+        }
+        if let Some(val) = self.sensor_delay.changed() {
+            send(Message::SensorDelay(val));
+        }
+        if let Some(val) = self.sensor_resolution.changed() {
+            send(Message::SensorResolution(val));
+        }
+        if let Some(val) = self.outlier_probability.changed() {
+            send(Message::OutlierProbability(val));
+        }
+        if let Some(val) = self.outlier_magnitude.changed() {
+            send(Message::OutlierMagnitude(val));
+        }
+        if let Some(val) = self.median_window.changed() {
+            send(Message::MedianWindow(val));
         }
 
-        // Gravitation
-        if let Some(val) = self.gravitation.changed() {
-            sim.config(Message::Gravitation(val));
+        // Initial conditions
+        if let Some(val) = self.init_pos.changed() {
+            send(Message::InitPos(val));
+        }
+        if let Some(val) = self.init_vel.changed() {
+            send(Message::InitVel(val));
+        }
+
+        // Random start
+        if let Some(val) = self.random_start.changed() {
+            send(Message::RandomStart(val));
+        }
+        if let Some(val) = self.pos_spread.changed() {
+            send(Message::PosSpread(val));
+        }
+        if let Some(val) = self.vel_spread.changed() {
+            send(Message::VelSpread(val));
+        }
+
+        // Gravity
+        if let Some(val) = self.gravity.changed() {
+            send(Message::Gravity(val));
+        }
+        if let Some(val) = self.process_noise.changed() {
+            send(Message::ProcessNoise(val));
+        }
+        // Softening
+        if let Some(val) = self.coupling.changed() {
+            send(Message::Coupling(val));
+        }
+        if let Some(val) = self.softening.changed() {
+            send(Message::ForceSoftening(val));
+        }
+        // Mass
+        if let Some(val) = self.mass.changed() {
+            send(Message::Mass(val));
+        }
+        // Tube
+        if let Some(val) = self.floor.changed() {
+            send(Message::Floor(val));
+        }
+        if let Some(val) = self.ceiling.changed() {
+            send(Message::Ceiling(val));
+        }
+        if let Some(val) = self.restitution.changed() {
+            send(Message::Restitution(val));
+        }
+        // Max. velocity
+        if let Some(val) = self.max_velocity.changed() {
+            send(Message::MaxVelocity(val.unwrap_or(f32::INFINITY)));
         }
         // Max. force
-        if let Some(val) = self.max_force.changed() {
-            sim.config(Message::MaxForce(val));
+        if let Some(val) = self.max_force_up.changed() {
+            send(Message::MaxForceUp(val));
+        }
+        if let Some(val) = self.max_force_down.changed() {
+            send(Message::MaxForceDown(val));
         }
         // Max. force rate
         if let Some(val) = self.max_force_rate.changed() {
-            sim.config(Message::MaxForceRate(val));
+            send(Message::MaxForceRate(val));
+        }
+        // Initial force
+        if let Some(val) = self.initial_force.changed() {
+            send(Message::InitialForce(val));
+        }
+        // Actuator gain
+        if let Some(val) = self.actuator_gain.changed() {
+            send(Message::ActuatorGain(val));
+        }
+        if let Some(val) = self.deadband.changed() {
+            send(Message::Deadband(val));
+        }
+        if let Some(val) = self.dead_time.changed() {
+            send(Message::DeadTime(val));
+        }
+        // Output inversion
+        if let Some(val) = self.invert_output.changed() {
+            send(Message::InvertOutput(val));
+        }
+        // Output low-pass
+        if let Some(val) = self.output_cutoff.changed() {
+            send(Message::OutputCutoff(val));
+        }
+        // Open loop after reset
+        if let Some(val) = self.engage_delay.changed() {
+            send(Message::EngageDelay(val));
+        }
+        // Hold ball, the release mode has to be known before dropping the ball
+        if let Some(val) = self.smooth_release.changed() {
+            send(Message::SmoothRelease(val));
         }
-        // Hold ball
         if let Some(val) = self.hold_ball.changed() {
-            sim.config(Message::HoldBall(val));
+            send(Message::HoldBall(val));
+        }
+        // Inductor following the target
+        if let Some(val) = self.follow_target.changed() {
+            send(Message::FollowTarget(val));
+        }
+        if let Some(val) = self.follow_rate.changed() {
+            send(Message::FollowRate(val));
+        }
+        if let Some(val) = self.reference.changed() {
+            send(Message::Reference(val));
         }
     }
 }
 
-#[derive(Default)]
-pub struct MyApp {
-    input: Input,
+/// Every plotted series. The name doubles as the stable legend entry
+#[derive(Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+enum Trace {
+    CurrentTarget,
+    Target,
+    Position,
+    Velocity,
+    Force,
+}
+
+impl Trace {
+    const ALL: [Trace; 5] = [
+        Trace::CurrentTarget,
+        Trace::Target,
+        Trace::Position,
+        Trace::Velocity,
+        Trace::Force,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Trace::CurrentTarget => "Current target [m]",
+            Trace::Target => "Target [m]",
+            Trace::Position => "Position [m]",
+            Trace::Velocity => "Velocity [m/s]",
+            Trace::Force => "Force [N]",
+        }
+    }
+}
+
+/// Signal of the optional fourth plot
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Channel {
+    Position,
+    Velocity,
+    Force,
+    Error,
+    Integral,
+    Derivative,
+    CommandVsForce,
+}
+
+impl Channel {
+    const ALL: [Channel; 7] = [
+        Channel::Position,
+        Channel::Velocity,
+        Channel::Force,
+        Channel::Error,
+        Channel::Integral,
+        Channel::Derivative,
+        Channel::CommandVsForce,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Channel::Position => "Position [m]",
+            Channel::Velocity => "Velocity [m/s]",
+            Channel::Force => "Force [N]",
+            Channel::Error => "Error [m]",
+            Channel::Integral => "Integral term [N]",
+            Channel::Derivative => "Derivative term [N]",
+            Channel::CommandVsForce => "Commanded vs. actual force [N]",
+        }
+    }
+
+    fn unit(self) -> &'static str {
+        match self {
+            Channel::Position | Channel::Error => "m",
+            Channel::Velocity => "m/s",
+            Channel::Force | Channel::Integral | Channel::Derivative | Channel::CommandVsForce => {
+                "N"
+            }
+        }
+    }
+}
+
+/// How the discrete samples of position and force are drawn
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum SampleDisplay {
+    /// Straight lines between the samples
+    Line,
+    /// Every sample is held until the next one (zero-order hold)
+    Stepped,
+    /// Line with a marker at every frame that has sampled
+    Markers,
+}
+
+impl SampleDisplay {
+    const ALL: [SampleDisplay; 3] = [
+        SampleDisplay::Line,
+        SampleDisplay::Stepped,
+        SampleDisplay::Markers,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            SampleDisplay::Line => "Line",
+            SampleDisplay::Stepped => "Stepped",
+            SampleDisplay::Markers => "Markers",
+        }
+    }
+}
+
+/// Plots of the central panel, apart from the user selected channel
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Subplot {
+    Position,
+    Velocity,
+    Force,
+}
+
+impl Subplot {
+    const ALL: [Subplot; 3] = [Subplot::Position, Subplot::Velocity, Subplot::Force];
+
+    fn name(self) -> &'static str {
+        match self {
+            Subplot::Position => "Position",
+            Subplot::Velocity => "Velocity",
+            Subplot::Force => "Force",
+        }
+    }
+}
+
+/// Formatter of a value axis that scales the ticks of the visible range with a common SI
+/// prefix, e.g. "250 mm" instead of "0.25", and shows as many decimals as the range needs
+fn si_formatter(unit: &'static str) -> impl Fn(f64, usize, &RangeInclusive<f64>) -> String {
+    move |tick, max_digits, range| {
+        let magnitude = range.start().abs().max(range.end().abs());
+        let (scale, prefix) = si_prefix(magnitude);
+        let span = (range.end() - range.start()).abs() / scale;
+        let decimals = if span > 0.0 {
+            (2.0 - span.log10().floor()).clamp(0.0, max_digits as f64) as usize
+        } else {
+            0
+        };
+        format!("{:.*} {prefix}{unit}", decimals, tick / scale)
+    }
+}
+
+/// Largest SI prefix from nano to mega that keeps `magnitude` at one or above
+fn si_prefix(magnitude: f64) -> (f64, &'static str) {
+    const PREFIXES: [(f64, &str); 6] = [
+        (1e6, "M"),
+        (1e3, "k"),
+        (1.0, ""),
+        (1e-3, "m"),
+        (1e-6, "µ"),
+        (1e-9, "n"),
+    ];
+    if !magnitude.is_normal() {
+        return (1.0, "");
+    }
+    PREFIXES
+        .into_iter()
+        .find(|(scale, _)| magnitude >= *scale)
+        .unwrap_or(PREFIXES[PREFIXES.len() - 1])
+}
+
+/// Holds every point until the next one (zero-order hold)
+fn stepped(points: &[[f64; 2]]) -> Vec<[f64; 2]> {
+    let mut stepped = Vec::with_capacity(2 * points.len());
+    for pair in points.windows(2) {
+        stepped.push(pair[0]);
+        stepped.push([pair[1][0], pair[0][1]]);
+    }
+    stepped.extend(points.last());
+    stepped
+}
+
+/// Settings of the GUI that don't affect the simulation
+struct Settings {
+    /// Dark or light visuals, persisted across sessions
+    dark_mode: bool,
+    /// Repaints with every sample instead of at most `fps_cap` times per second
+    max_fps: bool,
+    fps_cap: u32,
+    /// Upper limit of simulation steps per frame
+    max_steps: u32,
+    /// Simulated seconds per real second
+    speed: f32,
+    /// Plots the force of the inductor as the resulting acceleration of the ball
+    force_as_acceleration: bool,
+    /// Overlays the proportional, integral and derivative term on the force
+    pid_components: bool,
+    sample_display: SampleDisplay,
+    /// Marks the latest sample of every plot and optionally annotates its value
+    playhead: bool,
+    playhead_value: bool,
+    /// Visibility of the plots, indexed by `Subplot`
+    subplots: [bool; Subplot::ALL.len()],
+    /// Signal of the fourth plot, which is hidden with `None`
+    channel: Option<Channel>,
+    /// Plot style
+    line_widths: [f32; Trace::ALL.len()],
+    /// Colors picked for the traces, indexed by `Trace`. `None` follows the theme
+    colors: [Option<Color32>; Trace::ALL.len()],
+    /// Highlights position, velocity and force
+    highlight: bool,
+    show_grid: bool,
+    show_background: bool,
+    /// Approximate number of grid lines per axis; zero picks them automatically
+    grid_ticks: u32,
+    /// Value axes with SI prefixes, indexed by `Subplot`, and of the fourth plot
+    si_prefixes: [bool; Subplot::ALL.len()],
+    channel_si_prefix: bool,
+    /// Only shows the last `time_window` seconds like an oscilloscope, instead of everything
+    scroll: bool,
+    time_window: f32,
+    /// Seconds of data that are kept for plotting and exporting
+    history: f32,
+    /// Change of the target per pixel of dragging
+    target_speed: f64,
+    target_decimals: usize,
+    /// Steps the target by `target_step` with the arrow keys while typing
+    arrow_keys: bool,
+    target_step: f64,
+    /// Velocity kick of the push buttons
+    push: f32,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            dark_mode: DARK_MODE,
+            max_fps: MAX_FPS,
+            fps_cap: FPS_CAP,
+            max_steps: MAX_STEPS_PER_FRAME,
+            speed: SPEED,
+            force_as_acceleration: FORCE_AS_ACCELERATION,
+            pid_components: PID_COMPONENTS,
+            sample_display: SampleDisplay::Line,
+            playhead: PLAYHEAD,
+            playhead_value: PLAYHEAD_VALUE,
+            subplots: [true; Subplot::ALL.len()],
+            channel: None,
+            line_widths: [LINE_WIDTH; Trace::ALL.len()],
+            colors: [None; Trace::ALL.len()],
+            highlight: HIGHLIGHT,
+            show_grid: SHOW_GRID,
+            show_background: SHOW_BACKGROUND,
+            grid_ticks: GRID_TICKS,
+            si_prefixes: [SI_PREFIXES; Subplot::ALL.len()],
+            channel_si_prefix: SI_PREFIXES,
+            scroll: SCROLL,
+            time_window: TIME_WINDOW,
+            history: HISTORY,
+            target_speed: TARGET_SPEED,
+            target_decimals: TARGET_DECIMALS,
+            arrow_keys: ARROW_KEYS,
+            target_step: TARGET_STEP,
+            push: PUSH,
+        }
+    }
+}
+
+fn visuals(dark_mode: bool) -> egui::Visuals {
+    if dark_mode {
+        egui::Visuals::dark()
+    } else {
+        egui::Visuals::light()
+    }
+}
+
+/// Plot colors that stay legible on the background of the theme
+struct Palette {
+    current_target: Color32,
+    target: Color32,
+    position: Color32,
+    reference: Color32,
+    velocity: Color32,
+    force: Color32,
+    comparison: Color32,
+}
+
+impl Palette {
+    fn new(dark_mode: bool) -> Self {
+        if dark_mode {
+            Self {
+                current_target: Color32::WHITE,
+                target: Color32::GRAY,
+                position: Color32::RED,
+                reference: Color32::LIGHT_RED,
+                velocity: Color32::from_rgb(90, 150, 255),
+                force: Color32::GREEN,
+                comparison: Color32::from_rgb(255, 165, 0),
+            }
+        } else {
+            Self {
+                current_target: Color32::BLACK,
+                target: Color32::GRAY,
+                position: Color32::RED,
+                reference: Color32::DARK_RED,
+                velocity: Color32::BLUE,
+                force: Color32::DARK_GREEN,
+                comparison: Color32::from_rgb(230, 120, 0),
+            }
+        }
+    }
+
+    fn trace(&mut self, trace: Trace) -> &mut Color32 {
+        match trace {
+            Trace::CurrentTarget => &mut self.current_target,
+            Trace::Target => &mut self.target,
+            Trace::Position => &mut self.position,
+            Trace::Velocity => &mut self.velocity,
+            Trace::Force => &mut self.force,
+        }
+    }
+}
+
+/// `DragValue` that steps by `step` instead of `speed` with the arrow keys while typing
+fn drag_value<'a, N: egui::emath::Numeric>(
+    ui: &egui::Ui,
+    value: &'a mut N,
+    speed: f64,
+    step: Option<f64>,
+) -> DragValue<'a> {
+    // The `DragValue` takes the next automatic id and steps by its speed while focused
+    let typing = ui.memory(|mem| mem.has_focus(ui.next_auto_id()));
+    let speed = match step {
+        Some(step) if typing => step,
+        _ => speed,
+    };
+    DragValue::new(value).speed(speed)
+}
+
+/// Rounds `step` up to the next 1, 2 or 5 times a power of ten
+fn nice_step(step: f64) -> f64 {
+    let magnitude = 10f64.powf(step.log10().floor());
+    let mantissa = step / magnitude;
+    let nice = if mantissa <= 1.0 {
+        1.0
+    } else if mantissa <= 2.0 {
+        2.0
+    } else if mantissa <= 5.0 {
+        5.0
+    } else {
+        10.0
+    };
+    nice * magnitude
+}
+
+/// Grid with roughly `ticks` major lines across the visible range
+fn grid_step_sizes(ticks: u32) -> impl Fn(GridInput) -> [f64; 3] {
+    move |input: GridInput| {
+        let span = input.bounds.1 - input.bounds.0;
+        let step = nice_step(span / ticks as f64).max(input.base_step_size);
+        [step / 5.0, step, step * 5.0]
+    }
+}
+
+/// Second simulation with its own PID gains for comparing two tunings side by side
+///
+/// It starts as a copy of the main simulation, including the state of the noise generator,
+/// and gets every message but the gains. So both see the same target, noise and
+/// disturbances, and step in lockstep.
+struct Comparison {
     sim: Simulation,
+    kp: Cache<f32>,
+    ki: Cache<f32>,
+    kd: Cache<f32>,
+}
+
+impl Comparison {
+    fn new(sim: &Simulation, [kp, ki, kd]: [f32; 3]) -> Self {
+        Self {
+            sim: sim.clone(),
+            kp: Cache::new(kp),
+            ki: Cache::new(ki),
+            kd: Cache::new(kd),
+        }
+    }
+
+    fn config(&mut self, msg: Message) {
+        if !matches!(msg, Message::Kp(_) | Message::Ki(_) | Message::Kd(_)) {
+            self.sim.config(msg);
+        }
+    }
+
+    /// Sends the changed gains
+    fn update(&mut self) {
+        if let Some(val) = self.kp.changed() {
+            self.sim.config(Message::Kp(val));
+        }
+        if let Some(val) = self.ki.changed() {
+            self.sim.config(Message::Ki(val));
+        }
+        if let Some(val) = self.kd.changed() {
+            self.sim.config(Message::Kd(val));
+        }
+    }
+
+    /// Continues as a copy of `sim` with its own gains
+    fn sync(&mut self, sim: &Simulation) {
+        self.sim = sim.clone();
+        for msg in [
+            Message::Kp(self.kp.get()),
+            Message::Ki(self.ki.get()),
+            Message::Kd(self.kd.get()),
+        ] {
+            self.sim.config(msg);
+        }
+    }
+}
+
+/// Recorded run from a table of "Copy data", drawn faintly behind the live run
+#[derive(Debug)]
+struct GhostRun {
+    seconds: Vec<f32>,
+    target: Vec<f32>,
     pos: Vec<f32>,
     vel: Vec<f32>,
-    target: Vec<f32>,
     force: Vec<f32>,
-    seconds: Vec<f32>,
-    time: Time,
+}
+
+/// Why a table can't be imported as [`GhostRun`]
+#[derive(Debug, PartialEq)]
+enum ParseError {
+    /// Nothing but comments and blank lines
+    Empty,
+    MissingColumn(&'static str),
+    /// A row with fewer cells than the header, with its line number
+    ShortRow(usize),
+    /// A cell that isn't a number, with its line number and column
+    InvalidNumber(usize, &'static str),
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseError::Empty => write!(f, "No header row"),
+            ParseError::MissingColumn(name) => write!(f, "Missing column \"{name}\""),
+            ParseError::ShortRow(line) => write!(f, "Line {line}: too few columns"),
+            ParseError::InvalidNumber(line, name) => {
+                write!(f, "Line {line}: \"{name}\" isn't a number")
+            }
+        }
+    }
+}
+
+/// Everything the "Load state" button returns to
+struct SavedState {
+    sim: SimSnapshot,
+    /// Configuration that the GUI shows for the saved simulation
+    config: Config,
+    hold_ball: bool,
+    time: Duration,
+    step_count: u64,
+}
+
+#[derive(Default)]
+pub struct MyApp {
+    input: Input,
+    sim: Stepper,
+    pos: RingBuffer<f32>,
+    vel: RingBuffer<f32>,
+    target: RingBuffer<f32>,
+    force: RingBuffer<f32>,
+    command: RingBuffer<f32>,
+    proportional: RingBuffer<f32>,
+    integral: RingBuffer<f32>,
+    derivative: RingBuffer<f32>,
+    /// Position of the reference simulation, NaN while it isn't running
+    reference: RingBuffer<f32>,
+    /// Position and force of the comparison, NaN while it isn't running
+    compare_pos: RingBuffer<f32>,
+    compare_force: RingBuffer<f32>,
+    seconds: RingBuffer<f32>,
+    /// Whether the simulation has stepped forward in that frame
+    sampled: RingBuffer<bool>,
+    /// Statistics over the whole run, independent of the plotted history
+    pos_stats: RunningStats,
+    vel_stats: RunningStats,
+    force_stats: RunningStats,
+    /// Duration of time that has elapsed in the simulation
+    elapsed: Duration,
+    /// Number of simulation steps since the last reset
+    step_count: u64,
+    /// The simulation has produced NaN or Inf and is stopped until reset
+    diverged: bool,
+    /// The ball is out of control within the latest `INSTABILITY_WINDOW` seconds
+    unstable: bool,
+    /// The magnitude and the rate limit of the inductor have clipped recently
+    saturation: Latch,
+    rate_limit: Latch,
+    /// Second tuning that runs side by side, toggled by "Compare"
+    comparison: Option<Comparison>,
+    /// The simulation only advances by the "Step" button
+    paused: bool,
+    /// Sampling steps requested by the "Step" button for the next frame
+    single_steps: u32,
+    /// Simulation steps of the current frame and sampling time of the latest one
+    frame_steps: u32,
+    sampling_time: Option<Duration>,
+    /// Measured frames per second, smoothed over the latest frames
+    fps: Option<f32>,
+    /// Monte Carlo dialog
+    monte_carlo: MonteCarlo,
+    monte_carlo_open: bool,
+    monte_carlo_summary: Option<Summary>,
+    /// Frequency, gain [dB] and phase [°] of the last frequency response analysis
+    bode: Vec<(f32, f32, f32)>,
+    bode_open: bool,
+    /// Traces that are hidden via the "Traces" menu
+    hidden: HashSet<Trace>,
+    /// Resets zoom, pan and the traces hidden via legend of every plot on the next frame
+    reset_plots: bool,
+    settings: Settings,
+    /// Scenario dialog
+    scenario_open: bool,
+    scenario_text: String,
+    scenario_error: Option<String>,
+    scenario_seed: u64,
+    /// Outcome of the last auto-tuning
+    tune_status: Option<String>,
+    /// State of the "Save state" button
+    saved_state: Option<SavedState>,
+    /// Configuration dialog
+    config_open: bool,
+    config_text: String,
+    /// Entered file of the configuration, there is no native file dialog
+    #[cfg(not(target_arch = "wasm32"))]
+    config_path: String,
+    /// Why saving or loading the configuration has failed
+    config_error: Option<String>,
+    /// Setpoint profile of the loaded scenario
+    profile: Vec<Setpoint>,
+    /// Significant events, kept across resets
+    events: EventLog,
+    /// Imported run, kept across resets until removed
+    ghost: Option<GhostRun>,
+    /// Import dialog
+    ghost_open: bool,
+    ghost_text: String,
+    ghost_error: Option<String>,
+    /// "Export PNG" was clicked and the plots are captured at the end of the frame
+    export_png: bool,
+    /// Area of the plots in physical pixels, `None` before the first frame
+    plot_rect: Option<egui::Rect>,
 }
 
 impl MyApp {
-    /// Clears every buffer
+    /// Restores the configuration of the last session, if any
+    pub fn new(cc: &eframe::CreationContext<'_>) -> Self {
+        let mut app = Self::default();
+        // Applied before the first frame, so it doesn't flash in the wrong theme
+        if let Some(dark_mode) = cc
+            .storage
+            .and_then(|storage| eframe::get_value::<bool>(storage, THEME_STORAGE_KEY))
+        {
+            app.settings.dark_mode = dark_mode;
+        }
+        cc.egui_ctx.set_visuals(visuals(app.settings.dark_mode));
+        type Traces = (HashSet<Trace>, [Option<Color32>; Trace::ALL.len()]);
+        if let Some((hidden, colors)) = cc
+            .storage
+            .and_then(|storage| eframe::get_value::<Traces>(storage, TRACES_STORAGE_KEY))
+        {
+            app.hidden = hidden;
+            app.settings.colors = colors;
+        }
+        // An unreadable configuration, e.g. from an older version, is skipped
+        let config = cc
+            .storage
+            .and_then(|storage| eframe::get_value::<Config>(storage, STORAGE_KEY));
+        if let Some(config) = config {
+            app.input.apply(&config.migrate());
+            app.input.update(&mut app.sim);
+            // The initial force and a random start only take effect with a reset
+            app.sim.config(Message::Reset);
+        }
+        app
+    }
+    /// Appends the data of a frame to the buffers
+    fn record(&mut self, data: &Data, compared: Option<&Data>, sampled: bool) {
+        self.pos.push(data.pos);
+        self.target.push(data.target);
+        self.vel.push(data.vel);
+        self.force.push(data.force);
+        self.command.push(data.command);
+        self.proportional.push(data.p);
+        self.integral.push(data.i);
+        self.derivative.push(data.d);
+        self.seconds.push(self.elapsed.as_secs_f32());
+        self.reference.push(data.reference.unwrap_or(f32::NAN));
+        self.compare_pos
+            .push(compared.map_or(f32::NAN, |data| data.pos));
+        self.compare_force
+            .push(compared.map_or(f32::NAN, |data| data.force));
+        self.sampled.push(sampled);
+        self.pos_stats.push(data.pos);
+        self.vel_stats.push(data.vel);
+        self.force_stats.push(data.force);
+        self.trim();
+    }
+    /// Drops the frames that are older than `history` seconds
+    fn trim(&mut self) {
+        let oldest = self.elapsed.as_secs_f32() - self.settings.history;
+        let count = self.seconds.partition_point(|&t| t < oldest);
+        if count == 0 {
+            return;
+        }
+        self.pos.drop_front(count);
+        self.vel.drop_front(count);
+        self.target.drop_front(count);
+        self.force.drop_front(count);
+        self.command.drop_front(count);
+        self.proportional.drop_front(count);
+        self.integral.drop_front(count);
+        self.derivative.drop_front(count);
+        self.seconds.drop_front(count);
+        self.reference.drop_front(count);
+        self.compare_pos.drop_front(count);
+        self.compare_force.drop_front(count);
+        self.sampled.drop_front(count);
+    }
+    /// Buffers as table with a header row, one row per frame and `separator` between the columns
+    fn table(&self, separator: &str) -> String {
+        let columns: [(&str, &[f32]); 9] = [
+            ("time [s]", &self.seconds),
+            ("target [m]", &self.target),
+            ("position [m]", &self.pos),
+            ("velocity [m/s]", &self.vel),
+            ("force [N]", &self.force),
+            ("command [N]", &self.command),
+            ("proportional [N]", &self.proportional),
+            ("integral [N]", &self.integral),
+            ("derivative [N]", &self.derivative),
+        ];
+        let mut table = columns.map(|(name, _)| name).join(separator);
+        table.push('\n');
+        for row in 0..self.seconds.len() {
+            let row = columns.map(|(_, values)| values[row].to_string());
+            table += &row.join(separator);
+            table.push('\n');
+        }
+        table
+    }
+    /// Parses a table like the one of [`MyApp::table`], separated by tabs or commas
+    ///
+    /// Lines starting with `#` are comments. Further columns are ignored.
+    fn import_csv(data: &str) -> Result<GhostRun, ParseError> {
+        let mut lines = data
+            .lines()
+            .enumerate()
+            .map(|(i, line)| (i + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'));
+        let (_, header) = lines.next().ok_or(ParseError::Empty)?;
+        let separator = if header.contains('\t') { '\t' } else { ',' };
+        let header: Vec<&str> = header.split(separator).map(str::trim).collect();
+        let column = |name| {
+            let index = header.iter().position(|&cell| cell == name);
+            index
+                .map(|index| (name, index))
+                .ok_or(ParseError::MissingColumn(name))
+        };
+        let columns = [
+            column("time [s]")?,
+            column("target [m]")?,
+            column("position [m]")?,
+            column("velocity [m/s]")?,
+            column("force [N]")?,
+        ];
+
+        let mut values: [Vec<f32>; 5] = Default::default();
+        for (line, row) in lines {
+            let cells: Vec<&str> = row.split(separator).map(str::trim).collect();
+            for ((name, index), values) in columns.iter().zip(&mut values) {
+                let cell = cells.get(*index).ok_or(ParseError::ShortRow(line))?;
+                let value = cell
+                    .parse()
+                    .map_err(|_| ParseError::InvalidNumber(line, name))?;
+                values.push(value);
+            }
+        }
+        let [seconds, target, pos, vel, force] = values;
+        Ok(GhostRun {
+            seconds,
+            target,
+            pos,
+            vel,
+            force,
+        })
+    }
+    /// Replaces the ghost run by the table in the text field or shows why it can't
+    fn import_ghost_text(&mut self) {
+        match Self::import_csv(&self.ghost_text) {
+            Ok(ghost) => {
+                self.ghost_error = None;
+                let duration = ghost.seconds.last().copied().unwrap_or_default();
+                self.event(&format!("Imported a run of {duration:.2} s"));
+                self.ghost = Some(ghost);
+            }
+            Err(e) => self.ghost_error = Some(e.to_string()),
+        }
+    }
+    fn ghost_ui(&mut self, ui: &mut egui::Ui) {
+        ui.label("Paste the data of \"Copy data\" or drop a .csv or .tsv file onto the window");
+        ui.horizontal(|ui| {
+            if ui
+                .button("Import")
+                .on_hover_text("Draws the run faintly behind the live one")
+                .clicked()
+            {
+                self.import_ghost_text();
+            }
+            if ui
+                .add_enabled(self.ghost.is_some(), egui::Button::new("Remove"))
+                .clicked()
+            {
+                self.ghost = None;
+            }
+        });
+        if let Some(error) = &self.ghost_error {
+            ui.colored_label(Color32::RED, error);
+        }
+        egui::ScrollArea::vertical()
+            .max_height(400.0)
+            .show(ui, |ui| {
+                ui.add(
+                    egui::TextEdit::multiline(&mut self.ghost_text)
+                        .code_editor()
+                        .desired_width(f32::INFINITY),
+                );
+            });
+    }
+    /// Metrics of the response to the last change of the target
+    ///
+    /// The step starts with the first frame whose target differs from the frame before.
+    /// Without a change within the history the whole history counts as the step.
+    fn step_response(&self) -> StepMetrics {
+        let start = self
+            .target
+            .windows(2)
+            .rposition(|pair| pair[0] != pair[1])
+            .map_or(0, |i| i + 1);
+        let target = self.target.last().copied().unwrap_or(TARGET);
+        step_metrics(&self.seconds[start..], &self.pos[start..], target)
+    }
+    /// Drops the frames after `seconds`
+    fn rewind(&mut self, seconds: f32) {
+        let len = self.seconds.partition_point(|&t| t <= seconds);
+        self.pos.truncate(len);
+        self.vel.truncate(len);
+        self.target.truncate(len);
+        self.force.truncate(len);
+        self.command.truncate(len);
+        self.proportional.truncate(len);
+        self.integral.truncate(len);
+        self.derivative.truncate(len);
+        self.seconds.truncate(len);
+        self.reference.truncate(len);
+        self.compare_pos.truncate(len);
+        self.compare_force.truncate(len);
+        self.sampled.truncate(len);
+    }
+    fn save_state(&mut self) {
+        let sim = self.sim.with(|sim| sim.snapshot());
+        // Catching up with the samples before the snapshot
+        self.receive();
+        self.saved_state = Some(SavedState {
+            sim,
+            config: self.input.config(),
+            hold_ball: self.input.hold_ball.get(),
+            time: self.elapsed,
+            step_count: self.step_count,
+        });
+        self.event("State saved");
+    }
+    /// Returns to the saved state, with the plots from then on discarded
+    fn load_state(&mut self) {
+        let Some(saved) = &self.saved_state else {
+            return;
+        };
+        // The restored simulation already has the saved configuration, so the messages of
+        // the GUI are dropped
+        self.input.apply(&saved.config);
+        self.input.hold_ball.val = saved.hold_ball;
+        self.input.send(|_| ());
+        self.sim.discard();
+        let snapshot = saved.sim.clone();
+        self.sim.with(move |sim| sim.restore(&snapshot));
+        if let Some(comparison) = &mut self.comparison {
+            comparison.sync(&self.sim.simulation());
+        }
+        self.elapsed = saved.time;
+        self.step_count = saved.step_count;
+        self.diverged = false;
+        self.unstable = false;
+        self.rewind(saved.time.as_secs_f32());
+    }
+    /// Clears every buffer and drops the samples that haven't been received yet
     fn clear(&mut self) {
+        self.sim.discard();
         self.pos.clear();
         self.vel.clear();
         self.target.clear();
         self.force.clear();
+        self.command.clear();
+        self.proportional.clear();
+        self.integral.clear();
+        self.derivative.clear();
         self.seconds.clear();
+        self.reference.clear();
+        self.compare_pos.clear();
+        self.compare_force.clear();
+        self.sampled.clear();
+        self.pos_stats.reset();
+        self.vel_stats.reset();
+        self.force_stats.reset();
+        self.step_count = 0;
+        self.sampling_time = None;
+        self.diverged = false;
+        self.unstable = false;
+        self.saturation = Latch::default();
+        self.rate_limit = Latch::default();
+    }
+    /// Time until the next frame, `None` if nothing changes until the next input
+    ///
+    /// A frame is due with every sample, so a hidden or idle window doesn't repaint at the
+    /// refresh rate of the monitor. The simulation catches up with the real time on the next
+    /// frame either way.
+    fn repaint_after(&self, sampling_time: Duration) -> Option<Duration> {
+        let idle = self.paused && self.single_steps == 0;
+        if idle || self.diverged {
+            return None;
+        }
+        let sample = sampling_time.div_f32(self.settings.speed);
+        if self.settings.max_fps {
+            Some(sample)
+        } else {
+            let frame_time = Duration::from_secs_f32(1.0 / self.settings.fps_cap as f32);
+            Some(sample.max(frame_time))
+        }
+    }
+    /// Colors of the theme, with the ones picked for the traces
+    fn palette(&self) -> Palette {
+        let mut palette = Palette::new(self.settings.dark_mode);
+        for (trace, color) in Trace::ALL.into_iter().zip(self.settings.colors) {
+            if let Some(color) = color {
+                *palette.trace(trace) = color;
+            }
+        }
+        palette
+    }
+    /// Configures the simulation and the comparison alike
+    fn config(&mut self, msg: Message) {
+        if let Some(comparison) = &mut self.comparison {
+            comparison.config(msg.clone());
+        }
+        self.sim.config(msg);
+    }
+    /// Sends the changed values of the GUI to the simulation and the comparison
+    fn update_config(&mut self) {
+        let (sim, comparison) = (&mut self.sim, &mut self.comparison);
+        self.input.send(|msg| {
+            if let Some(comparison) = comparison {
+                comparison.config(msg.clone());
+            }
+            sim.config(msg);
+        });
+        if let Some(comparison) = comparison {
+            comparison.update();
+        }
+    }
+    /// Steps the simulation right away and records the frame
+    fn advance(&mut self, steps: u32, sampling_time: Duration) {
+        let data = self.sim.step(steps, sampling_time);
+        self.frame(data, steps, sampling_time);
+    }
+    /// Records the samples that the simulation has stepped in real time since the last frame
+    fn receive(&mut self) {
+        let samples = self.sim.samples();
+        let Some(last) = samples.last() else {
+            return;
+        };
+        self.elapsed += samples
+            .iter()
+            .map(|sample| sample.sampling_time)
+            .sum::<Duration>();
+        // Whether the limits of the inductor have clipped on any of the samples
+        let data = Data {
+            magnitude_limited: samples.iter().any(|sample| sample.data.magnitude_limited),
+            rate_limited: samples.iter().any(|sample| sample.data.rate_limited),
+            ..last.data
+        };
+        self.frame(data, samples.len() as u32, last.sampling_time);
+    }
+    /// Steps the comparison, if any, along with the simulation and records the last data
+    fn frame(&mut self, data: Data, steps: u32, sampling_time: Duration) {
+        let compared = self
+            .comparison
+            .as_mut()
+            .map(|comparison| comparison.sim.step(steps, sampling_time));
+        self.step_count += steps as u64;
+        self.frame_steps += steps;
+        if steps > 0 {
+            self.sampling_time = Some(sampling_time);
+        }
+        // The setpoint profile changes the target
+        self.input.target.sync(data.target);
+        self.sim.take_events(&mut self.events);
+        if !data.diverged && data.is_finite() {
+            self.record(&data, compared.as_ref(), steps > 0);
+            self.saturation.update(data.magnitude_limited);
+            self.rate_limit.update(data.rate_limited);
+            self.check_stability();
+        } else {
+            // Stops until reset, the simulation has logged the event. NaN and Inf never
+            // reach the plots and statistics
+            self.diverged = true;
+        }
+    }
+    /// Latest values with their units for the readouts, dashes before the first sample
+    fn readouts(&self) -> [(&'static str, String); 7] {
+        let fmt =
+            |val: Option<f32>, unit: &str| val.map_or("-".into(), |v| format!("{v:>9.4} {unit}"));
+        let pos = self.pos.last().copied();
+        let error = self
+            .target
+            .last()
+            .zip(pos)
+            .map(|(target, pos)| target - pos);
+        let sampling_time = self.sampling_time.map(|t| t.as_secs_f32() * 1e3);
+        [
+            ("Position", fmt(pos, "m")),
+            ("Velocity", fmt(self.vel.last().copied(), "m/s")),
+            ("Force", fmt(self.force.last().copied(), "N")),
+            ("Error", fmt(error, "m")),
+            ("Steps per frame", format!("{:>9}", self.frame_steps)),
+            ("Sampling time", fmt(sampling_time, "ms")),
+            (
+                "FPS",
+                self.fps.map_or("-".into(), |fps| format!("{fps:>9.1}")),
+            ),
+        ]
+    }
+    /// Judges the latest positions, logging when the ball goes out of control
+    fn check_stability(&mut self) {
+        let since = self.elapsed.as_secs_f32() - INSTABILITY_WINDOW;
+        let start = self.seconds.partition_point(|&t| t < since);
+        let bounds = (self.input.floor.get(), self.input.ceiling.get());
+        let unstable = detect_instability(&self.pos[start..], bounds);
+        if unstable && !self.unstable {
+            self.event("Loop unstable");
+        }
+        self.unstable = unstable;
     }
     /// Restarts everything and discards user input
     fn reset(&mut self) {
         self.clear();
+        self.comparison = None;
         self.sim.config(Message::Restart); // restart simulation
-        self.time = Default::default();
+        self.elapsed = Duration::ZERO;
         self.input = Default::default();
+        self.profile.clear();
+        self.event("Reset");
+    }
+    /// Pauses or resumes in real time, which skips the real time of the pause
+    fn pause(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+    /// Handles the keyboard shortcuts, unless a text field takes the keys
+    fn shortcuts(&mut self, ctx: &egui::Context) {
+        if ctx.wants_keyboard_input() {
+            return;
+        }
+        let pressed = |key| ctx.input(|i| i.key_pressed(key));
+        if pressed(egui::Key::Space) {
+            self.pause(!self.paused);
+        }
+        if pressed(egui::Key::R) {
+            if ctx.input(|i| i.modifiers.shift) {
+                self.reset();
+            } else {
+                self.restart();
+            }
+        }
+        if pressed(egui::Key::ArrowRight) && self.paused {
+            self.single_steps += 1;
+        }
+        if pressed(egui::Key::PlusEquals) {
+            self.settings.speed = (self.settings.speed * SPEED_NUDGE).min(MAX_SPEED);
+        }
+        if pressed(egui::Key::Minus) {
+            self.settings.speed = (self.settings.speed / SPEED_NUDGE).max(MIN_SPEED);
+        }
     }
     /// Restarts everything but keeps user input
     fn restart(&mut self) {
         self.clear();
-        self.sim.config(Message::Reset); // resets simulation
-        self.time = Default::default();
+        self.config(Message::Reset); // resets simulation
+        self.elapsed = Duration::ZERO;
+        self.event("Restart");
     }
-}
-
-impl eframe::App for MyApp {
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        ctx.request_repaint(); // Maximizing FPS
-
-        // Updating the simulation config
-        self.input.update(&mut self.sim);
-
-        // Stepping the simulation forward
-        let sampling_time = 1.0 / self.input.sampling_rate.get() as f32;
-        let sampling_time = Duration::from_secs_f32(sampling_time);
-        let steps = self.time.step(sampling_time);
-        if ctx.frame_nr() > 10 {
-            // GUI is stuttering for the first few samples
-            let data = self.sim.step(steps, sampling_time);
-            self.pos.push(data.pos);
-            self.target.push(self.input.target.get());
-            self.vel.push(data.vel);
-            self.force.push(data.force);
-            self.seconds.push(self.time.gui.elapsed().as_secs_f32());
+    /// Logs an event at the current simulated time
+    fn event(&mut self, text: &str) {
+        let time = self.elapsed.as_secs_f32();
+        self.events.push(time, "", text.to_owned());
+    }
+    /// Sets the PID gains by the Ziegler–Nichols rules, leaving them unchanged on failure
+    fn auto_tune(&mut self, sampling_time: Duration) {
+        match ziegler_nichols(&self.sim.simulation(), sampling_time) {
+            Ok(gains) => {
+                // Applied by the next `Input::update` like a manual change
+                self.input.set_gains(&gains, sampling_time);
+                let status = format!(
+                    "Tuned: P = {:.2}, I = {:.3}, D = {:.3}",
+                    gains.kp,
+                    self.input.ki.get(),
+                    gains.kd
+                );
+                self.event(&status);
+                self.tune_status = Some(status);
+            }
+            Err(err) => {
+                self.event(&format!("Auto-tuning failed: {err}"));
+                self.tune_status = Some(err);
+            }
+        }
+    }
+    /// Switches to the PID controller with the gains of `preset` and restarts
+    fn apply_preset(&mut self, preset: &Preset, sampling_time: Duration) {
+        self.input.controller.val = ControllerKind::Pid;
+        self.input.set_gains(&preset.gains, sampling_time);
+        self.input.d_mode.val = preset.d_mode;
+        self.update_config();
+        self.restart();
+        self.event(&format!("Preset: {}", preset.name));
+    }
+    fn monte_carlo_ui(&mut self, ui: &mut egui::Ui, sampling_time: Duration) {
+        let mc = &mut self.monte_carlo;
+        egui::Grid::new("monte_carlo_config").show(ui, |ui| {
+            ui.label("Trials");
+            ui.add(DragValue::new(&mut mc.trials).clamp_range(1..=10_000));
+            ui.end_row();
+            for (label, spread) in [
+                ("P spread [%]", &mut mc.kp_spread),
+                ("I spread [%]", &mut mc.ki_spread),
+                ("D spread [%]", &mut mc.kd_spread),
+            ] {
+                ui.label(label);
+                let mut percent = *spread * 100.0;
+                ui.add(DragValue::new(&mut percent).clamp_range(0.0..=100.0));
+                *spread = percent / 100.0;
+                ui.end_row();
+            }
+            ui.label("Duration [s]");
+            ui.add(
+                DragValue::new(&mut mc.duration)
+                    .speed(0.1)
+                    .clamp_range(0.1..=600.0),
+            );
+            ui.end_row();
+            ui.label("Seed");
+            ui.add(DragValue::new(&mut mc.seed));
+            ui.end_row();
+        });
+        if ui
+            .button("Run")
+            .on_hover_text("Restarted copies of the current simulation step to the target")
+            .clicked()
+        {
+            let gains = [
+                self.input.kp.get(),
+                self.input.ki.get(),
+                self.input.kd.get(),
+            ];
+            let target = self.input.target.get();
+            let summary = mc.run(&self.sim.simulation(), gains, target, sampling_time);
+            self.monte_carlo_summary = Some(summary);
         }
 
-        egui::TopBottomPanel::top("config1").show(ctx, |ui| {
-            ui.horizontal(|ui| {
-                if ui
-                    .button("Reset")
-                    .on_hover_text("Clears plots and resets with default values")
-                    .clicked()
-                {
-                    self.reset()
-                }
-                if ui
-                    .button("Restart")
-                    .on_hover_text("Clears plots and resets with given values")
-                    .clicked()
-                {
-                    self.restart();
-                }
-                ui.separator();
-                ui.label("Noise [σ]");
+        if let Some(summary) = &self.monte_carlo_summary {
+            ui.separator();
+            let fmt = |val: Option<f32>| val.map_or("-".into(), |v| format!("{v:.2}"));
+            egui::Grid::new("monte_carlo_summary")
+                .striped(true)
+                .show(ui, |ui| {
+                    for header in ["", "Mean", "σ", "Worst"] {
+                        ui.strong(header);
+                    }
+                    ui.end_row();
+                    ui.label("Overshoot [%]");
+                    ui.label(fmt(summary.overshoot.mean()));
+                    ui.label(fmt(summary.overshoot.std_dev()));
+                    ui.label(fmt(summary.overshoot.max()));
+                    ui.end_row();
+                    ui.label("Settling time [s]");
+                    ui.label(fmt(summary.settling_time.mean()));
+                    ui.label(fmt(summary.settling_time.std_dev()));
+                    ui.label(fmt(summary.settling_time.max()));
+                    ui.end_row();
+                });
+            ui.label(format!(
+                "{} of {} trials haven't settled",
+                summary.unsettled,
+                summary.overshoot.count()
+            ));
+        }
+    }
+    /// Measures the frequency response of a copy of the simulation and shows it
+    fn analyze(&mut self, sampling_time: Duration) {
+        let freqs = frequencies(self.input.sampling_rate.get());
+        self.bode = bode(&mut self.sim.simulation(), &freqs, sampling_time);
+        self.bode_open = true;
+        self.event("Frequency response analyzed");
+    }
+    fn bode_ui(&self, ui: &mut egui::Ui) {
+        // Logarithmic frequency axis
+        let log_freq = |&(freq, _, _): &(f32, f32, f32)| freq.log10() as f64;
+        let hz = |log_freq: f64, _: usize, _: &RangeInclusive<f64>| {
+            format!("{:.3} Hz", 10f64.powf(log_freq))
+        };
+        let group_id = ui.id().with("bode_axis");
+        for (name, unit, value) in [
+            (
+                "Gain",
+                "dB",
+                (|&(_, gain, _)| gain) as fn(&(f32, f32, f32)) -> f32,
+            ),
+            ("Phase", "°", |&(_, _, phase)| phase),
+        ] {
+            Plot::new(name)
+                .link_axis(group_id, true, false)
+                .x_axis_formatter(hz)
+                .y_axis_formatter(move |y, _, _| format!("{y} {unit}"))
+                .height(200.0)
+                .width(500.0)
+                .show(ui, |ui| {
+                    let points: PlotPoints = self
+                        .bode
+                        .iter()
+                        .filter(|point| value(point).is_finite())
+                        .map(|point| [log_freq(point), value(point) as f64])
+                        .collect();
+                    ui.line(Line::new(points).name(name));
+                });
+        }
+    }
+    /// Restarts with a pull-only magnet above the ball under earth-like gravitation
+    ///
+    /// Overrides everything the setup depends on, so it doesn't inherit e.g. another
+    /// coupling model from the previous configuration.
+    fn maglev(&mut self) {
+        let input = &mut self.input;
+        // Inverse-square magnet at `IND_POS`, above the ball in the tube
+        input.coupling.val = CouplingModel::InverseSquare;
+        input.softening.val = FORCE_SOFTENING;
+        input.follow_target.val = false;
+        input.floor.val = FLOOR;
+        input.ceiling.val = CEILING;
+        input.init_pos.val = BALL_POS;
+        input.mass.val = MASS;
+        input.gravity.val = GRAVITY;
+        input.max_force_up.val = MAX_FORCE;
+        input.max_force_down.val = 0.0;
+        input.actuator_gain.val = ACTUATOR_GAIN;
+        input.invert_output.val = false;
+        // The default gains, which are tuned for this setup
+        input.controller.val = ControllerKind::Pid;
+        input.i_mode.val = I_MODE;
+        input.kp.val = KP;
+        input.ki.val = KI;
+        input.kd.val = KD;
+        input.target_profile.val = TargetProfile::Constant(TARGET);
+        input.target.val = TARGET;
+        self.update_config();
+        self.restart();
+    }
+    /// Configures everything from the scenario and restarts with its seed
+    fn load_scenario(&mut self, scenario: Scenario) {
+        self.input.apply(&scenario.config);
+        self.input.noise_seed.val = None; // The seed of the scenario takes over
+        self.input.hold_ball.val = false;
+        // The initial force only takes effect with the reset of the restart
+        self.update_config();
+        self.config(Message::Profile(scenario.profile.clone()));
+        self.profile = scenario.profile;
+        self.scenario_seed = scenario.seed;
+        // Seeding first, so the seed also fixes a random start
+        let seed = scenario.seed;
+        self.sim.with(move |sim| sim.seed(seed));
+        if let Some(comparison) = &mut self.comparison {
+            comparison.sim.seed(scenario.seed);
+        }
+        self.restart();
+        self.event(&format!("Scenario loaded with seed {}", scenario.seed));
+    }
+    /// Current configuration as scenario
+    fn scenario(&self) -> Scenario {
+        let input = &self.input;
+        // A profile changes the target, so the run has started with the recorded one
+        let target = match self.target.first() {
+            Some(&target) if !self.profile.is_empty() => target,
+            _ => input.target.get(),
+        };
+        Scenario {
+            seed: self.scenario_seed,
+            config: Config {
+                target,
+                ..input.config()
+            },
+            profile: self.profile.clone(),
+            ..Default::default()
+        }
+    }
+    /// File the configuration is saved to and loaded from, `CONFIG_FILE` if none is entered
+    fn config_file(&self) -> &str {
+        #[cfg(not(target_arch = "wasm32"))]
+        if !self.config_path.is_empty() {
+            return &self.config_path;
+        }
+        CONFIG_FILE
+    }
+    /// Writes the configuration to the file on native and downloads it on the web
+    fn save_config(&mut self) {
+        self.config_text = self.input.config().to_json();
+        #[cfg(not(target_arch = "wasm32"))]
+        let saved =
+            std::fs::write(self.config_file(), &self.config_text).map_err(|e| e.to_string());
+        #[cfg(target_arch = "wasm32")]
+        let saved = screenshot::download_text(&self.config_text, CONFIG_FILE);
+        let file = self.config_file().to_owned();
+        match saved {
+            Ok(()) => {
+                self.config_error = None;
+                self.event(&format!("Config saved to {file}"));
+            }
+            Err(e) => self.config_error = Some(format!("Saving {file}: {e}")),
+        }
+    }
+    /// Configures everything from the file without restarting
+    #[cfg(not(target_arch = "wasm32"))]
+    fn load_config(&mut self) {
+        match std::fs::read_to_string(self.config_file()) {
+            Ok(json) => {
+                self.config_text = json;
+                self.load_config_text();
+            }
+            Err(e) => self.config_error = Some(format!("Loading {}: {e}", self.config_file())),
+        }
+    }
+    /// Configures everything from the text field without restarting, or shows why it can't
+    fn load_config_text(&mut self) {
+        match Config::from_json(&self.config_text) {
+            Ok(config) => {
+                self.config_error = None;
+                self.input.apply(&config);
+                self.event("Config loaded");
+            }
+            Err(e) => self.config_error = Some(e),
+        }
+    }
+    /// Writes the plot area of a screenshot to `PNG_FILE`
+    #[cfg(not(target_arch = "wasm32"))]
+    fn save_png(&mut self, image: &egui::ColorImage) {
+        // The whole window if the plots haven't been laid out yet
+        let rect = self.plot_rect.unwrap_or(egui::Rect::EVERYTHING);
+        let png = screenshot::encode_png(&screenshot::crop(image, rect));
+        match png.and_then(|png| std::fs::write(PNG_FILE, png).map_err(|e| e.to_string())) {
+            Ok(()) => self.event(&format!("Plots saved to {PNG_FILE}")),
+            Err(e) => self.event(&format!("Saving {PNG_FILE}: {e}")),
+        }
+    }
+    /// Loads the scenario in the text field or shows why it can't
+    fn load_scenario_text(&mut self) {
+        match Scenario::from_json(&self.scenario_text) {
+            Ok(scenario) => {
+                self.scenario_error = None;
+                self.load_scenario(scenario);
+            }
+            Err(e) => self.scenario_error = Some(e),
+        }
+    }
+    fn scenario_ui(&mut self, ui: &mut egui::Ui) {
+        ui.label("Paste a scenario or drop a scenario file onto the window");
+        ui.horizontal(|ui| {
+            if ui
+                .button("Load scenario")
+                .on_hover_text("Configures everything and restarts deterministically")
+                .clicked()
+            {
+                self.load_scenario_text();
+            }
+            if ui
+                .button("Current")
+                .on_hover_text("Fills in the current configuration and copies it to the clipboard")
+                .clicked()
+            {
+                self.scenario_text = self.scenario().to_json();
+                ui.output_mut(|o| o.copied_text = self.scenario_text.clone());
+            }
+            ui.label("Seed");
+            ui.add(DragValue::new(&mut self.scenario_seed));
+        });
+        if let Some(error) = &self.scenario_error {
+            ui.colored_label(Color32::RED, error);
+        }
+        egui::ScrollArea::vertical()
+            .max_height(400.0)
+            .show(ui, |ui| {
                 ui.add(
-                    DragValue::new(self.input.noise.get_mut())
-                        .speed(0.001)
-                        .clamp_range(0.0..=1.0),
+                    egui::TextEdit::multiline(&mut self.scenario_text)
+                        .code_editor()
+                        .desired_width(f32::INFINITY),
                 );
-                ui.separator();
-                ui.label("Target");
+            });
+    }
+    fn config_ui(&mut self, ui: &mut egui::Ui) {
+        // Without a file dialog, the file is entered by hand on native
+        #[cfg(not(target_arch = "wasm32"))]
+        ui.horizontal(|ui| {
+            ui.label("File");
+            ui.add(egui::TextEdit::singleline(&mut self.config_path).hint_text(CONFIG_FILE));
+        });
+        ui.label("Paste a configuration or drop a configuration file onto the window");
+        ui.horizontal(|ui| {
+            #[cfg(not(target_arch = "wasm32"))]
+            let save_hint = "Writes every parameter to the file";
+            #[cfg(target_arch = "wasm32")]
+            let save_hint = format!("Downloads every parameter as {CONFIG_FILE}");
+            if ui.button("Save config").on_hover_text(save_hint).clicked() {
+                self.save_config();
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            if ui
+                .button("Load config")
+                .on_hover_text("Reads every parameter from the file")
+                .clicked()
+            {
+                self.load_config();
+            }
+            if ui
+                .button("Apply")
+                .on_hover_text("Configures everything from the text without restarting")
+                .clicked()
+            {
+                self.load_config_text();
+            }
+        });
+        if let Some(error) = &self.config_error {
+            ui.colored_label(Color32::RED, error);
+        }
+        egui::ScrollArea::vertical()
+            .max_height(400.0)
+            .show(ui, |ui| {
                 ui.add(
-                    DragValue::new(self.input.target.get_mut())
-                        .speed(0.01)
-                        .clamp_range(0.25..=0.75),
+                    egui::TextEdit::multiline(&mut self.config_text)
+                        .code_editor()
+                        .desired_width(f32::INFINITY),
                 );
-                ui.separator();
-                ui.label("Sampling Rate [Hz]");
+            });
+    }
+    fn controls_ui(&mut self, ui: &mut egui::Ui) {
+        let (icon, hover) = if self.settings.dark_mode {
+            ("☀", "Switches to the light theme")
+        } else {
+            ("🌙", "Switches to the dark theme")
+        };
+        if ui.button(icon).on_hover_text(hover).clicked() {
+            self.settings.dark_mode = !self.settings.dark_mode;
+            ui.ctx().set_visuals(visuals(self.settings.dark_mode));
+        }
+        ui.label("?").on_hover_text(
+            "Keyboard shortcuts, unless a text field is focused\n\
+            Space: pause or resume\n\
+            R: restart\n\
+            Shift + R: reset\n\
+            →: step while paused\n\
+            + / -: faster or slower",
+        );
+        if ui
+            .button("Reset")
+            .on_hover_text("Clears plots and resets with default values")
+            .clicked()
+        {
+            self.reset()
+        }
+        if ui
+            .button("Restart")
+            .on_hover_text("Clears plots and resets with given values")
+            .clicked()
+        {
+            self.restart();
+        }
+        if self.paused {
+            if ui
+                .button("Resume")
+                .on_hover_text("Continues in real time")
+                .clicked()
+            {
+                self.pause(false);
+            }
+            if ui
+                .button("Step")
+                .on_hover_text("Advances by one sampling period")
+                .clicked()
+            {
+                self.single_steps += 1;
+            }
+        } else if ui
+            .button("Pause")
+            .on_hover_text("Freezes simulation and plots")
+            .clicked()
+        {
+            self.pause(true);
+        }
+        if ui
+            .button("Save state")
+            .on_hover_text("Remembers the ball, controller and configuration")
+            .clicked()
+        {
+            self.save_state();
+        }
+        if ui
+            .add_enabled(self.saved_state.is_some(), egui::Button::new("Load state"))
+            .on_hover_text("Jumps back to the saved state, with new noise")
+            .clicked()
+        {
+            self.load_state();
+        }
+        ui.label("Speed ×")
+            .on_hover_text("Simulated seconds per real second, for slow motion or fast-forward");
+        ui.add(
+            DragValue::new(&mut self.settings.speed)
+                .speed(0.01)
+                .clamp_range(MIN_SPEED..=MAX_SPEED),
+        );
+        if ui
+            .button("Fit to window")
+            .on_hover_text("Resets zoom and pan of all plots")
+            .clicked()
+        {
+            self.reset_plots = true;
+        }
+        if ui
+            .button("Maglev")
+            .on_hover_text("Restarts with a pull-only magnet above the ball")
+            .clicked()
+        {
+            self.maglev();
+        }
+    }
+    fn noise_ui(&mut self, ui: &mut egui::Ui) {
+        ui.label("Noise [σ]");
+        ui.add(
+            DragValue::new(self.input.noise.get_mut())
+                .speed(0.001)
+                .clamp_range(0.0..=1.0),
+        );
+        egui::ComboBox::from_id_source("noise_kind")
+            .selected_text(self.input.noise_kind.get().name())
+            .show_ui(ui, |ui| {
+                for kind in NoiseKind::ALL {
+                    ui.selectable_value(self.input.noise_kind.get_mut(), kind, kind.name());
+                }
+            })
+            .response
+            .on_hover_text("Distribution of the noise with the same σ");
+        let noise_seed = self.input.noise_seed.get_mut();
+        let mut seeded = noise_seed.is_some();
+        if ui
+            .checkbox(&mut seeded, "Seed")
+            .on_hover_text("Restarts the noise with this seed, so the runs are reproducible")
+            .changed()
+        {
+            *noise_seed = seeded.then_some(NOISE_SEED);
+        }
+        if let Some(seed) = noise_seed {
+            ui.add(DragValue::new(seed));
+        }
+        ui.checkbox(self.input.freeze_noise.get_mut(), "Freeze")
+            .on_hover_text(
+                "Every restart repeats the noise of the current run for a fair comparison",
+            );
+        ui.label("Lag [s]")
+            .on_hover_text("Time constant of the sensor's first-order response, 0 disables it");
+        ui.add(
+            DragValue::new(self.input.sensor_tau.get_mut())
+                .speed(0.001)
+                .clamp_range(0.0..=f32::INFINITY),
+        );
+        ui.label("Sensor delay [ms]")
+            .on_hover_text("Transport delay of the measurement in whole samples");
+        ui.add(
+            // Stored in seconds like every other duration
+            DragValue::new(self.input.sensor_delay.get_mut())
+                .speed(0.001)
+                .clamp_range(0.0..=f32::INFINITY)
+                .custom_formatter(|s, _| format!("{:.0}", s * 1000.0))
+                .custom_parser(|text| text.parse::<f64>().ok().map(|ms| ms / 1000.0)),
+        );
+        ui.label("ADC step [m]")
+            .on_hover_text("Resolution of the measurement, 0 disables the quantization");
+        ui.add(
+            DragValue::new(self.input.sensor_resolution.get_mut())
+                .speed(0.0001)
+                .clamp_range(0.0..=f32::INFINITY),
+        );
+    }
+    fn target_ui(&mut self, ui: &mut egui::Ui) {
+        ui.label("Target")
+            .on_hover_text("Drag, or click to type an exact value");
+        let settings = &self.settings;
+        let step = settings.arrow_keys.then_some(settings.target_step);
+        ui.add(
+            drag_value(ui, self.input.target.get_mut(), settings.target_speed, step)
+                .max_decimals(settings.target_decimals)
+                .clamp_range(0.25..=0.75),
+        );
+        ui.menu_button("⚙", |ui| {
+            let settings = &mut self.settings;
+            egui::Grid::new("target_input").show(ui, |ui| {
+                ui.label("Drag speed")
+                    .on_hover_text("Change of the target per pixel of dragging");
                 ui.add(
-                    DragValue::new(self.input.sampling_rate.get_mut())
-                        .speed(0.1)
-                        .clamp_range(1..=u32::MAX),
+                    DragValue::new(&mut settings.target_speed)
+                        .speed(0.0001)
+                        .clamp_range(0.0001..=0.1),
                 );
-                ui.separator();
-                ui.label("P");
-                ui.add(DragValue::new(self.input.kp.get_mut()).speed(1));
-                ui.separator();
-                ui.label("I");
-                ui.add(DragValue::new(self.input.ki.get_mut()).speed(0.01));
-                ui.separator();
-                ui.label("D");
-                ui.add(DragValue::new(self.input.kd.get_mut()).speed(0.1));
-
-                // Link to egui
-                ui.separator();
-                ui.hyperlink_to("Source", "https://github.com/raui100/pid_ball");
-                ui.hyperlink_to("Made with egui", "https://github.com/emilk/egui");
+                ui.end_row();
+                ui.label("Decimals");
+                ui.add(DragValue::new(&mut settings.target_decimals).clamp_range(1..=6));
+                ui.end_row();
+                ui.checkbox(&mut settings.arrow_keys, "Arrow keys")
+                    .on_hover_text("Steps by a fixed increment while typing");
+                ui.add_enabled(
+                    settings.arrow_keys,
+                    DragValue::new(&mut settings.target_step)
+                        .speed(0.0001)
+                        .clamp_range(0.0001..=0.5),
+                );
+                ui.end_row();
             });
-        });
-        egui::TopBottomPanel::top("config2").show(ctx, |ui| {
-            ui.horizontal(|ui| {
-                // Hold/Drop ball
-                if self.input.hold_ball.get() {
+        })
+        .response
+        .on_hover_text("Precision of the target input");
+        let target = self.input.target.get();
+        let profile = self.input.target_profile.get_mut();
+        egui::ComboBox::from_id_source("target_profile")
+            .selected_text(profile.name())
+            .show_ui(ui, |ui| {
+                for kind in TargetProfile::all(target) {
+                    let selected = kind.name() == profile.name();
+                    if ui.selectable_label(selected, kind.name()).clicked() && !selected {
+                        *profile = kind;
+                    }
+                }
+            })
+            .response
+            .on_hover_text("Time-varying target for studying the tracking");
+        let drag = |ui: &mut egui::Ui, label: &str, value: &mut f32, speed: f64| {
+            ui.label(label);
+            ui.add(DragValue::new(value).speed(speed));
+        };
+        match profile {
+            TargetProfile::Constant(_) => (),
+            TargetProfile::Ramp { from, to, duration } => {
+                drag(ui, "From", from, 0.01);
+                drag(ui, "To", to, 0.01);
+                drag(ui, "Duration [s]", duration, 0.1);
+            }
+            TargetProfile::Sine {
+                center,
+                amplitude,
+                freq_hz,
+            } => {
+                drag(ui, "Center", center, 0.01);
+                drag(ui, "Amplitude", amplitude, 0.01);
+                drag(ui, "Frequency [Hz]", freq_hz, 0.01);
+            }
+            TargetProfile::Square {
+                low,
+                high,
+                period_s,
+            } => {
+                drag(ui, "Low", low, 0.01);
+                drag(ui, "High", high, 0.01);
+                drag(ui, "Period [s]", period_s, 0.1);
+            }
+        }
+    }
+    fn timing_ui(&mut self, ui: &mut egui::Ui) {
+        ui.label("Sampling Rate [Hz]");
+        ui.add(
+            DragValue::new(self.input.sampling_rate.get_mut())
+                .speed(0.1)
+                .clamp_range(1..=u32::MAX),
+        );
+        ui.separator();
+        ui.label("Sub-steps")
+            .on_hover_text("Physics steps per sampling step for a more accurate integration");
+        ui.add(
+            DragValue::new(self.input.sub_steps.get_mut())
+                .speed(0.1)
+                .clamp_range(1..=1000),
+        );
+        ui.separator();
+        ui.label("Control divider")
+            .on_hover_text("The controller only updates every n-th sampling step");
+        ui.add(
+            DragValue::new(self.input.control_divider.get_mut())
+                .speed(0.1)
+                .clamp_range(1..=1000),
+        );
+    }
+    fn gains_ui(&mut self, ui: &mut egui::Ui, sampling_time: Duration) {
+        ui.label("P");
+        ui.add(DragValue::new(self.input.kp.get_mut()).speed(1));
+        ui.separator();
+        ui.label("I");
+        ui.add(DragValue::new(self.input.ki.get_mut()).speed(0.01));
+        let i_limit = self.input.i_limit.get_mut();
+        let mut limited = i_limit.is_some();
+        if ui
+            .checkbox(&mut limited, "Limit")
+            .on_hover_text("Clamps the integral term against windup")
+            .changed()
+        {
+            *i_limit = limited.then_some(INTEGRAL_LIMIT);
+        }
+        if let Some(i_limit) = i_limit {
+            ui.add(
+                DragValue::new(i_limit)
+                    .speed(0.1)
+                    .clamp_range(0.0..=f32::INFINITY)
+                    .prefix("±")
+                    .suffix(" N"),
+            );
+        }
+        let i_band = self.input.i_band.get_mut();
+        let mut separated = i_band.is_some();
+        if ui
+            .checkbox(&mut separated, "Band")
+            .on_hover_text(
+                "Integral separation: only integrates while the error is within the band \
+                and holds the integral outside, which reduces overshoot on large steps",
+            )
+            .changed()
+        {
+            *i_band = separated.then_some(INTEGRAL_BAND);
+        }
+        if let Some(i_band) = i_band {
+            ui.add(
+                DragValue::new(i_band)
+                    .speed(0.001)
+                    .clamp_range(0.0..=f32::INFINITY)
+                    .prefix("±")
+                    .suffix(" m"),
+            );
+        }
+        ui.separator();
+        ui.label("D");
+        ui.add(DragValue::new(self.input.kd.get_mut()).speed(0.1));
+        ui.label("D filter [s]")
+            .on_hover_text("Time constant of the low-pass on the derivative term, 0 disables it");
+        ui.add(
+            DragValue::new(self.input.d_filter_tau.get_mut())
+                .speed(0.001)
+                .clamp_range(0.0..=f32::INFINITY),
+        );
+        ui.label("FF [N]")
+            .on_hover_text("Feed-forward added to the output, e.g. against the gravitation");
+        ui.add(DragValue::new(self.input.kff.get_mut()).speed(0.1));
+        let output_limit = self.input.output_limit.get_mut();
+        let mut clamped = output_limit.is_some();
+        if ui
+            .checkbox(&mut clamped, "Clamp")
+            .on_hover_text(
+                "Clamps the command of the controller, independent of the maximum \
+                force of the inductor",
+            )
+            .changed()
+        {
+            *output_limit = clamped.then_some(CONTROLLER_OUTPUT_LIMIT);
+        }
+        if let Some(output_limit) = output_limit {
+            ui.add(
+                DragValue::new(output_limit)
+                    .speed(0.1)
+                    .clamp_range(0.0..=f32::INFINITY)
+                    .prefix("±")
+                    .suffix(" N"),
+            );
+        }
+        let mut preset = None;
+        egui::ComboBox::from_id_source("preset")
+            .selected_text("Preset")
+            .show_ui(ui, |ui| {
+                for candidate in &Preset::ALL {
                     if ui
-                        .button("Drop")
-                        .on_hover_text("Holds the ball still")
+                        .selectable_label(false, candidate.name)
+                        .on_hover_text(candidate.description)
                         .clicked()
                     {
-                        self.input.hold_ball.val = false;
+                        preset = Some(candidate);
+                    }
+                }
+            })
+            .response
+            .on_hover_text("Restarts with the gains of a preset");
+        if let Some(preset) = preset {
+            self.apply_preset(preset, sampling_time);
+        }
+        if ui
+            .button("Auto-tune")
+            .on_hover_text(
+                "Raises P of a copy of the simulation until the ball oscillates \
+                and sets the gains by the Ziegler–Nichols rules",
+            )
+            .clicked()
+        {
+            self.auto_tune(sampling_time);
+        }
+        if let Some(status) = &self.tune_status {
+            ui.label(status);
+        }
+    }
+    fn compare_ui(&mut self, ui: &mut egui::Ui) {
+        let mut compare = self.comparison.is_some();
+        if ui
+            .checkbox(&mut compare, "Compare")
+            .on_hover_text(
+                "Runs a copy of the simulation with its own PID gains, \
+                dashed in the plots",
+            )
+            .changed()
+        {
+            let gains = [
+                self.input.kp.get(),
+                self.input.ki.get(),
+                self.input.kd.get(),
+            ];
+            // The samples before the copy don't step the comparison
+            let sim = self.sim.simulation();
+            self.receive();
+            self.comparison = compare.then(|| Comparison::new(&sim, gains));
+        }
+        if let Some(comparison) = &mut self.comparison {
+            for (name, gain) in [
+                ("P₂", &mut comparison.kp),
+                ("I₂", &mut comparison.ki),
+                ("D₂", &mut comparison.kd),
+            ] {
+                ui.label(name);
+                ui.add(DragValue::new(gain.get_mut()).speed(0.1));
+            }
+        }
+    }
+    fn progress_ui(&self, ui: &mut egui::Ui) {
+        ui.label(format!("{:.2} s", self.elapsed.as_secs_f32()))
+            .on_hover_text("Simulated time since the last reset");
+        ui.label(format!("{} steps", self.step_count))
+            .on_hover_text("Simulation steps since the last reset");
+        indicator(ui, "SAT", self.saturation.active())
+            .on_hover_text("The force of the inductor is clipped at its maximum");
+        indicator(ui, "RATE", self.rate_limit.active())
+            .on_hover_text("The force of the inductor is clipped by its maximum rate");
+    }
+    fn disturbance_ui(&mut self, ui: &mut egui::Ui) {
+        // Hold/Drop ball
+        if self.input.hold_ball.get() {
+            if ui
+                .button("Drop")
+                .on_hover_text("Holds the ball still")
+                .clicked()
+            {
+                self.input.hold_ball.val = false;
+            }
+        } else if ui
+            .button("Hold")
+            .on_hover_text("Free floating ball")
+            .clicked()
+        {
+            self.input.hold_ball.val = true;
+        }
+        ui.checkbox(self.input.smooth_release.get_mut(), "Smooth")
+            .on_hover_text("Dropping the ball resets the controller and balances the gravitation");
+        ui.separator();
+
+        // Disturbance
+        if ui
+            .button("Push up")
+            .on_hover_text("Kicks the ball, unless it is held")
+            .clicked()
+        {
+            self.config(Message::Disturbance(self.settings.push));
+        }
+        if ui
+            .button("Push down")
+            .on_hover_text("Kicks the ball, unless it is held")
+            .clicked()
+        {
+            self.config(Message::Disturbance(-self.settings.push));
+        }
+        ui.add(
+            DragValue::new(&mut self.settings.push)
+                .speed(0.01)
+                .clamp_range(0.0..=f32::INFINITY)
+                .suffix(" m/s"),
+        );
+    }
+    fn physics_ui(&mut self, ui: &mut egui::Ui) {
+        // Gravity
+        ui.label("Gravity [m/s²]").on_hover_text(
+            "Downward acceleration, '9.81' on earth. Negative values pull the ball up",
+        );
+        ui.add(DragValue::new(self.input.gravity.get_mut()).speed(0.1));
+        ui.label("Process noise [σ N]")
+            .on_hover_text("Random force on the ball, e.g. by air currents");
+        ui.add(
+            DragValue::new(self.input.process_noise.get_mut())
+                .speed(0.01)
+                .clamp_range(0.0..=f32::INFINITY),
+        );
+        ui.separator();
+
+        // Mass
+        ui.label("Mass [kg]");
+        ui.add(
+            DragValue::new(self.input.mass.get_mut())
+                .speed(0.01)
+                .clamp_range(MIN_MASS..=f32::INFINITY),
+        );
+        ui.separator();
+
+        // Tube
+        ui.label("Tube [m]")
+            .on_hover_text("The ball stays between floor and ceiling");
+        let ceiling = self.input.ceiling.get();
+        ui.add(
+            DragValue::new(self.input.floor.get_mut())
+                .speed(0.01)
+                .clamp_range(f32::NEG_INFINITY..=ceiling),
+        );
+        let floor = self.input.floor.get();
+        ui.add(
+            DragValue::new(self.input.ceiling.get_mut())
+                .speed(0.01)
+                .clamp_range(floor..=f32::INFINITY),
+        );
+        ui.label("Restitution").on_hover_text(
+            "Ratio of the speeds after and before hitting floor or ceiling. \
+            0 is inelastic, 1 elastic",
+        );
+        ui.add(
+            DragValue::new(self.input.restitution.get_mut())
+                .speed(0.01)
+                .clamp_range(0.0..=1.0),
+        );
+        ui.separator();
+
+        // Coupling between inductor and ball
+        let coupling = self.input.coupling.get_mut();
+        egui::ComboBox::from_id_source("coupling")
+            .selected_text(coupling.name())
+            .show_ui(ui, |ui| {
+                for model in CouplingModel::ALL {
+                    // Keeping the decay of the exponential model when selecting it again
+                    let selected =
+                        std::mem::discriminant(coupling) == std::mem::discriminant(&model);
+                    if ui.selectable_label(selected, model.name()).clicked() && !selected {
+                        *coupling = model;
+                    }
+                }
+            })
+            .response
+            .on_hover_text("How the inductor force falls off with the distance to the ball");
+        if let CouplingModel::Exponential { k } = coupling {
+            ui.label("k [1/m]")
+                .on_hover_text("Decay of the force with the distance");
+            ui.add(DragValue::new(k).speed(0.01).clamp_range(0.0..=f32::MAX));
+        }
+        ui.label("Softening").on_hover_text(
+            "The inductor force falls off with 1 / (softening + distance²), \
+            or 1 / (√softening + distance) with the inverse linear coupling. \
+            Smaller values sharpen the near field",
+        );
+        ui.add(
+            DragValue::new(self.input.softening.get_mut())
+                .speed(0.01)
+                .clamp_range(0.001..=f32::MAX),
+        );
+        ui.separator();
+
+        // Max. velocity
+        let max_velocity = self.input.max_velocity.get_mut();
+        let mut limited = max_velocity.is_some();
+        if ui
+            .checkbox(&mut limited, "Max. velocity")
+            .on_hover_text("Limits the speed of the ball like a viscous medium")
+            .changed()
+        {
+            *max_velocity = limited.then_some(VELOCITY_LIMIT);
+        }
+        if let Some(max_velocity) = max_velocity {
+            ui.add(
+                DragValue::new(max_velocity)
+                    .speed(0.1)
+                    .clamp_range(0.0..=f32::INFINITY)
+                    .suffix(" m/s"),
+            );
+        }
+    }
+    fn inductor_ui(&mut self, ui: &mut egui::Ui) {
+        // Max force
+        ui.label("Max. force up [N]")
+            .on_hover_text("Limit of the upward force");
+        ui.add(
+            DragValue::new(self.input.max_force_up.get_mut())
+                .speed(1.0)
+                .clamp_range(0.0..=f32::INFINITY),
+        );
+        ui.label("down [N]")
+            .on_hover_text("Limit of the downward force");
+        ui.add(
+            DragValue::new(self.input.max_force_down.get_mut())
+                .speed(1.0)
+                .clamp_range(0.0..=f32::INFINITY),
+        );
+        if ui
+            .button("Magnet")
+            .on_hover_text("An electromagnet pulls the ball upwards but can't push it")
+            .clicked()
+        {
+            self.input.max_force_down.val = 0.0;
+        }
+        ui.label("Deadband [N]")
+            .on_hover_text("Smaller requested forces count as zero");
+        ui.add(
+            DragValue::new(self.input.deadband.get_mut())
+                .speed(0.1)
+                .clamp_range(0.0..=f32::INFINITY),
+        );
+        ui.label("Dead time [ms]")
+            .on_hover_text("Delay between the controller and the force of the inductor");
+        ui.add(
+            // Stored in seconds like every other duration
+            DragValue::new(self.input.dead_time.get_mut())
+                .speed(0.001)
+                .clamp_range(0.0..=f32::INFINITY)
+                .custom_formatter(|s, _| format!("{:.0}", s * 1000.0))
+                .custom_parser(|text| text.parse::<f64>().ok().map(|ms| ms / 1000.0)),
+        );
+        ui.separator();
+
+        // Max force rate
+        ui.label("Max. force rate [N/s]")
+            .on_hover_text("Limits the rate with which the force can adapt");
+        ui.add(
+            DragValue::new(self.input.max_force_rate.get_mut())
+                .speed(0.1)
+                .clamp_range(0.0..=f32::INFINITY),
+        );
+        ui.separator();
+
+        // Initial force
+        ui.label("Initial force [N]").on_hover_text(
+            "Force of the inductor after a restart. '9.81' cancels earth-like gravitation",
+        );
+        ui.add(DragValue::new(self.input.initial_force.get_mut()).speed(0.1));
+        ui.separator();
+
+        // Actuator gain
+        ui.label("Actuator gain")
+            .on_hover_text("Force of the inductor per unit of controller output");
+        ui.add(DragValue::new(self.input.actuator_gain.get_mut()).speed(0.01));
+        ui.checkbox(self.input.invert_output.get_mut(), "Invert")
+            .on_hover_text("The actuator pushes against the controller output");
+        ui.separator();
+
+        // Output low-pass
+        ui.label("Output cutoff [Hz]").on_hover_text(
+            "Low-pass on the controller output against actuator chatter, 0 disables it",
+        );
+        ui.add(
+            DragValue::new(self.input.output_cutoff.get_mut())
+                .speed(0.1)
+                .clamp_range(0.0..=f32::INFINITY),
+        );
+        ui.separator();
+
+        // Inductor following the target
+        ui.checkbox(self.input.follow_target.get_mut(), "Follow target")
+            .on_hover_text(
+                "Moves the inductor along with the target, as if the whole apparatus moved",
+            );
+        if self.input.follow_target.get() {
+            ui.label("Rate [m/s]")
+                .on_hover_text("Maximum speed of the inductor");
+            ui.add(
+                DragValue::new(self.input.follow_rate.get_mut())
+                    .speed(0.01)
+                    .clamp_range(0.0..=f32::INFINITY),
+            );
+        }
+    }
+    fn rendering_ui(&mut self, ui: &mut egui::Ui) {
+        // Reference
+        ui.checkbox(self.input.reference.get_mut(), "Reference")
+            .on_hover_text(
+                "Plots the position of a noise-free copy with a much finer physics step, \
+            which shows the integration error of the simulation",
+            );
+        ui.separator();
+
+        // Repaint rate
+        ui.checkbox(&mut self.settings.max_fps, "Max. FPS")
+            .on_hover_text("Repaints with every sample, up to the refresh rate");
+        if !self.settings.max_fps {
+            ui.label("FPS cap")
+                .on_hover_text("Saves power by repainting less often");
+            ui.add(
+                DragValue::new(&mut self.settings.fps_cap)
+                    .speed(0.1)
+                    .clamp_range(1..=240),
+            );
+        }
+        ui.label("Max. steps")
+            .on_hover_text("Steps per frame, beyond which a stalled GUI skips time");
+        ui.add(
+            DragValue::new(&mut self.settings.max_steps)
+                .speed(1.0)
+                .clamp_range(1..=u32::MAX),
+        );
+    }
+    fn plot_settings_ui(&mut self, ui: &mut egui::Ui) {
+        // Visibility of the plotted traces
+        ui.menu_button("Traces", |ui| {
+            let mut palette = self.palette();
+            for trace in Trace::ALL {
+                ui.horizontal(|ui| {
+                    let color = palette.trace(trace);
+                    if egui::color_picker::color_edit_button_srgba(
+                        ui,
+                        color,
+                        egui::color_picker::Alpha::Opaque,
+                    )
+                    .changed()
+                    {
+                        self.settings.colors[trace as usize] = Some(*color);
                     }
-                } else if ui
-                    .button("Hold")
-                    .on_hover_text("Free floating ball")
+                    let mut visible = !self.hidden.contains(&trace);
+                    if ui.checkbox(&mut visible, trace.name()).changed() {
+                        if visible {
+                            self.hidden.remove(&trace);
+                        } else {
+                            self.hidden.insert(trace);
+                        }
+                    }
+                });
+            }
+            if ui
+                .button("Theme colors")
+                .on_hover_text("Discards the picked colors")
+                .clicked()
+            {
+                self.settings.colors = Default::default();
+            }
+            ui.separator();
+            ui.horizontal(|ui| {
+                if ui
+                    .button("Show all")
+                    .on_hover_text("Also shows traces hidden via the legend")
                     .clicked()
                 {
-                    self.input.hold_ball.val = true;
+                    self.hidden.clear();
+                    self.reset_plots = true;
                 }
-                ui.separator();
+                if ui.button("Hide all").clicked() {
+                    self.hidden.extend(Trace::ALL);
+                }
+            });
+            ui.separator();
+            ui.checkbox(
+                &mut self.settings.force_as_acceleration,
+                "Force as acceleration",
+            )
+            .on_hover_text("Divides the force by the mass of the ball");
+            ui.checkbox(&mut self.settings.pid_components, "Show PID components")
+                .on_hover_text("Overlays the terms of the PID controller on the force");
+            ui.separator();
+            ui.label("Plots");
+            for subplot in Subplot::ALL {
+                ui.checkbox(
+                    &mut self.settings.subplots[subplot as usize],
+                    subplot.name(),
+                );
+            }
+            ui.separator();
+            ui.label("Position and force")
+                .on_hover_text("Makes the discrete sampling visible");
+            for display in SampleDisplay::ALL {
+                ui.radio_value(&mut self.settings.sample_display, display, display.name());
+            }
+        });
 
-                // Gravitation
-                ui.label("Gravitation")
-                    .on_hover_text("Use '-9.81' for earth-like gravitation");
-                ui.add(DragValue::new(self.input.gravitation.get_mut()).speed(0.1));
-                ui.separator();
+        // Signal of the fourth plot
+        ui.label("Extra plot");
+        egui::ComboBox::from_id_source("channel")
+            .selected_text(self.settings.channel.map_or("None", Channel::name))
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut self.settings.channel, None, "None");
+                for channel in Channel::ALL {
+                    ui.selectable_value(&mut self.settings.channel, Some(channel), channel.name());
+                }
+            });
 
-                // Max force
-                ui.label("Max. force [N]")
-                    .on_hover_text("The ball weighs 1 Kg");
-                ui.add(
-                    DragValue::new(self.input.max_force.get_mut())
-                        .speed(1.0)
-                        .clamp_range(0.0..=f32::INFINITY),
-                );
-                ui.separator();
+        // Time window
+        if ui
+            .checkbox(&mut self.settings.scroll, "Follow")
+            .on_hover_text(
+                "Scrolls with the latest seconds instead of showing the full history. \
+                Only the value axes can be panned and zoomed meanwhile",
+            )
+            .changed()
+        {
+            // Drops a panned or zoomed view, which would keep the time axis in place
+            self.reset_plots = true;
+        }
+        if self.settings.scroll {
+            ui.add(
+                DragValue::new(&mut self.settings.time_window)
+                    .speed(0.1)
+                    .clamp_range(0.1..=f32::INFINITY)
+                    .suffix(" s"),
+            );
+        }
+        ui.label("History:");
+        ui.add(
+            DragValue::new(&mut self.settings.history)
+                .speed(1.0)
+                .clamp_range(1.0..=f32::INFINITY)
+                .suffix(" s"),
+        )
+        .on_hover_text("Older data is discarded to bound the memory");
 
-                // Max force rate
-                ui.label("Max. force rate [N/s]")
-                    .on_hover_text("Limits the rate with which the force can adapt");
+        // Clipboard
+        if ui
+            .button("Copy data")
+            .on_hover_text("Copies the plotted data as tab separated values")
+            .clicked()
+        {
+            let table = self.table("\t");
+            ui.output_mut(|o| o.copied_text = table);
+        }
+        if ui
+            .button("Import CSV")
+            .on_hover_text("Shows a recorded run behind the live one")
+            .clicked()
+        {
+            self.ghost_open = true;
+        }
+        self.export_png |= ui
+            .button("Export PNG")
+            .on_hover_text(format!("Saves the plots as {PNG_FILE}"))
+            .clicked();
+
+        // Style of every plot
+        ui.menu_button("Plot style", |ui| {
+            let settings = &mut self.settings;
+            egui::Grid::new("line_widths").show(ui, |ui| {
+                for trace in Trace::ALL {
+                    ui.label(trace.name());
+                    ui.add(
+                        DragValue::new(&mut settings.line_widths[trace as usize])
+                            .speed(0.1)
+                            .clamp_range(0.5..=10.0)
+                            .suffix(" px"),
+                    );
+                    ui.end_row();
+                }
+            });
+            ui.checkbox(&mut settings.highlight, "Highlight")
+                .on_hover_text("Emphasizes position, velocity and force");
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut settings.playhead, "Playhead")
+                    .on_hover_text("Marks the latest sample of every plot");
+                ui.add_enabled(
+                    settings.playhead,
+                    egui::Checkbox::new(&mut settings.playhead_value, "Value"),
+                );
+            });
+            ui.separator();
+            ui.checkbox(&mut settings.show_grid, "Grid");
+            ui.checkbox(&mut settings.show_background, "Background");
+            ui.horizontal(|ui| {
+                ui.label("Grid lines")
+                    .on_hover_text("Approximate number per axis, 0 picks them automatically");
                 ui.add(
-                    DragValue::new(self.input.max_force_rate.get_mut())
+                    DragValue::new(&mut settings.grid_ticks)
                         .speed(0.1)
-                        .clamp_range(0.0..=f32::INFINITY),
+                        .clamp_range(0..=50),
                 );
             });
+            ui.separator();
+            ui.label("SI prefixes")
+                .on_hover_text("Scales the value axis, e.g. to mm, instead of base units");
+            ui.horizontal(|ui| {
+                for subplot in Subplot::ALL {
+                    ui.checkbox(&mut settings.si_prefixes[subplot as usize], subplot.name());
+                }
+                ui.checkbox(&mut settings.channel_si_prefix, "Extra plot");
+            });
+            ui.separator();
+            ui.horizontal(|ui| {
+                if ui
+                    .button("Publication")
+                    .on_hover_text("Plots without grid and background")
+                    .clicked()
+                {
+                    settings.show_grid = false;
+                    settings.show_background = false;
+                }
+                if ui.button("Default").clicked() {
+                    settings.line_widths = [LINE_WIDTH; Trace::ALL.len()];
+                    settings.highlight = HIGHLIGHT;
+                    settings.playhead = PLAYHEAD;
+                    settings.playhead_value = PLAYHEAD_VALUE;
+                    settings.show_grid = SHOW_GRID;
+                    settings.show_background = SHOW_BACKGROUND;
+                    settings.grid_ticks = GRID_TICKS;
+                    settings.si_prefixes = [SI_PREFIXES; Subplot::ALL.len()];
+                    settings.channel_si_prefix = SI_PREFIXES;
+                }
+            });
         });
+    }
+    fn controller_ui(&mut self, ui: &mut egui::Ui) {
+        // Control law
+        ui.label("Controller");
+        egui::ComboBox::from_id_source("controller")
+            .selected_text(self.input.controller.get().name())
+            .show_ui(ui, |ui| {
+                for kind in ControllerKind::ALL {
+                    ui.selectable_value(self.input.controller.get_mut(), kind, kind.name());
+                }
+            });
+        if self.input.controller.get() == ControllerKind::LeadLag {
+            ui.label("K")
+                .on_hover_text("Static gain of the compensator");
+            ui.add(DragValue::new(self.input.lead_lag_gain.get_mut()).speed(1));
+            ui.label("Lead [s]")
+                .on_hover_text("Time constant of the zero");
+            ui.add(
+                DragValue::new(self.input.lead_time.get_mut())
+                    .speed(0.001)
+                    .clamp_range(0.0..=f32::INFINITY),
+            );
+            ui.label("Lag [s]")
+                .on_hover_text("Time constant of the pole");
+            ui.add(
+                DragValue::new(self.input.lag_time.get_mut())
+                    .speed(0.001)
+                    .clamp_range(0.0..=f32::INFINITY),
+            );
+            ui.label("Bias [N]")
+                .on_hover_text("Constant output, e.g. to carry the weight of the ball");
+            ui.add(DragValue::new(self.input.lead_lag_bias.get_mut()).speed(0.1));
+        }
+        if self.input.controller.get() == ControllerKind::BangBang {
+            ui.label("Force [N]")
+                .on_hover_text("Output is either plus or minus this");
+            ui.add(DragValue::new(self.input.bang_bang_force.get_mut()).speed(0.1));
+            ui.label("Hysteresis [m]")
+                .on_hover_text("Band around the target without switching");
+            ui.add(
+                DragValue::new(self.input.bang_bang_hysteresis.get_mut())
+                    .speed(0.001)
+                    .clamp_range(0.0..=f32::INFINITY),
+            );
+        }
+        ui.separator();
 
-        // Painting the ball
-        let y_width = ctx.available_rect().width();
-        egui::SidePanel::left("ball")
-            .default_width(y_width * 0.2) // Gives 20% of the space to the animation
-            .show(ctx, |ui| {
-                if let Some(&pos) = self.pos.last() {
-                    let Vec2 { x, y } = ui.available_size();
-                    let radius = x * 0.8 * 0.5; // Taking 80% of the available space
-                    let y_ball = y - pos * y;
-                    let x_ball = x * 0.56;
-                    ui.painter()
-                        .circle_filled(Pos2::new(x_ball, y_ball), radius, Color32::RED);
+        // Proportional mode
+        ui.label("P on")
+            .on_hover_text("Acting on the measurement reduces overshoot on setpoint steps");
+        egui::ComboBox::from_id_source("p_mode")
+            .selected_text(self.input.p_mode.get().name())
+            .show_ui(ui, |ui| {
+                for mode in ProportionalMode::ALL {
+                    ui.selectable_value(self.input.p_mode.get_mut(), mode, mode.name());
                 }
             });
+        ui.separator();
 
-        // Plotting position and velocity of the ball
-        egui::CentralPanel::default().show(ctx, |ui| {
-            let height = ui.available_height() / 3.0;
-            let group_id = ui.id().with("x_axis");
-            let line = |y: &[f32]| {
-                PlotPoints::from_iter(
-                    self.seconds
+        // Derivative mode
+        ui.label("D on")
+            .on_hover_text("Acting on the error kicks the force on setpoint steps");
+        egui::ComboBox::from_id_source("d_mode")
+            .selected_text(self.input.d_mode.get().name())
+            .show_ui(ui, |ui| {
+                for mode in DerivativeMode::ALL {
+                    ui.selectable_value(self.input.d_mode.get_mut(), mode, mode.name());
+                }
+            });
+        ui.separator();
+
+        // Derivative after reset
+        ui.label("D start").on_hover_text(
+            "Skip: the first sample after a reset leaves the derivative untouched\n\
+            Seed: the first sample is its own predecessor, so the derivative starts at zero",
+        );
+        egui::ComboBox::from_id_source("d_start")
+            .selected_text(self.input.d_start.get().name())
+            .show_ui(ui, |ui| {
+                for start in DerivativeStart::ALL {
+                    ui.selectable_value(self.input.d_start.get_mut(), start, start.name());
+                }
+            });
+        ui.separator();
+
+        // Source of the derivative
+        ui.label("D from").on_hover_text(
+            "Position: differences of the noisy positions, which amplify the noise\n\
+            Velocity sensor: a dedicated sensor with its own noise",
+        );
+        egui::ComboBox::from_id_source("d_source")
+            .selected_text(self.input.d_source.get().name())
+            .show_ui(ui, |ui| {
+                for source in DerivativeSource::ALL {
+                    ui.selectable_value(self.input.d_source.get_mut(), source, source.name());
+                }
+            });
+        if self.input.d_source.get() == DerivativeSource::FromVelocitySensor {
+            ui.label("Noise [σ m/s]")
+                .on_hover_text("Standard deviation of the velocity sensor");
+            ui.add(
+                DragValue::new(self.input.vel_noise.get_mut())
+                    .speed(0.001)
+                    .clamp_range(0.0..=10.0),
+            );
+        }
+        ui.separator();
+
+        // Unit of the integral gain
+        ui.label("I").on_hover_text(
+            "Per sample: the integral action changes with the sampling rate\n\
+            Per second: the integral is scaled by the sampling time, \
+            so Ki has to be larger by the sampling rate",
+        );
+        egui::ComboBox::from_id_source("i_mode")
+            .selected_text(self.input.i_mode.get().name())
+            .show_ui(ui, |ui| {
+                for mode in IntegralMode::ALL {
+                    ui.selectable_value(self.input.i_mode.get_mut(), mode, mode.name());
+                }
+            });
+        ui.checkbox(self.input.preload.get_mut(), "Preload")
+            .on_hover_text("Restarts with the integral that holds the ball at the target");
+        ui.separator();
+
+        // Back-calculation anti-windup
+        ui.label("Kt").on_hover_text(
+            "Corrects the integral by Kt × (realized - requested force) while saturated. \
+            0 disables the anti-windup",
+        );
+        ui.add(
+            DragValue::new(self.input.kt.get_mut())
+                .speed(0.01)
+                .clamp_range(0.0..=f32::INFINITY),
+        );
+        ui.checkbox(self.input.conditional.get_mut(), "Conditional")
+            .on_hover_text(
+                "Freezes the integral while the inductor clips the force by rate or magnitude",
+            );
+    }
+    /// Dead zone, filtering of the measurement and the start of the loop
+    fn loop_ui(&mut self, ui: &mut egui::Ui) {
+        // Dead zone
+        ui.label("Dead zone [m]").on_hover_text(
+            "Errors up to this size count as zero, which trades a small offset \
+            for less actuator hunting",
+        );
+        ui.add(
+            DragValue::new(self.input.dead_zone.get_mut())
+                .speed(0.001)
+                .clamp_range(0.0..=0.5),
+        );
+        ui.separator();
+
+        // Sensor outliers
+        ui.label("Outliers")
+            .on_hover_text("Chance of every measurement to be a spike");
+        ui.add(
+            DragValue::new(self.input.outlier_probability.get_mut())
+                .speed(0.001)
+                .clamp_range(0.0..=1.0),
+        );
+        if self.input.outlier_probability.get() > 0.0 {
+            ui.label("±");
+            ui.add(
+                DragValue::new(self.input.outlier_magnitude.get_mut())
+                    .speed(0.01)
+                    .suffix(" m"),
+            );
+        }
+        ui.separator();
+
+        // Median filter
+        ui.label("Median of").on_hover_text(
+            "Filters the measurement with the median of the last samples, 1 disables it",
+        );
+        ui.add(
+            DragValue::new(self.input.median_window.get_mut())
+                .speed(0.1)
+                .clamp_range(1..=99),
+        );
+        ui.separator();
+
+        // Initial conditions
+        ui.label("Initial position [m]")
+            .on_hover_text("Position of the ball after a restart");
+        ui.add(DragValue::new(self.input.init_pos.get_mut()).speed(0.01));
+        ui.label("Initial velocity [m/s]")
+            .on_hover_text("Velocity of the ball after a restart");
+        ui.add(DragValue::new(self.input.init_vel.get_mut()).speed(0.01));
+
+        // Random start
+        ui.checkbox(self.input.random_start.get_mut(), "Random start")
+            .on_hover_text("Every restart draws the position and velocity of the ball");
+        if self.input.random_start.get() {
+            ui.label("±");
+            ui.add(
+                DragValue::new(self.input.pos_spread.get_mut())
+                    .speed(0.01)
+                    .clamp_range(0.0..=f32::INFINITY)
+                    .suffix(" m"),
+            );
+            ui.label("±");
+            ui.add(
+                DragValue::new(self.input.vel_spread.get_mut())
+                    .speed(0.01)
+                    .clamp_range(0.0..=f32::INFINITY)
+                    .suffix(" m/s"),
+            );
+        }
+        ui.separator();
+
+        // Late engagement
+        ui.label("Engage after [s]").on_hover_text(
+            "The loop stays open after a restart and the inductor keeps the initial force",
+        );
+        ui.add(
+            DragValue::new(self.input.engage_delay.get_mut())
+                .speed(0.01)
+                .clamp_range(0.0..=f32::INFINITY),
+        );
+    }
+    fn tools_ui(&mut self, ui: &mut egui::Ui, sampling_time: Duration) {
+        // Robustness
+        if ui
+            .button("Monte Carlo")
+            .on_hover_text("Step responses with randomly perturbed gains")
+            .clicked()
+        {
+            self.monte_carlo_open = true;
+        }
+        if ui
+            .button("Analyze")
+            .on_hover_text(
+                "Frequency response from the target to the position, \
+                measured with sines on a copy of the simulation",
+            )
+            .clicked()
+        {
+            self.analyze(sampling_time);
+        }
+        if ui
+            .button("Scenario")
+            .on_hover_text("Loads or shares a reproducible experiment")
+            .clicked()
+        {
+            self.scenario_open = true;
+        }
+        if ui
+            .button("Config")
+            .on_hover_text("Saves or loads every parameter as JSON")
+            .clicked()
+        {
+            self.config_open = true;
+        }
+    }
+    /// Opens the scenario, configuration or recorded run dropped onto the window
+    fn open_dropped(&mut self, ctx: &egui::Context) {
+        let dropped = ctx.input(|i| i.raw.dropped_files.first().cloned());
+        if let Some(file) = dropped {
+            let text = match (&file.bytes, &file.path) {
+                (Some(bytes), _) => Ok(String::from_utf8_lossy(bytes).into_owned()),
+                (None, Some(path)) => std::fs::read_to_string(path).map_err(|e| e.to_string()),
+                (None, None) => Err("Dropped file is empty".to_owned()),
+            };
+            // The web only knows the name of the file
+            let name = file.path.as_ref().map_or(file.name.clone(), |path| {
+                path.to_string_lossy().into_owned()
+            });
+            if name.ends_with(".csv") || name.ends_with(".tsv") {
+                self.ghost_open = true;
+                match text {
+                    Ok(text) => {
+                        self.ghost_text = text;
+                        self.import_ghost_text();
+                    }
+                    Err(e) => self.ghost_error = Some(e),
+                }
+            } else if self.config_open {
+                match text {
+                    Ok(text) => {
+                        self.config_text = text;
+                        self.load_config_text();
+                    }
+                    Err(e) => self.config_error = Some(e),
+                }
+            } else {
+                self.scenario_open = true;
+                match text {
+                    Ok(text) => {
+                        self.scenario_text = text;
+                        self.load_scenario_text();
+                    }
+                    Err(e) => self.scenario_error = Some(e),
+                }
+            }
+        }
+    }
+    fn events_ui(&mut self, ui: &mut egui::Ui) {
+        ui.collapsing("Events", |ui| {
+            if ui.button("Clear").clicked() {
+                self.events.clear();
+            }
+            egui::ScrollArea::vertical()
+                .max_height(150.0)
+                .stick_to_bottom(true)
+                .show(ui, |ui| {
+                    for event in self.events.iter() {
+                        ui.monospace(format!("{:>8.2} s  {}", event.time, event.text));
+                    }
+                });
+        });
+    }
+    fn stats_ui(&mut self, ui: &mut egui::Ui) {
+        ui.collapsing("Statistics", |ui| {
+            let fmt = |val: Option<f32>| val.map_or("-".into(), |v| format!("{v:.4}"));
+            egui::Grid::new("stats_grid").striped(true).show(ui, |ui| {
+                for header in ["", "Mean", "σ", "Min", "Max"] {
+                    ui.strong(header);
+                }
+                ui.end_row();
+                for (name, stats) in [
+                    ("Position [m]", &self.pos_stats),
+                    ("Velocity [m/s]", &self.vel_stats),
+                    ("Force [N]", &self.force_stats),
+                ] {
+                    ui.label(name);
+                    ui.label(fmt(stats.mean()));
+                    ui.label(fmt(stats.std_dev()));
+                    ui.label(fmt(stats.min()));
+                    ui.label(fmt(stats.max()));
+                    ui.end_row();
+                }
+            });
+            ui.label(format!("Samples: {}", self.pos_stats.count()));
+            let errors = self.sim.with(|sim| *sim.errors());
+            ui.horizontal(|ui| {
+                ui.label(format!("IAE: {:.4} m·s", errors.iae))
+                    .on_hover_text("Integral of the absolute error since the last reset");
+                ui.separator();
+                ui.label(format!("ISE: {:.4} m²·s", errors.ise))
+                    .on_hover_text("Integral of the squared error since the last reset");
+                ui.separator();
+                ui.label(format!("ITAE: {:.4} m·s²", errors.itae))
+                    .on_hover_text(
+                        "Integral of the time-weighted absolute error since the last reset",
+                    );
+            });
+        });
+        ui.collapsing("Step response", |ui| {
+            let metrics = self.step_response();
+            let fmt = |val: Option<f32>| val.map_or("-".into(), |v| format!("{v:.3} s"));
+            ui.horizontal(|ui| {
+                ui.label(format!("Rise time (10–90%): {}", fmt(metrics.rise_time)))
+                    .on_hover_text("Not shown until the ball has covered 90% of the step");
+                ui.separator();
+                ui.label(format!("Overshoot: {:.1}%", metrics.overshoot));
+                ui.separator();
+                ui.label(format!(
+                    "Settling time (±2%): {}",
+                    fmt(metrics.settling_time)
+                ))
+                .on_hover_text("Not shown while the ball is outside of the band");
+            });
+        });
+    }
+    fn readouts_ui(&self, ui: &mut egui::Ui) {
+        egui::Grid::new("readouts_grid")
+            .striped(true)
+            .show(ui, |ui| {
+                for (name, value) in self.readouts() {
+                    ui.label(name);
+                    ui.monospace(value);
+                    ui.end_row();
+                }
+            });
+    }
+    fn ball_ui(&self, ui: &mut egui::Ui) {
+        if let Some(&pos) = self.pos.last() {
+            let Vec2 { x, y } = ui.available_size();
+            let radius = x * 0.8 * 0.5; // Taking 80% of the available space
+            let y_ball = y - pos * y;
+            let x_ball = x * 0.56;
+            // In the color of the position trace
+            let color = self.palette().position;
+            ui.painter()
+                .circle_filled(Pos2::new(x_ball, y_ball), radius, color);
+        }
+    }
+    fn plots_ui(&self, ui: &mut egui::Ui, reset_plots: bool) {
+        let palette = self.palette();
+        // The visible plots share the height and the lowest one shows the time axis
+        let shown = |subplot: Subplot| self.settings.subplots[subplot as usize];
+        let lowest = Subplot::ALL.into_iter().filter(|&p| shown(p)).last();
+        let plots = Subplot::ALL.into_iter().filter(|&p| shown(p)).count()
+            + self.settings.channel.is_some() as usize;
+        let height = ui.available_height() / plots.max(1) as f32;
+        let group_id = ui.id().with("x_axis");
+        // Start of the visible time window and index of its first sample
+        let latest = self.seconds.last().copied().unwrap_or_default();
+        let window_start = self
+            .settings
+            .scroll
+            .then_some(latest - self.settings.time_window);
+        let first = window_start.map_or(0, |start| self.seconds.partition_point(|&t| t < start));
+        let points = |y: &[f32]| -> Vec<[f64; 2]> {
+            self.seconds[first..]
+                .iter()
+                .zip(&y[first..])
+                .map(|(x, y)| [*x as f64, *y as f64])
+                .collect()
+        };
+        let line = |y: &[f32]| PlotPoints::from(points(y));
+        // Leaving out the frames without the signal, which are NaN
+        let partial = |y: &[f32]| -> Vec<[f64; 2]> {
+            points(y).into_iter().filter(|[_, y]| !y.is_nan()).collect()
+        };
+        // Faint line of the imported run
+        let ghost = |ui: &mut PlotUi, signal: fn(&GhostRun) -> &[f32], name, color: Color32| {
+            if let Some(ghost) = &self.ghost {
+                let points: PlotPoints = ghost
+                    .seconds
+                    .iter()
+                    .zip(signal(ghost))
+                    .map(|(x, y)| [*x as f64, *y as f64])
+                    .collect();
+                ui.line(
+                    Line::new(points)
+                        .name(name)
+                        .color(color.gamma_multiply(GHOST_ALPHA)),
+                );
+            }
+        };
+        // Line of a sampled signal and its markers, depending on the sample display
+        let sampled = |y: &[f32]| -> (Line, Option<Points>) {
+            let points = points(y);
+            match self.settings.sample_display {
+                SampleDisplay::Line => (Line::new(points), None),
+                SampleDisplay::Stepped => (Line::new(stepped(&points)), None),
+                SampleDisplay::Markers => {
+                    let markers: Vec<[f64; 2]> = points
                         .iter()
-                        .zip(y)
-                        .map(|(x, y)| [*x as f64, *y as f64]),
-                )
+                        .zip(&self.sampled[first..])
+                        .filter_map(|(point, &sampled)| sampled.then_some(*point))
+                        .collect();
+                    (Line::new(points), Some(Points::new(markers).radius(2.0)))
+                }
+            }
+        };
+        let visible = |trace: Trace| !self.hidden.contains(&trace);
+        let width = |trace: Trace| self.settings.line_widths[trace as usize];
+        let highlight = self.settings.highlight;
+        let plot = |id: &str| {
+            let plot = Plot::new(id)
+                .legend(Legend {
+                    text_style: egui::TextStyle::Heading,
+                    background_alpha: 1.0,
+                    position: Corner::LeftBottom,
+                })
+                .link_axis(group_id, true, false)
+                .show_grid(self.settings.show_grid)
+                .show_background(self.settings.show_background)
+                .height(height);
+            let plot = match window_start {
+                // Panning or zooming the time axis would stop following the latest samples
+                Some(start) => plot
+                    .include_x(start)
+                    .allow_drag([false, true])
+                    .allow_zoom([false, true])
+                    .allow_boxed_zoom(false)
+                    .allow_scroll(false),
+                None => plot,
             };
-
-            let legend = Legend {
-                text_style: egui::TextStyle::Heading,
-                background_alpha: 1.0,
-                position: Corner::LeftBottom,
+            let ticks = self.settings.grid_ticks;
+            let plot = if ticks > 0 {
+                plot.x_grid_spacer(uniform_grid_spacer(grid_step_sizes(ticks)))
+                    .y_grid_spacer(uniform_grid_spacer(grid_step_sizes(ticks)))
+            } else {
+                plot
+            };
+            if reset_plots {
+                plot.reset()
+            } else {
+                plot
+            }
+        };
+        // Marker and value of the latest sample
+        let playhead = |ui: &mut PlotUi, y: Option<&f32>, color: Color32| {
+            if !self.settings.playhead {
+                return;
+            }
+            let (Some(&x), Some(&y)) = (self.seconds.last(), y) else {
+                return;
             };
+            let point = PlotPoint::new(x, y);
+            ui.points(Points::new([x as f64, y as f64]).radius(4.0).color(color));
+            if self.settings.playhead_value {
+                ui.text(
+                    Text::new(point, format!(" {y:.3}"))
+                        .anchor(Align2::LEFT_BOTTOM)
+                        .color(color),
+                );
+            }
+        };
+        // Only the lowest plot has a time axis
+        // Value axis with SI prefixes if enabled, base units otherwise
+        let prefixed = |plot: Plot, enabled: bool, unit: &'static str| {
+            if enabled {
+                plot.y_axis_formatter(si_formatter(unit))
+                    .y_axis_width(SI_AXIS_WIDTH)
+            } else {
+                plot
+            }
+        };
+        let subplot = |subplot: Subplot, id: &str| {
+            let unit = match subplot {
+                Subplot::Position => "m",
+                Subplot::Velocity => "m/s",
+                Subplot::Force if self.settings.force_as_acceleration => "m/s²",
+                Subplot::Force => "N",
+            };
+            let plot = prefixed(plot(id), self.settings.si_prefixes[subplot as usize], unit);
+            if self.settings.channel.is_none() && lowest == Some(subplot) {
+                plot.x_axis_label("Time [s]")
+            } else {
+                plot.show_axes([false, true])
+            }
+        };
 
-            // Position
-            Plot::new("pos")
-                .legend(legend.clone())
-                .link_axis(group_id, true, false)
-                .show_axes([false, true])
-                .height(height)
-                .show(ui, |ui| {
-                    // Plotting the current target as horizontal line
-                    ui.hline(HLine::new(self.input.target.val).color(Color32::BLACK));
-                    // Plotting the target over time
+        // Position
+        if shown(Subplot::Position) {
+            subplot(Subplot::Position, "pos").show(ui, |ui| {
+                // Plotting the current target as horizontal line
+                if visible(Trace::CurrentTarget) {
+                    ui.hline(
+                        HLine::new(self.input.target.val)
+                            .name(Trace::CurrentTarget.name())
+                            .width(width(Trace::CurrentTarget))
+                            .color(palette.current_target),
+                    );
+                }
+                // Plotting the target over time
+                if visible(Trace::Target) {
                     ui.line(
                         Line::new(line(&self.target))
-                            .name("Target [m]")
-                            .color(Color32::GRAY),
+                            .name(Trace::Target.name())
+                            .width(width(Trace::Target))
+                            .color(palette.target),
+                    );
+                }
+                // Plotting the position of the ball
+                if visible(Trace::Position) {
+                    let (line, markers) = sampled(&self.pos);
+                    let name = Trace::Position.name();
+                    ui.line(
+                        line.name(name)
+                            .width(width(Trace::Position))
+                            .highlight(highlight)
+                            .color(palette.position),
                     );
-                    // Plotting the position of the ball
+                    if let Some(markers) = markers {
+                        ui.points(markers.name(name).color(palette.position));
+                    }
+                    playhead(ui, self.pos.last(), palette.position);
+                }
+                // Plotting the position of the reference, leaving out where it didn't run
+                if self.input.reference.get() {
                     ui.line(
-                        Line::new(line(&self.pos))
-                            .name("Position [m]")
-                            .highlight(true)
-                            .color(Color32::RED),
+                        Line::new(partial(&self.reference))
+                            .name("Reference [m]")
+                            .width(width(Trace::Position))
+                            .style(LineStyle::dashed_loose())
+                            .color(palette.reference),
                     );
-                });
+                }
+                // Plotting the imported run
+                if visible(Trace::Target) {
+                    ghost(ui, |g| &g.target, "Imported target [m]", palette.target);
+                }
+                if visible(Trace::Position) {
+                    ghost(ui, |g| &g.pos, "Imported position [m]", palette.position);
+                }
+                // Plotting the position of the comparison
+                if self.comparison.is_some() {
+                    ui.line(
+                        Line::new(partial(&self.compare_pos))
+                            .name("Comparison [m]")
+                            .width(width(Trace::Position))
+                            .style(LineStyle::dashed_dense())
+                            .color(palette.comparison),
+                    );
+                }
+            });
+        }
 
-            // Velocity
-            Plot::new("vel")
-                .link_axis(group_id, true, false)
-                .show_axes([false, true])
-                .legend(legend.clone())
-                .height(height)
-                .show(ui, |ui| {
-                    // Plotting the velocity
+        // Velocity
+        if shown(Subplot::Velocity) {
+            subplot(Subplot::Velocity, "vel").show(ui, |ui| {
+                // Plotting the velocity
+                if visible(Trace::Velocity) {
                     ui.line(
                         Line::new(line(&self.vel))
-                            .name("Velocity [m/s]")
-                            .highlight(true)
-                            .color(Color32::BLUE),
+                            .name(Trace::Velocity.name())
+                            .width(width(Trace::Velocity))
+                            .highlight(highlight)
+                            .color(palette.velocity),
                     );
-                });
+                    playhead(ui, self.vel.last(), palette.velocity);
+                    ghost(ui, |g| &g.vel, "Imported velocity [m/s]", palette.velocity);
+                }
+            });
+        }
 
-            // Force
-            Plot::new("force")
-                .link_axis(group_id, true, false)
-                .legend(legend)
-                .height(height)
-                .x_axis_label("Time [s]")
-                .show(ui, |ui| {
-                    // Plotting the velocity
+        // Force
+        if shown(Subplot::Force) {
+            subplot(Subplot::Force, "force").show(ui, |ui| {
+                // Plotting the force
+                if visible(Trace::Force) {
+                    let ((line, markers), name) = if self.settings.force_as_acceleration {
+                        let mass = self.input.mass.get();
+                        let acc: Vec<f32> = self.force.iter().map(|f| f / mass).collect();
+                        (sampled(&acc), "Acceleration [m/s²]")
+                    } else {
+                        (sampled(&self.force), Trace::Force.name())
+                    };
                     ui.line(
-                        Line::new(line(&self.force))
-                            .name("Force [N]")
-                            .highlight(true)
-                            .color(Color32::GREEN),
+                        line.name(name)
+                            .width(width(Trace::Force))
+                            .highlight(highlight)
+                            .color(palette.force),
                     );
+                    if let Some(markers) = markers {
+                        ui.points(markers.name(name).color(palette.force));
+                    }
+                    let last = self.force.last().map(|force| {
+                        if self.settings.force_as_acceleration {
+                            force / self.input.mass.get()
+                        } else {
+                            *force
+                        }
+                    });
+                    playhead(ui, last.as_ref(), palette.force);
+                }
+                // Plotting the force of the imported run, only in Newton as its mass is unknown
+                if visible(Trace::Force) && !self.settings.force_as_acceleration {
+                    ghost(ui, |g| &g.force, "Imported force [N]", palette.force);
+                }
+                // Plotting the force of the comparison
+                if self.comparison.is_some() {
+                    ui.line(
+                        Line::new(partial(&self.compare_force))
+                            .name("Comparison [N]")
+                            .width(width(Trace::Force))
+                            .style(LineStyle::dashed_dense())
+                            .color(palette.comparison),
+                    );
+                }
+                // Terms of the controller, before the actuator gain and limits
+                if self.settings.pid_components {
+                    for (values, name, color) in [
+                        (&self.proportional, "P term [N]", Color32::LIGHT_BLUE),
+                        (&self.integral, "I term [N]", Color32::GOLD),
+                        (&self.derivative, "D term [N]", Color32::LIGHT_RED),
+                    ] {
+                        ui.line(
+                            Line::new(line(values))
+                                .name(name)
+                                .width(width(Trace::Force))
+                                .color(color),
+                        );
+                    }
+                }
+            });
+        }
+
+        // User selected channel
+        if let Some(channel) = self.settings.channel {
+            let plot = prefixed(
+                plot("channel"),
+                self.settings.channel_si_prefix,
+                channel.unit(),
+            );
+            plot.x_axis_label("Time [s]").show(ui, |ui| {
+                let color = Color32::from_rgb(160, 32, 240);
+                let values: Cow<'_, [f32]> = match channel {
+                    Channel::Position => Cow::Borrowed(&self.pos),
+                    Channel::Velocity => Cow::Borrowed(&self.vel),
+                    Channel::Force | Channel::CommandVsForce => Cow::Borrowed(&self.force),
+                    Channel::Error => self
+                        .target
+                        .iter()
+                        .zip(&self.pos)
+                        .map(|(target, pos)| target - pos)
+                        .collect(),
+                    Channel::Integral => Cow::Borrowed(&self.integral),
+                    Channel::Derivative => Cow::Borrowed(&self.derivative),
+                };
+                if channel == Channel::CommandVsForce {
+                    ui.line(
+                        Line::new(line(&self.command))
+                            .name("Commanded force [N]")
+                            .width(width(Trace::Force))
+                            .color(Color32::GOLD),
+                    );
+                    ui.line(
+                        Line::new(line(&values))
+                            .name(Trace::Force.name())
+                            .width(width(Trace::Force))
+                            .highlight(highlight)
+                            .color(palette.force),
+                    );
+                    playhead(ui, values.last(), palette.force);
+                } else {
+                    ui.line(
+                        Line::new(line(&values))
+                            .name(channel.name())
+                            .highlight(highlight)
+                            .color(color),
+                    );
+                    playhead(ui, values.last(), color);
+                }
+            });
+        }
+    }
+}
+
+impl eframe::App for MyApp {
+    fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        eframe::set_value(storage, STORAGE_KEY, &self.input.config());
+        eframe::set_value(storage, THEME_STORAGE_KEY, &self.settings.dark_mode);
+        let traces = (&self.hidden, &self.settings.colors);
+        eframe::set_value(storage, TRACES_STORAGE_KEY, &traces);
+    }
+
+    #[cfg_attr(target_arch = "wasm32", allow(unused_variables))]
+    fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
+        // Before stepping, so a single step takes effect in this frame
+        self.shortcuts(ctx);
+
+        // Updating the simulation config
+        self.update_config();
+
+        // Measuring the real frame rate
+        let dt = ctx.input(|i| i.unstable_dt);
+        if dt > 0.0 {
+            let fps = 1.0 / dt;
+            self.fps = Some(
+                self.fps
+                    .map_or(fps, |old| old + (fps - old) * FPS_SMOOTHING),
+            );
+        }
+
+        // Stepping the simulation forward
+        self.frame_steps = 0;
+        let sampling_time = 1.0 / self.input.sampling_rate.get() as f32;
+        let sampling_time = Duration::from_secs_f32(sampling_time);
+        // GUI is stuttering for the first few samples
+        let ready = ctx.frame_nr() > 10 && !self.diverged;
+        // In real time, independent of the frames
+        let pace = (ready && !self.paused).then_some(Pace {
+            sampling_time,
+            speed: self.settings.speed,
+            max_steps: self.settings.max_steps,
+        });
+        self.sim.run(pace, self.elapsed);
+        self.receive();
+        // While paused the plots freeze, apart from single steps
+        let steps = std::mem::take(&mut self.single_steps);
+        if ready && self.paused && steps > 0 {
+            self.elapsed += sampling_time * steps;
+            self.advance(steps, sampling_time);
+        }
+
+        if self.diverged {
+            egui::TopBottomPanel::top("diverged").show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.colored_label(Color32::RED, "Simulation diverged — reset to continue");
+                    if ui.button("Restart").clicked() {
+                        self.restart();
+                    }
+                });
+            });
+        } else if self.unstable {
+            egui::TopBottomPanel::top("unstable").show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.colored_label(Color32::RED, egui::RichText::new("UNSTABLE").strong());
+                    ui.label(format!(
+                        "Within the last {INSTABILITY_WINDOW} s the ball has oscillated ever \
+                        more or has bounced between floor and ceiling"
+                    ));
+                });
+            });
+        }
+
+        egui::TopBottomPanel::top("config1").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                self.controls_ui(ui);
+                ui.separator();
+                self.noise_ui(ui);
+                ui.separator();
+                self.target_ui(ui);
+                ui.separator();
+                self.timing_ui(ui);
+                ui.separator();
+                self.gains_ui(ui, sampling_time);
+
+                // Second tuning side by side
+                ui.separator();
+                self.compare_ui(ui);
+
+                // Progress of the run
+                ui.separator();
+                self.progress_ui(ui);
+
+                // Link to egui
+                ui.separator();
+                ui.hyperlink_to("Source", "https://github.com/raui100/pid_ball");
+                ui.hyperlink_to("Made with egui", "https://github.com/emilk/egui");
+            });
+        });
+        egui::TopBottomPanel::top("config2").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                self.disturbance_ui(ui);
+                ui.separator();
+                self.physics_ui(ui);
+                ui.separator();
+                self.inductor_ui(ui);
+                ui.separator();
+                self.rendering_ui(ui);
+                ui.separator();
+                self.plot_settings_ui(ui);
+            });
+        });
+
+        egui::TopBottomPanel::top("config3").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                self.controller_ui(ui);
+                ui.separator();
+                self.loop_ui(ui);
+                ui.separator();
+                self.tools_ui(ui, sampling_time);
+            });
+        });
+
+        // Scenarios, configurations and recorded runs dropped onto the window
+        self.open_dropped(ctx);
+
+        // Reproducible experiments
+        let mut open = self.scenario_open;
+        egui::Window::new("Scenario")
+            .open(&mut open)
+            .show(ctx, |ui| self.scenario_ui(ui));
+        self.scenario_open = open;
+
+        // Configuration files
+        let mut open = self.config_open;
+        egui::Window::new("Configuration")
+            .open(&mut open)
+            .show(ctx, |ui| self.config_ui(ui));
+        self.config_open = open;
+
+        // Recorded run to compare with
+        let mut open = self.ghost_open;
+        egui::Window::new("Import CSV")
+            .open(&mut open)
+            .show(ctx, |ui| self.ghost_ui(ui));
+        self.ghost_open = open;
+
+        // Monte Carlo robustness test
+        let mut open = self.monte_carlo_open;
+        egui::Window::new("Monte Carlo")
+            .open(&mut open)
+            .resizable(false)
+            .show(ctx, |ui| self.monte_carlo_ui(ui, sampling_time));
+        self.monte_carlo_open = open;
+
+        // Bode plot of the last analysis
+        let mut open = self.bode_open;
+        egui::Window::new("Frequency response")
+            .open(&mut open)
+            .show(ctx, |ui| self.bode_ui(ui));
+        self.bode_open = open;
+
+        // Log of significant events
+        egui::TopBottomPanel::bottom("events").show(ctx, |ui| self.events_ui(ui));
+
+        // Statistics of the current run
+        egui::TopBottomPanel::bottom("stats").show(ctx, |ui| self.stats_ui(ui));
+
+        // Latest values, more precise than reading them off the plots
+        egui::SidePanel::right("readouts")
+            .resizable(false)
+            .show(ctx, |ui| self.readouts_ui(ui));
+
+        // Painting the ball
+        let y_width = ctx.available_rect().width();
+        egui::SidePanel::left("ball")
+            .default_width(y_width * 0.2) // Gives 20% of the space to the animation
+            .show(ctx, |ui| self.ball_ui(ui));
+
+        // Plotting position and velocity of the ball
+        let reset_plots = std::mem::take(&mut self.reset_plots);
+        let plots = egui::CentralPanel::default().show(ctx, |ui| self.plots_ui(ui, reset_plots));
+        let to_pixels = |pos: Pos2| (pos.to_vec2() * ctx.pixels_per_point()).to_pos2();
+        let rect = plots.response.rect;
+        self.plot_rect = Some(egui::Rect::from_min_max(
+            to_pixels(rect.min),
+            to_pixels(rect.max),
+        ));
+
+        // Capturing the plots once they are painted
+        if std::mem::take(&mut self.export_png) {
+            #[cfg(not(target_arch = "wasm32"))]
+            frame.request_screenshot(); // Arrives in `post_rendering`
+            #[cfg(target_arch = "wasm32")]
+            {
+                let rect = self.plot_rect.unwrap_or(egui::Rect::EVERYTHING);
+                // Microtasks run after painting, while the canvas still holds the frame
+                wasm_bindgen_futures::spawn_local(async move {
+                    if let Err(e) = screenshot::download_canvas(CANVAS_ID, rect, PNG_FILE) {
+                        log::warn!("Exporting the plots: {e}");
+                    }
                 });
+            }
+        }
+
+        // After the buttons of this frame, which may have paused or resumed
+        if let Some(after) = self.repaint_after(sampling_time) {
+            ctx.request_repaint_after(after);
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn post_rendering(&mut self, _window_size_px: [u32; 2], frame: &eframe::Frame) {
+        if let Some(image) = frame.screenshot() {
+            self.save_png(&image);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repaints_with_the_samples() {
+        let sampling_time = Duration::from_millis(10);
+        let mut app = MyApp::default();
+        app.settings.max_fps = true;
+        assert_eq!(app.repaint_after(sampling_time), Some(sampling_time));
+        app.settings.speed = 2.0;
+        assert_eq!(app.repaint_after(sampling_time), Some(sampling_time / 2));
+
+        // Capped by the FPS
+        app.settings.max_fps = false;
+        app.settings.fps_cap = 10;
+        let frame_time = app.repaint_after(sampling_time).unwrap();
+        assert!(
+            (frame_time.as_secs_f32() - 0.1).abs() < 1e-6,
+            "{frame_time:?}"
+        );
+
+        // Nothing to do until the "Step" button
+        app.paused = true;
+        assert_eq!(app.repaint_after(sampling_time), None);
+        app.single_steps = 1;
+        assert!(app.repaint_after(sampling_time).is_some());
+    }
+
+    #[test]
+    fn picked_colors_override_the_theme() {
+        let mut app = MyApp::default();
+        let theme = Palette::new(app.settings.dark_mode);
+        assert_eq!(app.palette().position, theme.position);
+
+        app.settings.colors[Trace::Position as usize] = Some(Color32::YELLOW);
+        let palette = app.palette();
+        assert_eq!(palette.position, Color32::YELLOW);
+        assert_eq!(palette.velocity, theme.velocity);
+        // The other theme keeps the picked color
+        app.settings.dark_mode = !app.settings.dark_mode;
+        assert_eq!(app.palette().position, Color32::YELLOW);
+    }
+
+    #[test]
+    fn palette_contrasts_with_the_plot_background() {
+        let luminance = |c: Color32| egui::Rgba::from(c).intensity();
+        for dark_mode in [false, true] {
+            let background = luminance(visuals(dark_mode).extreme_bg_color);
+            let palette = Palette::new(dark_mode);
+            for color in [
+                palette.current_target,
+                palette.target,
+                palette.position,
+                palette.reference,
+                palette.velocity,
+                palette.force,
+                palette.comparison,
+            ] {
+                let contrast = (luminance(color) - background).abs();
+                assert!(contrast > 0.1, "{color:?} in dark mode {dark_mode}");
+            }
+        }
+    }
+
+    #[test]
+    fn step_starts_with_the_last_target_change() {
+        let mut app = MyApp::default();
+        // Settled at 0.2, then a step to 0.6 that is followed exactly
+        for (i, (target, pos)) in [(0.2, 0.2), (0.2, 0.2), (0.6, 0.2), (0.6, 0.6), (0.6, 0.6)]
+            .into_iter()
+            .enumerate()
+        {
+            app.seconds.push(i as f32);
+            app.target.push(target);
+            app.pos.push(pos);
+        }
+        let metrics = app.step_response();
+        assert_eq!(metrics.rise_time, Some(0.0));
+        assert_eq!(metrics.settling_time, Some(1.0));
+    }
+
+    #[test]
+    fn pid_components_are_recorded_and_cleared() {
+        let mut app = MyApp::default();
+        let data = app.sim.step(1, Duration::from_millis(10));
+        app.record(&data, None, true);
+        assert_eq!(app.proportional.last(), Some(&data.p));
+        assert_eq!(app.integral.last(), Some(&data.i));
+        assert_eq!(app.derivative.last(), Some(&data.d));
+        app.clear();
+        assert!(app.proportional.is_empty() && app.integral.is_empty());
+        assert!(app.derivative.is_empty());
+    }
+
+    #[test]
+    fn identical_comparison_runs_in_lockstep() {
+        let sampling_time = Duration::from_millis(10);
+        let mut app = MyApp::default();
+        app.input.process_noise.val = 0.5;
+        app.update_config();
+        let gains = [app.input.kp.get(), app.input.ki.get(), app.input.kd.get()];
+        app.comparison = Some(Comparison::new(&app.sim.simulation(), gains));
+        for frame in 0..300 {
+            if frame == 100 {
+                app.config(Message::Disturbance(1.0));
+            }
+            app.advance(1, sampling_time);
+        }
+        assert_eq!(app.compare_pos.len(), app.pos.len());
+        assert!(app.pos.iter().eq(app.compare_pos.iter()));
+        assert!(app.force.iter().eq(app.compare_force.iter()));
+
+        // Only the gains of the comparison differ
+        app.comparison.as_mut().unwrap().kp.val *= 2.0;
+        app.update_config();
+        app.advance(10, sampling_time);
+        assert_ne!(app.pos.last(), app.compare_pos.last());
+    }
+
+    /// Runs the shortcuts of a frame in which `key` is pressed
+    fn press(app: &mut MyApp, ctx: &egui::Context, key: egui::Key, modifiers: egui::Modifiers) {
+        let event = egui::Event::Key {
+            key,
+            pressed: true,
+            repeat: false,
+            modifiers,
+        };
+        let input = egui::RawInput {
+            events: vec![event],
+            modifiers,
+            ..Default::default()
+        };
+        ctx.begin_frame(input);
+        app.shortcuts(ctx);
+        let _ = ctx.end_frame();
+    }
+
+    #[test]
+    fn keyboard_shortcuts() {
+        let ctx = egui::Context::default();
+        let none = egui::Modifiers::NONE;
+        let mut app = MyApp::default();
+        press(&mut app, &ctx, egui::Key::Space, none);
+        assert!(app.paused);
+        press(&mut app, &ctx, egui::Key::ArrowRight, none);
+        assert_eq!(app.single_steps, 1);
+        press(&mut app, &ctx, egui::Key::Space, none);
+        assert!(!app.paused);
+        press(&mut app, &ctx, egui::Key::ArrowRight, none);
+        assert_eq!(app.single_steps, 1, "only steps while paused");
+
+        press(&mut app, &ctx, egui::Key::PlusEquals, none);
+        assert_eq!(app.settings.speed, SPEED * SPEED_NUDGE);
+        for _ in 0..100 {
+            press(&mut app, &ctx, egui::Key::Minus, none);
+        }
+        assert_eq!(app.settings.speed, MIN_SPEED);
+
+        // Restart keeps the values, reset returns to the defaults
+        app.input.kp.val = 1.0;
+        press(&mut app, &ctx, egui::Key::R, none);
+        assert_eq!(app.input.kp.val, 1.0);
+        press(&mut app, &ctx, egui::Key::R, egui::Modifiers::SHIFT);
+        assert_eq!(app.input.kp.val, KP);
+
+        // Typing into a text field doesn't trigger them
+        let id = egui::Id::new("field");
+        ctx.begin_frame(Default::default());
+        ctx.memory_mut(|mem| {
+            mem.interested_in_focus(id);
+            mem.request_focus(id);
         });
+        let _ = ctx.end_frame();
+        press(&mut app, &ctx, egui::Key::Space, none);
+        assert!(!app.paused);
+    }
+
+    #[test]
+    fn imports_the_copied_data() {
+        let mut app = MyApp::default();
+        app.update_config();
+        app.advance(20, Duration::from_millis(10));
+        let table = format!("# Recorded run\n{}", app.table("\t"));
+        let ghost = MyApp::import_csv(&table).unwrap();
+        assert_eq!(ghost.seconds, &*app.seconds);
+        assert_eq!(ghost.pos, &*app.pos);
+        assert_eq!(ghost.force, &*app.force);
+
+        // Comma separated with further columns in another order
+        let csv = "# comment\n\nforce [N],time [s],position [m],velocity [m/s],target [m],x\n\
+                   # another comment\n1.5, 0.01, 0.5, -0.1, 0.6, 9\n";
+        let ghost = MyApp::import_csv(csv).unwrap();
+        assert_eq!(ghost.seconds, [0.01]);
+        assert_eq!(ghost.force, [1.5]);
+        assert_eq!(ghost.vel, [-0.1]);
+    }
+
+    #[test]
+    fn import_rejects_malformed_tables() {
+        let missing = "time [s],target [m],position [m],force [N]\n0.01,0.6,0.5,1.5\n";
+        assert_eq!(
+            MyApp::import_csv(missing).unwrap_err(),
+            ParseError::MissingColumn("velocity [m/s]")
+        );
+        assert_eq!(
+            MyApp::import_csv("# only\n\n").unwrap_err(),
+            ParseError::Empty
+        );
+        let header = "time [s],target [m],position [m],velocity [m/s],force [N]";
+        assert_eq!(
+            MyApp::import_csv(&format!("{header}\n0.01,0.6,0.5\n")).unwrap_err(),
+            ParseError::ShortRow(2)
+        );
+        assert_eq!(
+            MyApp::import_csv(&format!("{header}\n0.01,0.6,high,0,1\n")).unwrap_err(),
+            ParseError::InvalidNumber(2, "position [m]")
+        );
+    }
+
+    #[test]
+    fn comparison_follows_the_samples_in_real_time() {
+        let sampling_time = Duration::from_millis(1);
+        let mut app = MyApp::default();
+        let gains = [app.input.kp.get(), app.input.ki.get(), app.input.kd.get()];
+        app.comparison = Some(Comparison::new(&app.sim.simulation(), gains));
+        let pace = Pace {
+            sampling_time,
+            speed: SPEED,
+            max_steps: MAX_STEPS_PER_FRAME,
+        };
+        app.sim.run(Some(pace), app.elapsed);
+        for _ in 0..5 {
+            std::thread::sleep(Duration::from_millis(10));
+            app.receive();
+        }
+        app.sim.run(None, app.elapsed);
+        app.sim.with(|_| ());
+        app.receive();
+        assert!(app.step_count > 0);
+        assert_eq!(app.elapsed, sampling_time * app.step_count as u32);
+        assert!(app.pos.iter().eq(app.compare_pos.iter()));
+    }
+
+    #[test]
+    fn presets_apply_their_gains() {
+        let sampling_time = Duration::from_millis(10);
+        for preset in &Preset::ALL {
+            let mut app = MyApp::default();
+            app.input.controller.val = ControllerKind::BangBang;
+            app.update_config();
+            app.advance(10, sampling_time);
+            app.apply_preset(preset, sampling_time);
+
+            assert!(app.input.controller.get() == ControllerKind::Pid);
+            let pid = app.sim.with(|sim| sim.pid().clone());
+            assert_eq!(pid.kp, preset.gains.kp, "{}", preset.name);
+            // The integral gain per sample of the default integral mode
+            let ki = preset.gains.ki * sampling_time.as_secs_f32();
+            assert_eq!(pid.ki, ki, "{}", preset.name);
+            assert_eq!(pid.kd, preset.gains.kd, "{}", preset.name);
+            assert!(pid.d_mode == preset.d_mode, "{}", preset.name);
+            // Like a restart
+            assert!(app.pos.is_empty());
+            assert_eq!(app.elapsed, Duration::ZERO);
+        }
+    }
+
+    #[test]
+    fn maglev_overrides_the_previous_setup() {
+        let sampling_time = Duration::from_secs_f32(1.0 / SAMPLING_RATE as f32);
+        let mut app = MyApp::default();
+        app.input.coupling.val = CouplingModel::InverseLinear;
+        app.input.softening.val = 0.25;
+        app.input.follow_target.val = true;
+        app.input.init_pos.val = 0.1;
+        app.input.max_force_up.val = 5.0;
+        app.input.kp.val = 10.0;
+        app.input.i_mode.val = IntegralMode::PerSecond;
+        app.input.target.val = 0.3;
+        app.update_config();
+        app.advance(100, sampling_time);
+        app.maglev();
+
+        assert!(app.input.coupling.get() == CouplingModel::InverseSquare);
+        assert!(!app.input.follow_target.get());
+        assert_eq!(app.input.max_force_down.get(), 0.0);
+        for _ in 0..11 * SAMPLING_RATE {
+            app.elapsed += sampling_time;
+            app.advance(1, sampling_time);
+        }
+        assert!(app.force.iter().all(|&force| force >= 0.0), "magnet pushed");
+        let pos = *app.pos.last().unwrap();
+        assert!((pos - TARGET).abs() < 0.01, "{pos}");
+    }
+
+    #[test]
+    fn seeding_and_freezing_are_separate() {
+        let sampling_time = Duration::from_millis(10);
+        let run = |app: &mut MyApp| {
+            app.restart();
+            for _ in 0..50 {
+                app.elapsed += sampling_time;
+                app.advance(1, sampling_time);
+            }
+            app.pos.iter().copied().collect::<Vec<_>>()
+        };
+        let seeded = || {
+            let mut app = MyApp::default();
+            app.input.noise_seed.val = Some(NOISE_SEED);
+            app.update_config();
+            app
+        };
+        let mut app = seeded();
+        let first = run(&mut app);
+        assert_eq!(run(&mut seeded()), first);
+        // Seeded but not frozen, every restart faces new noise
+        let second = run(&mut app);
+        assert_ne!(second, first);
+
+        app.input.freeze_noise.val = true;
+        app.update_config();
+        assert_eq!(run(&mut app), second);
+        assert_eq!(run(&mut app), second);
+    }
+
+    #[test]
+    fn config_file_round_trip() {
+        let path =
+            std::env::temp_dir().join(format!("pid_ball_config_{}.json", std::process::id()));
+        let mut app = MyApp {
+            config_path: path.to_string_lossy().into_owned(),
+            ..Default::default()
+        };
+        app.input.kp.val = 123.0;
+        app.save_config();
+        assert!(app.config_error.is_none(), "{:?}", app.config_error);
+        app.input.kp.val = KP;
+        app.config_text.clear();
+        app.load_config();
+        assert!(app.config_error.is_none(), "{:?}", app.config_error);
+        assert_eq!(app.input.kp.get(), 123.0);
+
+        std::fs::write(&path, "{\"kp\": ").unwrap();
+        app.load_config();
+        assert!(app.config_error.is_some());
+        assert_eq!(app.input.kp.get(), 123.0);
+        std::fs::remove_file(&path).unwrap();
+        app.load_config();
+        assert!(app.config_error.is_some());
+    }
+
+    #[test]
+    fn readouts_show_dashes_until_the_first_sample() {
+        let sampling_time = Duration::from_millis(10);
+        let mut app = MyApp::default();
+        let values = |app: &MyApp| app.readouts().map(|(_, value)| value.trim().to_string());
+        assert_eq!(values(&app), ["-", "-", "-", "-", "0", "-", "-"]);
+
+        app.elapsed += sampling_time * 3;
+        app.advance(3, sampling_time);
+        let readouts = values(&app);
+        let pos = app.pos.last().unwrap();
+        let error = app.target.last().unwrap() - pos;
+        assert_eq!(readouts[0], format!("{pos:.4} m"));
+        assert_eq!(readouts[3], format!("{error:.4} m"));
+        assert_eq!(readouts[4], "3");
+        assert_eq!(readouts[5], "10.0000 ms");
+
+        app.restart();
+        assert_eq!(values(&app)[..4], ["-", "-", "-", "-"]);
+    }
+
+    #[test]
+    fn undamped_loop_is_unstable() {
+        let sampling_time = Duration::from_millis(10);
+        let run = |app: &mut MyApp| {
+            for _ in 0..1000 {
+                app.elapsed += sampling_time;
+                app.advance(1, sampling_time);
+            }
+        };
+        let mut app = MyApp::default();
+        run(&mut app);
+        assert!(!app.unstable);
+
+        app.input.ki.val = 0.0;
+        app.input.kd.val = 0.0;
+        app.update_config();
+        run(&mut app);
+        assert!(app.unstable);
+        app.restart();
+        assert!(!app.unstable);
+    }
+
+    #[test]
+    fn load_state_rewinds() {
+        let sampling_time = Duration::from_millis(10);
+        let mut app = MyApp::default();
+        let run = |app: &mut MyApp, steps: u32| {
+            for _ in 0..steps {
+                let data = app.sim.step(1, sampling_time);
+                app.elapsed += sampling_time;
+                app.record(&data, None, true);
+            }
+        };
+        run(&mut app, 50);
+        app.input.kp.val = 100.0;
+        app.input.update(&mut app.sim);
+        app.save_state();
+        let pos = *app.pos.last().unwrap();
+
+        app.input.kp.val = 10.0;
+        app.input.update(&mut app.sim);
+        run(&mut app, 50);
+        app.load_state();
+        assert_eq!(app.pos.len(), 50);
+        assert_eq!(app.pos.last(), Some(&pos));
+        assert_eq!(app.elapsed, sampling_time * 50);
+        assert_eq!(app.input.kp.get(), 100.0);
+        assert_eq!(app.input.kp.changed(), None);
+    }
+
+    #[test]
+    fn history_is_bounded() {
+        let mut app = MyApp::default();
+        app.settings.history = 1.0;
+        for _ in 0..1000 {
+            let data = app.sim.step(1, Duration::from_millis(10));
+            app.elapsed += Duration::from_millis(10);
+            app.record(&data, None, true);
+        }
+        // One second of frames every 10 ms plus the frame at the boundary
+        assert!(app.seconds.len() <= 101, "{}", app.seconds.len());
+        assert_eq!(app.pos.len(), app.seconds.len());
+        assert!(app.seconds[0] >= 9.0 - 1e-3);
+    }
+
+    #[test]
+    fn si_prefixes() {
+        let format = si_formatter("m");
+        assert_eq!(format(0.25, 5, &(0.0..=0.3)), "250 mm");
+        assert_eq!(format(0.0003, 5, &(0.0..=0.0005)), "300 µm");
+        assert_eq!(format(2.5, 5, &(-5.0..=5.0)), "2.5 m");
+        assert_eq!(format(1500.0, 5, &(0.0..=2000.0)), "1.50 km");
+        assert_eq!(format(0.0, 5, &(0.0..=0.0)), "0 m");
     }
 }
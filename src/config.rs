@@ -0,0 +1,232 @@
+use crate::default::*;
+use crate::sim::{
+    ControllerKind, CouplingModel, DerivativeMode, DerivativeSource, DerivativeStart, IntegralMode,
+    NoiseKind, ProportionalMode, TargetProfile,
+};
+use serde::{Deserialize, Serialize};
+
+/// Every parameter of the simulation that can be entered in the GUI
+///
+/// Missing fields take their default value and unknown fields are ignored.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub controller: ControllerKind,
+    pub lead_lag_gain: f32,
+    pub lead_time: f32,
+    pub lag_time: f32,
+    pub lead_lag_bias: f32,
+    pub bang_bang_force: f32,
+    pub bang_bang_hysteresis: f32,
+    pub kp: f32,
+    pub ki: f32,
+    pub kd: f32,
+    pub kt: f32,
+    pub kff: f32,
+    pub conditional_integration: bool,
+    /// `None` for no limit
+    pub i_limit: Option<f32>,
+    /// `None` integrates at any error
+    pub i_band: Option<f32>,
+    /// `None` leaves the command unclamped
+    pub output_limit: Option<f32>,
+    pub p_mode: ProportionalMode,
+    pub d_filter_tau: f32,
+    pub d_mode: DerivativeMode,
+    pub d_start: DerivativeStart,
+    pub d_source: DerivativeSource,
+    pub i_mode: IntegralMode,
+    pub preload: bool,
+    pub target: f32,
+    pub target_profile: TargetProfile,
+    pub dead_zone: f32,
+    pub sampling_rate: u32,
+    pub sub_steps: u32,
+    pub control_divider: u32,
+    pub noise: f32,
+    pub noise_kind: NoiseKind,
+    pub vel_noise: f32,
+    pub sensor_tau: f32,
+    pub sensor_delay: f32,
+    pub sensor_resolution: f32,
+    pub outlier_probability: f32,
+    pub outlier_magnitude: f32,
+    pub median_window: u32,
+    pub random_start: bool,
+    pub init_pos: f32,
+    pub init_vel: f32,
+    pub pos_spread: f32,
+    pub vel_spread: f32,
+    /// Downward acceleration [m/s^2]
+    pub gravity: f32,
+    /// Gravitation of older configurations, with up positive. Read but never written,
+    /// see [`Config::migrate`]
+    #[serde(skip_serializing)]
+    pub gravitation: Option<f32>,
+    pub process_noise: f32,
+    pub coupling: CouplingModel,
+    pub softening: f32,
+    pub mass: f32,
+    pub floor: f32,
+    pub ceiling: f32,
+    pub restitution: f32,
+    /// `None` for no limit
+    pub max_velocity: Option<f32>,
+    pub max_force_up: f32,
+    pub max_force_down: f32,
+    pub max_force_rate: f32,
+    pub initial_force: f32,
+    pub actuator_gain: f32,
+    /// Pull-only magnet of older configurations, now without downward force. Read but
+    /// never written, see [`Config::migrate`]
+    #[serde(skip_serializing)]
+    pub pull_only: Option<bool>,
+    pub deadband: f32,
+    pub dead_time: f32,
+    pub invert_output: bool,
+    pub output_cutoff: f32,
+    pub engage_delay: f32,
+    pub follow_target: bool,
+    pub follow_rate: f32,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            controller: CONTROLLER,
+            lead_lag_gain: LEAD_LAG_GAIN,
+            lead_time: LEAD_TIME,
+            lag_time: LAG_TIME,
+            lead_lag_bias: LEAD_LAG_BIAS,
+            bang_bang_force: BANG_BANG_FORCE,
+            bang_bang_hysteresis: BANG_BANG_HYSTERESIS,
+            kp: KP,
+            ki: KI,
+            kd: KD,
+            kt: KT,
+            kff: KFF,
+            conditional_integration: CONDITIONAL_INTEGRATION,
+            i_limit: I_LIMIT,
+            i_band: I_BAND,
+            output_limit: OUTPUT_LIMIT,
+            p_mode: P_MODE,
+            d_filter_tau: D_FILTER_TAU,
+            d_mode: D_MODE,
+            d_start: D_START,
+            d_source: D_SOURCE,
+            i_mode: I_MODE,
+            preload: PRELOAD,
+            target: TARGET,
+            target_profile: TargetProfile::Constant(TARGET),
+            dead_zone: DEAD_ZONE,
+            sampling_rate: SAMPLING_RATE,
+            sub_steps: SUB_STEPS,
+            control_divider: CONTROL_DIVIDER,
+            noise: NOISE,
+            noise_kind: NOISE_KIND,
+            vel_noise: VEL_NOISE,
+            sensor_tau: SENSOR_TAU,
+            sensor_delay: SENSOR_DELAY,
+            sensor_resolution: SENSOR_RESOLUTION,
+            outlier_probability: OUTLIER_PROBABILITY,
+            outlier_magnitude: OUTLIER_MAGNITUDE,
+            median_window: MEDIAN_WINDOW as u32,
+            random_start: RANDOM_START,
+            init_pos: BALL_POS,
+            init_vel: BALL_VEL,
+            pos_spread: POS_SPREAD,
+            vel_spread: VEL_SPREAD,
+            gravity: GRAVITY,
+            gravitation: None,
+            process_noise: PROCESS_NOISE,
+            coupling: COUPLING,
+            softening: FORCE_SOFTENING,
+            mass: MASS,
+            floor: FLOOR,
+            ceiling: CEILING,
+            restitution: RESTITUTION,
+            max_velocity: None,
+            max_force_up: MAX_FORCE,
+            max_force_down: MAX_FORCE,
+            max_force_rate: MAX_FORCE_RATE,
+            initial_force: INITIAL_FORCE,
+            actuator_gain: ACTUATOR_GAIN,
+            pull_only: None,
+            deadband: DEADBAND,
+            dead_time: DEAD_TIME,
+            invert_output: INVERT_OUTPUT,
+            output_cutoff: OUTPUT_CUTOFF,
+            engage_delay: ENGAGE_DELAY,
+            follow_target: FOLLOW_TARGET,
+            follow_rate: FOLLOW_RATE,
+        }
+    }
+}
+
+impl Config {
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let config: Self = serde_json::from_str(json).map_err(|e| e.to_string())?;
+        Ok(config.migrate())
+    }
+
+    /// Converts the fields of older configurations
+    pub fn migrate(mut self) -> Self {
+        if let Some(gravitation) = self.gravitation.take() {
+            self.gravity = -gravitation;
+        }
+        if self.pull_only.take() == Some(true) {
+            self.max_force_down = 0.0;
+        }
+        self
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("config is serializable")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let config = Config {
+            kp: 100.0,
+            i_limit: Some(5.0),
+            d_mode: DerivativeMode::OnError,
+            target_profile: TargetProfile::Sine {
+                center: 0.5,
+                amplitude: 0.1,
+                freq_hz: 0.5,
+            },
+            ..Default::default()
+        };
+        assert!(Config::from_json(&config.to_json()).unwrap() == config);
+    }
+
+    #[test]
+    fn signed_gravitation_is_migrated() {
+        let config = Config::from_json(r#"{"gravitation": -9.81}"#).unwrap();
+        assert_eq!(config.gravity, 9.81);
+        assert_eq!(config.gravitation, None);
+        assert!(!config.to_json().contains("gravitation"));
+        assert_eq!(Config::from_json("{}").unwrap().gravity, GRAVITY);
+    }
+
+    #[test]
+    fn pull_only_is_migrated() {
+        let config = Config::from_json(r#"{"pull_only": true}"#).unwrap();
+        assert_eq!(config.max_force_down, 0.0);
+        assert_eq!(config.pull_only, None);
+        assert!(!config.to_json().contains("pull_only"));
+        let config = Config::from_json(r#"{"pull_only": false}"#).unwrap();
+        assert_eq!(config.max_force_down, MAX_FORCE);
+    }
+
+    #[test]
+    fn malformed() {
+        assert!(Config::from_json("{\"kp\": ").is_err());
+        assert!(Config::from_json("{\"kp\": \"fast\"}").is_err());
+    }
+}
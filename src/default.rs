@@ -1,13 +1,107 @@
-pub const GRAVITATION: f32 = -9.81; // m/s^2
-pub const KP: f32 = 150.0;
-pub const KI: f32 = 1.0;
-pub const KD: f32 = 20.0;
-pub const TARGET: f32 = 0.6;
+pub use pid_ball_core::default::*;
+
+use crate::sim::{ControllerKind, CouplingModel, NoiseKind};
+
+pub const CONTROLLER: ControllerKind = ControllerKind::Pid;
+pub const GRAVITY: f32 = 9.81; // m/s^2, downwards
+pub const PROCESS_NOISE: f32 = 0.0; // N, deterministic plant
 pub const SAMPLING_RATE: u32 = 100;
 pub const NOISE: f32 = 0.001;
-pub const BALL_POS: f32 = 0.5;
-pub const BALL_VEL: f32 = 0.0;
-pub const IND_POS: f32 = 1.0;
-pub const MAX_FORCE: f32 = 50.0;
-pub const MAX_FORCE_RATE: f32 = 200.0;
+pub const NOISE_KIND: NoiseKind = NoiseKind::Normal;
+pub const VEL_NOISE: f32 = 0.01; // m/s, of the velocity sensor
+pub const FREEZE_NOISE: bool = false; // Every restart faces new noise
+pub const SEEDED_NOISE: bool = false; // Seeded from entropy
+pub const NOISE_SEED: u64 = 42; // When the noise is seeded
+pub const RANDOM_START: bool = false;
+pub const POS_SPREAD: f32 = 0.1; // m
+pub const VEL_SPREAD: f32 = 0.5; // m/s
+pub const SENSOR_TAU: f32 = 0.0; // s, no lag
+pub const SENSOR_DELAY: f32 = 0.0; // s, no transport delay
+pub const SENSOR_RESOLUTION: f32 = 0.0; // m, not quantized
+pub const OUTLIER_PROBABILITY: f32 = 0.0;
+pub const OUTLIER_MAGNITUDE: f32 = 0.2; // m
+pub const MEDIAN_WINDOW: usize = 1; // Unfiltered
+pub const FORCE_SOFTENING: f32 = 1.0; // m^2
+pub const COUPLING: CouplingModel = CouplingModel::InverseSquare;
+pub const COUPLING_DECAY: f32 = 1.0; // 1/m, when switching to the exponential coupling
+pub const FLOOR: f32 = 0.0; // m, bottom of the tube
+pub const CEILING: f32 = 1.0; // m, top of the tube
+pub const RESTITUTION: f32 = 0.0; // Inelastic
+pub const MIN_MASS: f32 = 0.01; // kg
 pub const HOLD_BALL: bool = false;
+pub const PUSH: f32 = 0.5; // m/s, velocity kick of the push buttons
+pub const INTEGRAL_LIMIT: f32 = 10.0; // N, when the integral limit is switched on
+pub const INTEGRAL_BAND: f32 = 0.05; // m, when the integral separation is switched on
+pub const CONTROLLER_OUTPUT_LIMIT: f32 = 20.0; // N, when the output limit is switched on
+pub const VELOCITY_LIMIT: f32 = 5.0; // m/s, when the velocity limit is switched on
+pub const INVERT_OUTPUT: bool = false;
+pub const OUTPUT_CUTOFF: f32 = 0.0; // Hz, unfiltered
+pub const ENGAGE_DELAY: f32 = 0.0; // s, closed loop right away
+pub const SMOOTH_RELEASE: bool = true;
+pub const PRELOAD: bool = false;
+pub const SUB_STEPS: u32 = 1; // Physics steps per sampling step
+pub const REFERENCE: bool = false;
+pub const REFERENCE_SUB_STEPS: u32 = 100; // Times the sub-steps of the simulation
+pub const CONTROL_DIVIDER: u32 = 1; // Controller updates every n-th step
+pub const TARGET_AMPLITUDE: f32 = 0.1; // m, of the time-varying target profiles
+pub const TARGET_PERIOD: f32 = 4.0; // s
+pub const FOLLOW_TARGET: bool = false; // The inductor stays at `IND_POS`
+pub const FOLLOW_RATE: f32 = 0.1; // m/s
+pub const MAX_FPS: bool = true;
+pub const FPS_CAP: u32 = 30;
+pub const SPEED: f32 = 1.0; // Real time
+pub const MIN_SPEED: f32 = 0.1;
+pub const MAX_SPEED: f32 = 10.0;
+pub const SPEED_NUDGE: f32 = 1.25; // Factor of the + and - keys
+pub const MAX_STEPS_PER_FRAME: u32 = 1000; // Drops the backlog of longer frame gaps
+pub const FORCE_AS_ACCELERATION: bool = false;
+pub const PID_COMPONENTS: bool = false;
+pub const LIMIT_LATCH: f32 = 0.3; // s of real time the limit indicators stay lit
+pub const INSTABILITY_WINDOW: f32 = 5.0; // s of the latest positions that are judged
+pub const FPS_SMOOTHING: f32 = 0.1; // Weight of the latest frame in the measured FPS
+pub const LINE_WIDTH: f32 = 1.0; // px
+pub const HIGHLIGHT: bool = true;
+pub const PLAYHEAD: bool = true;
+pub const PLAYHEAD_VALUE: bool = false;
+pub const SHOW_GRID: bool = true;
+pub const SHOW_BACKGROUND: bool = true;
+pub const GRID_TICKS: u32 = 0; // Automatic
+pub const SI_PREFIXES: bool = false; // Base SI units on the value axes
+pub const SI_AXIS_WIDTH: usize = 8; // Characters of a value axis with SI prefixes
+pub const STORAGE_KEY: &str = "config_v1"; // New key for incompatible changes of `Config`
+pub const THEME_STORAGE_KEY: &str = "dark_mode"; // Independent of the `Config` version
+pub const TRACES_STORAGE_KEY: &str = "traces"; // Hidden traces and picked colors
+pub const DARK_MODE: bool = true; // Like egui
+pub const CONFIG_FILE: &str = "pid_ball_config.json"; // Name of the download on the web
+pub const GHOST_ALPHA: f32 = 0.35; // Opacity of the lines of an imported run
+pub const PNG_FILE: &str = "pid_ball_plots.png"; // In the working directory or downloaded
+#[cfg(target_arch = "wasm32")]
+pub const CANVAS_ID: &str = "the_canvas_id"; // Like in main.rs and index.html
+pub const TUNE_KP_START: f32 = 1.0; // Proportional gain of the first auto-tuning run
+pub const TUNE_KP_FACTOR: f32 = 1.25; // Rise of the gain per run
+pub const TUNE_KP_MAX: f32 = 10_000.0; // Gives up beyond
+pub const TUNE_BISECTIONS: u32 = 8; // Refinements of the ultimate gain
+pub const TUNE_DURATION: f32 = 20.0; // s, of every run
+pub const TUNE_MIN_CROSSINGS: usize = 4; // Of the mean in the second half of a run
+pub const TUNE_MIN_AMPLITUDE: f32 = 0.001; // m
+pub const BODE_MIN_FREQ: f32 = 0.1; // Hz
+pub const BODE_MAX_FREQ: f32 = 20.0; // Hz, at most a quarter of the sampling rate
+pub const BODE_POINTS: usize = 30;
+pub const BODE_AMPLITUDE: f32 = 0.01; // m, small enough to stay clear of the force limits
+pub const BODE_SETTLE_TIME: f32 = 3.0; // s, discarded at every frequency
+pub const BODE_SETTLE_CYCLES: u32 = 3; // Discarded, if longer than the settle time
+pub const BODE_CYCLES: u32 = 5; // Measured after settling
+pub const EVENT_LOG_LEN: usize = 500;
+pub const SCROLL: bool = false; // Shows the full history
+pub const TIME_WINDOW: f32 = 10.0; // s
+pub const HISTORY: f32 = 300.0; // s
+pub const TARGET_SPEED: f64 = 0.01; // m per px
+pub const TARGET_DECIMALS: usize = 3;
+pub const ARROW_KEYS: bool = true;
+pub const TARGET_STEP: f64 = 0.01; // m
+
+// Monte Carlo robustness test
+pub const MC_TRIALS: u32 = 100;
+pub const MC_SPREAD: f32 = 0.2; // ±20 %
+pub const MC_DURATION: f32 = 10.0; // s
+pub const MC_SEED: u64 = 0;
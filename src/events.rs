@@ -0,0 +1,68 @@
+use std::collections::VecDeque;
+
+use crate::default::EVENT_LOG_LEN;
+
+/// Something noteworthy that happened at `time` seconds of simulated time
+#[derive(Clone)]
+pub struct Event {
+    pub time: f32,
+    /// Consecutive events with the same non-empty key are merged, e.g. while dragging a gain
+    pub key: &'static str,
+    pub text: String,
+}
+
+/// Bounded log of events, dropping the oldest ones
+#[derive(Clone, Default)]
+pub struct EventLog {
+    events: VecDeque<Event>,
+}
+
+impl EventLog {
+    pub fn push(&mut self, time: f32, key: &'static str, text: String) {
+        if let Some(last) = self.events.back_mut() {
+            if last.key == key && !key.is_empty() {
+                *last = Event { time, key, text };
+                return;
+            }
+        }
+        if self.events.len() == EVENT_LOG_LEN {
+            self.events.pop_front();
+        }
+        self.events.push_back(Event { time, key, text });
+    }
+
+    /// Moves all events of `other` into this log
+    pub fn append(&mut self, other: &mut EventLog) {
+        for event in other.events.drain(..) {
+            self.push(event.time, event.key, event.text);
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Event> {
+        self.events.iter()
+    }
+
+    pub fn clear(&mut self) {
+        self.events.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merges_and_bounds() {
+        let mut log = EventLog::default();
+        log.push(0.0, "kp", "Kp = 1".into());
+        log.push(0.1, "kp", "Kp = 2".into());
+        assert_eq!(log.iter().count(), 1);
+        assert_eq!(log.iter().next().unwrap().text, "Kp = 2");
+
+        for n in 0..2 * EVENT_LOG_LEN {
+            log.push(n as f32, "", "Reset".into());
+        }
+        assert_eq!(log.iter().count(), EVENT_LOG_LEN);
+        assert_eq!(log.iter().next().unwrap().time, EVENT_LOG_LEN as f32);
+    }
+}
@@ -1,6 +1,24 @@
 #![warn(clippy::all, rust_2018_idioms)]
 
+mod analysis;
 mod app;
+mod config;
 mod default;
+mod events;
+mod metrics;
+mod monte_carlo;
+mod ring_buffer;
+mod scenario;
+mod screenshot;
 mod sim;
+mod stats;
+mod stepper;
+mod tune;
 pub use app::MyApp;
+pub use metrics::ErrorAccumulator;
+pub use sim::{
+    Ball, BangBang, ControllerKind, CouplingModel, Data, DerivativeMode, DerivativeSource,
+    DerivativeStart, Inductor, IntegralMode, LeadLag, Message, NoiseKind, Pid, ProportionalMode,
+    Setpoint, Simulation, TargetProfile,
+};
+pub use web_time::Duration;
@@ -1,10 +1,6 @@
 #![warn(clippy::all, rust_2018_idioms)]
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")] // hide console window on Windows in release
 
-mod app;
-mod default;
-mod sim;
-
 // When compiling natively:
 #[cfg(not(target_arch = "wasm32"))]
 fn main() -> eframe::Result<()> {
@@ -17,7 +13,7 @@ fn main() -> eframe::Result<()> {
     eframe::run_native(
         "PID Ball",
         native_options,
-        Box::new(|_cc| Box::<app::MyApp>::default()),
+        Box::new(|cc| Box::new(pid_ball::MyApp::new(cc))),
     )
 }
 
@@ -34,7 +30,7 @@ fn main() {
             .start(
                 "the_canvas_id", // hardcode it
                 web_options,
-                Box::new(|_cc| Box::new(pid_ball::MyApp::default())),
+                Box::new(|cc| Box::new(pid_ball::MyApp::new(cc))),
             )
             .await
             .expect("failed to start eframe");
@@ -0,0 +1,251 @@
+/// Relative band around the target in which the response counts as settled
+pub const SETTLING_BAND: f32 = 0.02;
+
+/// Fractions of the step height between which the rise time is measured
+pub const RISE_START: f32 = 0.1;
+pub const RISE_END: f32 = 0.9;
+
+/// Ratio of the amplitudes of the second to the first half of the window for instability
+pub const INSTABILITY_GROWTH: f32 = 1.5;
+/// Smallest amplitude [m] of an unstable oscillation, below it is noise
+pub const INSTABILITY_AMPLITUDE: f32 = 0.02;
+/// Fewest crossings of the mean of an oscillation, i.e. two periods
+pub const INSTABILITY_CROSSINGS: usize = 4;
+
+/// Characteristics of a step response
+pub struct StepMetrics {
+    /// Time from 10% to 90% of the step height. `None` if it hasn't reached 90% (yet)
+    pub rise_time: Option<f32>,
+    /// Overshoot beyond the target in percent of the step height
+    pub overshoot: f32,
+    /// Time until the response stays within the settling band around the target.
+    /// `None` if it hasn't settled (yet)
+    pub settling_time: Option<f32>,
+}
+
+/// Metrics of the response `pos` that starts at the first sample and steps towards `target`
+pub fn step_metrics(seconds: &[f32], pos: &[f32], target: f32) -> StepMetrics {
+    let (Some(&start_time), Some(&start)) = (seconds.first(), pos.first()) else {
+        return StepMetrics {
+            rise_time: None,
+            overshoot: 0.0,
+            settling_time: None,
+        };
+    };
+    let height = target - start;
+
+    // First time the response has covered `fraction` of the step height
+    let reached = |fraction: f32| {
+        let level = fraction * height.abs();
+        pos.iter()
+            .position(|&p| (p - start) * height.signum() >= level)
+            .map(|i| seconds[i])
+    };
+    let rise_time = match (height != 0.0, reached(RISE_START), reached(RISE_END)) {
+        (true, Some(from), Some(to)) => Some(to - from),
+        _ => None,
+    };
+
+    // Overshoot in direction of the step
+    let peak = pos
+        .iter()
+        .map(|&p| (p - target) * height.signum())
+        .fold(0.0, f32::max);
+    let overshoot = if height == 0.0 {
+        0.0
+    } else {
+        100.0 * peak / height.abs()
+    };
+
+    // The response has settled after the last sample outside of the band
+    let band = SETTLING_BAND * height.abs();
+    let settling_time = match pos.iter().rposition(|&p| (p - target).abs() > band) {
+        None => Some(0.0),
+        Some(last) => seconds.get(last + 1).map(|&t| t - start_time),
+    };
+
+    StepMetrics {
+        rise_time,
+        overshoot,
+        settling_time,
+    }
+}
+
+/// Whether the ball has gone out of control in `pos`, a window of the latest positions
+///
+/// It is unstable if it leaves `bounds`, bounces between both of them, or oscillates with
+/// an amplitude that grows by `INSTABILITY_GROWTH` from the first to the second half of
+/// the window. NaN and Inf count as having left.
+pub fn detect_instability(pos: &[f32], (low, high): (f32, f32)) -> bool {
+    if pos.iter().any(|p| !(low..=high).contains(p)) {
+        return true;
+    }
+    let hits = |bound: f32| pos.iter().any(|&p| p == bound);
+    if hits(low) && hits(high) {
+        return true;
+    }
+
+    // Half the peak-to-peak amplitude and the crossings of the mean
+    let oscillation = |pos: &[f32]| {
+        let (min, max) = pos
+            .iter()
+            .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), &p| {
+                (min.min(p), max.max(p))
+            });
+        let mean = pos.iter().sum::<f32>() / pos.len() as f32;
+        let crossings = pos
+            .windows(2)
+            .filter(|pair| (pair[0] < mean) != (pair[1] < mean))
+            .count();
+        (0.5 * (max - min), crossings)
+    };
+    let (first, second) = pos.split_at(pos.len() / 2);
+    if first.is_empty() {
+        return false;
+    }
+    let (before, _) = oscillation(first);
+    let (amplitude, crossings) = oscillation(second);
+    crossings >= INSTABILITY_CROSSINGS
+        && amplitude > INSTABILITY_AMPLITUDE
+        && amplitude > INSTABILITY_GROWTH * before
+}
+
+/// Integrals of the control error over a run, which rate a tuning by a single number each
+#[derive(Clone, Copy, Default, Debug, PartialEq)]
+pub struct ErrorAccumulator {
+    /// Integral of the absolute error [m·s]
+    pub iae: f32,
+    /// Integral of the squared error [m²·s], which punishes large errors
+    pub ise: f32,
+    /// Integral of the time-weighted absolute error [m·s²], which punishes lasting errors
+    pub itae: f32,
+}
+
+impl ErrorAccumulator {
+    /// Adds the `error` that has lasted for `dt` seconds until `t` seconds into the run
+    pub fn add(&mut self, error: f32, dt: f32, t: f32) {
+        self.iae += error.abs() * dt;
+        self.ise += error * error * dt;
+        self.itae += t * error.abs() * dt;
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn constant_error() {
+        // An error of -0.5 m for 2 s
+        let mut errors = ErrorAccumulator::default();
+        let dt = 0.001;
+        for i in 1..=2000 {
+            errors.add(-0.5, dt, i as f32 * dt);
+        }
+        assert!((errors.iae - 1.0).abs() < 1e-3, "{}", errors.iae);
+        assert!((errors.ise - 0.5).abs() < 1e-3, "{}", errors.ise);
+        // 0.5 * t^2 / 2 at t = 2 s
+        assert!((errors.itae - 1.0).abs() < 2e-3, "{}", errors.itae);
+
+        errors.reset();
+        assert_eq!(errors, ErrorAccumulator::default());
+    }
+
+    #[test]
+    fn diverging_ramp_is_unstable() {
+        let pos: Vec<f32> = (0..500).map(|i| 0.5 + 0.002 * i as f32).collect();
+        assert!(!detect_instability(&pos[..200], (0.0, 1.0)));
+        assert!(detect_instability(&pos, (0.0, 1.0)));
+        assert!(detect_instability(&[0.5, f32::NAN], (0.0, 1.0)));
+    }
+
+    #[test]
+    fn growing_oscillation_is_unstable() {
+        let oscillation = |growth: f32| -> Vec<f32> {
+            (0..500)
+                .map(|i| {
+                    let t = i as f32 * 0.01;
+                    0.5 + 0.01 * (growth * t).exp() * (10.0 * t).sin()
+                })
+                .collect()
+        };
+        assert!(detect_instability(&oscillation(0.5), (0.0, 1.0)));
+        assert!(!detect_instability(&oscillation(-0.5), (0.0, 1.0)));
+        // Bouncing between floor and ceiling doesn't grow any more
+        let bouncing: Vec<f32> = oscillation(1.0).iter().map(|p| p.clamp(0.4, 0.6)).collect();
+        assert!(detect_instability(&bouncing, (0.4, 0.6)));
+    }
+
+    #[test]
+    fn settling_trace_is_stable() {
+        let (_, pos) = second_order(0.5, 5.0);
+        assert!(!detect_instability(&pos, (-1.0, 2.0)));
+        // A step late in the window isn't an oscillation
+        let step: Vec<f32> = (0..500).map(|i| if i < 400 { 0.5 } else { 0.6 }).collect();
+        assert!(!detect_instability(&step, (0.0, 1.0)));
+        assert!(!detect_instability(&[], (0.0, 1.0)));
+    }
+
+    /// Underdamped second order response from 0 to 1, sampled every 10 ms
+    fn second_order(zeta: f32, omega: f32) -> (Vec<f32>, Vec<f32>) {
+        let omega_d = omega * (1.0 - zeta * zeta).sqrt();
+        let phi = zeta.acos();
+        (0..1000)
+            .map(|i| {
+                let t = i as f32 * 0.01;
+                let envelope = (-zeta * omega * t).exp() / (1.0 - zeta * zeta).sqrt();
+                (t, 1.0 - envelope * (omega_d * t + phi).sin())
+            })
+            .unzip()
+    }
+
+    #[test]
+    fn underdamped_response() {
+        let (seconds, pos) = second_order(0.5, 5.0);
+        let metrics = step_metrics(&seconds, &pos, 1.0);
+        // Analytic overshoot: exp(-pi * zeta / sqrt(1 - zeta^2)) = 16.3%
+        assert!(
+            (metrics.overshoot - 16.3).abs() < 0.2,
+            "{}",
+            metrics.overshoot
+        );
+        // Rise time of about 1.64 / omega for zeta = 0.5
+        let rise_time = metrics.rise_time.unwrap();
+        assert!((rise_time - 0.33).abs() < 0.03, "{rise_time}");
+        // The envelope enters the 2% band after about 4 / (zeta * omega)
+        let settling_time = metrics.settling_time.unwrap();
+        assert!(
+            settling_time > 1.0 && settling_time < 1.8,
+            "{settling_time}"
+        );
+    }
+
+    #[test]
+    fn downward_step() {
+        let (seconds, pos) = second_order(0.5, 5.0);
+        let pos: Vec<f32> = pos.iter().map(|p| 1.0 - p).collect();
+        let metrics = step_metrics(&seconds, &pos, 0.0);
+        assert!(
+            (metrics.overshoot - 16.3).abs() < 0.2,
+            "{}",
+            metrics.overshoot
+        );
+        assert!(metrics.rise_time.is_some());
+    }
+
+    #[test]
+    fn not_yet_settled() {
+        let (seconds, pos) = second_order(0.5, 5.0);
+        let metrics = step_metrics(&seconds[..20], &pos[..20], 1.0);
+        assert!(metrics.rise_time.is_none());
+        assert!(metrics.settling_time.is_none());
+
+        let metrics = step_metrics(&[], &[], 1.0);
+        assert!(metrics.rise_time.is_none());
+        assert!(metrics.settling_time.is_none());
+    }
+}
@@ -0,0 +1,130 @@
+use crate::default::*;
+use crate::metrics::step_metrics;
+use crate::sim::{Message, Simulation};
+use crate::stats::RunningStats;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use web_time::Duration;
+
+/// Robustness test that runs many simulations with randomly perturbed gains
+pub struct MonteCarlo {
+    pub trials: u32,
+    /// Relative perturbation of each gain, e.g. `0.1` draws from ±10 %
+    pub kp_spread: f32,
+    pub ki_spread: f32,
+    pub kd_spread: f32,
+    /// Simulated duration of every trial
+    pub duration: f32,
+    /// Seed for the perturbations and the sensor noise
+    pub seed: u64,
+}
+
+impl Default for MonteCarlo {
+    fn default() -> Self {
+        Self {
+            trials: MC_TRIALS,
+            kp_spread: MC_SPREAD,
+            ki_spread: MC_SPREAD,
+            kd_spread: MC_SPREAD,
+            duration: MC_DURATION,
+            seed: MC_SEED,
+        }
+    }
+}
+
+/// Distribution of the step response metrics over all trials
+#[derive(Default)]
+pub struct Summary {
+    pub overshoot: RunningStats,
+    /// Settling time of the trials that have settled
+    pub settling_time: RunningStats,
+    /// Number of trials that haven't settled within the duration
+    pub unsettled: u32,
+}
+
+impl MonteCarlo {
+    /// Restarted copy of `sim` for `trial`. Seeding first, so the seed also fixes a random start
+    fn start(&self, sim: &Simulation, trial: u32) -> Simulation {
+        let mut sim = sim.clone();
+        sim.seed(self.seed.wrapping_add(trial as u64));
+        sim.config(Message::Reset);
+        sim
+    }
+
+    /// Runs every trial on a restarted copy of `sim` with the gains perturbed around `gains`
+    pub fn run(
+        &self,
+        sim: &Simulation,
+        gains: [f32; 3],
+        target: f32,
+        sampling_time: Duration,
+    ) -> Summary {
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        let mut summary = Summary::default();
+        let steps = (self.duration / sampling_time.as_secs_f32()) as u32;
+        for trial in 0..self.trials {
+            let mut sim = self.start(sim, trial);
+            let [kp, ki, kd] = gains;
+            let mut perturb = |gain: f32, spread: f32| {
+                let spread = spread.abs();
+                if spread > 0.0 {
+                    gain * (1.0 + rng.gen_range(-spread..=spread))
+                } else {
+                    gain
+                }
+            };
+            sim.config(Message::Kp(perturb(kp, self.kp_spread)));
+            sim.config(Message::Ki(perturb(ki, self.ki_spread)));
+            sim.config(Message::Kd(perturb(kd, self.kd_spread)));
+
+            let mut seconds = vec![0.0];
+            let mut pos = vec![sim.step(0, sampling_time).pos];
+            for n in 1..=steps {
+                seconds.push(n as f32 * sampling_time.as_secs_f32());
+                pos.push(sim.step(1, sampling_time).pos);
+            }
+
+            let metrics = step_metrics(&seconds, &pos, target);
+            summary.overshoot.push(metrics.overshoot);
+            match metrics.settling_time {
+                Some(t) => summary.settling_time.push(t),
+                None => summary.unsettled += 1,
+            }
+        }
+        summary
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reproducible() {
+        let mc = MonteCarlo {
+            trials: 5,
+            duration: 2.0,
+            ..Default::default()
+        };
+        let sim = Simulation::default();
+        let sampling_time = Duration::from_millis(10);
+        let a = mc.run(&sim, [KP, KI, KD], TARGET, sampling_time);
+        let b = mc.run(&sim, [KP, KI, KD], TARGET, sampling_time);
+        assert_eq!(a.overshoot.count(), 5);
+        assert_eq!(a.overshoot.mean(), b.overshoot.mean());
+        assert_eq!(a.overshoot.max(), b.overshoot.max());
+        assert_eq!(a.settling_time.mean(), b.settling_time.mean());
+        assert_eq!(a.unsettled, b.unsettled);
+    }
+
+    #[test]
+    fn trials_start_randomly() {
+        let mc = MonteCarlo::default();
+        let mut sim = Simulation::default();
+        sim.config(Message::RandomStart(true));
+        let sampling_time = Duration::from_millis(10);
+        let a = mc.start(&sim, 0).step(0, sampling_time).pos;
+        let b = mc.start(&sim, 1).step(0, sampling_time).pos;
+        assert_ne!(a, b);
+    }
+}
@@ -0,0 +1,85 @@
+use std::ops::Deref;
+
+/// Growable buffer that drops from the front in amortized constant time
+///
+/// Unlike a `VecDeque` the samples stay contiguous, so the buffer derefs to a slice
+/// for plotting.
+#[derive(Clone)]
+pub struct RingBuffer<T> {
+    data: Vec<T>,
+    /// Index of the oldest sample that hasn't been dropped
+    start: usize,
+}
+
+impl<T> Default for RingBuffer<T> {
+    fn default() -> Self {
+        Self {
+            data: Vec::new(),
+            start: 0,
+        }
+    }
+}
+
+impl<T> RingBuffer<T> {
+    pub fn push(&mut self, val: T) {
+        self.data.push(val);
+    }
+
+    /// Drops the `count` oldest samples
+    pub fn drop_front(&mut self, count: usize) {
+        self.start = (self.start + count).min(self.data.len());
+        // Moving the samples once the dropped ones dominate keeps the cost amortized
+        if self.start > self.data.len() / 2 {
+            self.data.drain(..self.start);
+            self.start = 0;
+        }
+    }
+
+    /// Keeps the `len` oldest samples
+    pub fn truncate(&mut self, len: usize) {
+        self.data.truncate(self.start + len);
+    }
+
+    pub fn clear(&mut self) {
+        self.data.clear();
+        self.start = 0;
+    }
+}
+
+impl<T> Deref for RingBuffer<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        &self.data[self.start..]
+    }
+}
+
+impl<'a, T> IntoIterator for &'a RingBuffer<T> {
+    type Item = &'a T;
+    type IntoIter = std::slice::Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn drops_the_oldest() {
+        let mut buffer = RingBuffer::default();
+        for n in 0..10 {
+            buffer.push(n);
+            if buffer.len() > 3 {
+                buffer.drop_front(1);
+            }
+        }
+        assert_eq!(&*buffer, &[7, 8, 9]);
+        assert!(buffer.data.len() <= 2 * 3, "dropped samples must be freed");
+
+        buffer.drop_front(10);
+        assert!(buffer.is_empty());
+    }
+}
@@ -0,0 +1,109 @@
+use std::collections::BTreeMap;
+
+use crate::config::Config;
+use crate::sim::Setpoint;
+use serde::{Deserialize, Serialize};
+
+/// Version of the scenario schema that is written and the newest one that can be read
+pub const VERSION: u32 = 1;
+
+/// Complete, reproducible experiment: configuration, noise seed and setpoint profile
+///
+/// Missing fields take their default value and unknown fields are ignored,
+/// so scenarios stay loadable when the schema grows.
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct Scenario {
+    pub version: u32,
+    /// Seed of the sensor noise
+    pub seed: u64,
+    /// Its target holds until the first setpoint of the profile
+    #[serde(flatten)]
+    pub config: Config,
+    pub profile: Vec<Setpoint>,
+    /// Fields this version doesn't know
+    #[serde(flatten, skip_serializing)]
+    pub unknown: BTreeMap<String, serde_json::Value>,
+}
+
+impl Default for Scenario {
+    fn default() -> Self {
+        Self {
+            version: VERSION,
+            seed: 0,
+            config: Config::default(),
+            profile: Vec::new(),
+            unknown: BTreeMap::new(),
+        }
+    }
+}
+
+impl Scenario {
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let mut scenario: Self = serde_json::from_str(json).map_err(|e| e.to_string())?;
+        scenario.config = scenario.config.migrate();
+        if scenario.version > VERSION {
+            return Err(format!(
+                "Scenario version {} is newer than the supported version {VERSION}",
+                scenario.version
+            ));
+        }
+        for field in scenario.unknown.keys() {
+            log::warn!("Ignoring unknown scenario field `{field}`");
+        }
+        Ok(scenario)
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("scenario is serializable")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::default::KP;
+
+    #[test]
+    fn round_trip() {
+        let scenario = Scenario {
+            seed: 42,
+            config: Config {
+                kp: 100.0,
+                ..Default::default()
+            },
+            profile: vec![Setpoint {
+                time: 1.5,
+                target: 0.4,
+            }],
+            ..Default::default()
+        };
+        let loaded = Scenario::from_json(&scenario.to_json()).unwrap();
+        assert_eq!(loaded.seed, 42);
+        assert_eq!(loaded.config.kp, 100.0);
+        assert_eq!(loaded.profile.len(), 1);
+        assert_eq!(loaded.profile[0].target, 0.4);
+    }
+
+    #[test]
+    fn missing_and_unknown_fields() {
+        let scenario =
+            Scenario::from_json(r#"{"version": 1, "ki": 2.0, "colour": "red"}"#).unwrap();
+        assert_eq!(scenario.config.ki, 2.0);
+        assert_eq!(scenario.config.kp, KP);
+        assert!(scenario.unknown.contains_key("colour"));
+        assert!(!scenario.unknown.contains_key("ki"));
+    }
+
+    #[test]
+    fn signed_gravitation_is_migrated() {
+        let scenario = Scenario::from_json(r#"{"version": 1, "gravitation": -3.71}"#).unwrap();
+        assert_eq!(scenario.config.gravity, 3.71);
+        assert!(scenario.unknown.is_empty());
+    }
+
+    #[test]
+    fn newer_version() {
+        assert!(Scenario::from_json(r#"{"version": 2}"#).is_err());
+    }
+}
@@ -0,0 +1,165 @@
+#[cfg(not(target_arch = "wasm32"))]
+use egui::{Color32, ColorImage};
+use egui::{Pos2, Rect};
+
+/// Part of `image` within `rect` in physical pixels, clipped to the image
+#[cfg(not(target_arch = "wasm32"))]
+pub fn crop(image: &ColorImage, rect: Rect) -> ColorImage {
+    let [width, height] = image.size;
+    let bounds = Rect::from_min_max(Pos2::ZERO, [width as f32, height as f32].into());
+    let rect = rect.intersect(bounds);
+    if !rect.is_positive() {
+        return ColorImage::new([0, 0], Color32::TRANSPARENT);
+    }
+    image.region(&rect, None)
+}
+
+/// Encodes `image` as PNG with 8-bit RGBA
+#[cfg(not(target_arch = "wasm32"))]
+pub fn encode_png(image: &ColorImage) -> Result<Vec<u8>, String> {
+    let [width, height] = image.size;
+    let mut bytes = Vec::new();
+    let mut encoder = png::Encoder::new(&mut bytes, width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let rgba: Vec<u8> = image
+        .pixels
+        .iter()
+        .flat_map(|color| color.to_srgba_unmultiplied())
+        .collect();
+    encoder
+        .write_header()
+        .and_then(|mut writer| writer.write_image_data(&rgba))
+        .map_err(|e| e.to_string())?;
+    Ok(bytes)
+}
+
+/// Downloads the part of the canvas within `rect` in physical pixels as PNG
+///
+/// The drawing buffer of WebGL is only valid until the frame is presented, so this must run
+/// after painting but before the browser composites, e.g. in a microtask of the frame.
+#[cfg(target_arch = "wasm32")]
+pub fn download_canvas(canvas_id: &str, rect: Rect, file_name: &str) -> Result<(), String> {
+    use wasm_bindgen::JsCast;
+    use web_sys::{CanvasRenderingContext2d, HtmlCanvasElement};
+
+    let js_error = |e: wasm_bindgen::JsValue| format!("{e:?}");
+    let document = web_sys::window()
+        .and_then(|window| window.document())
+        .ok_or("No document")?;
+    let canvas: HtmlCanvasElement = document
+        .get_element_by_id(canvas_id)
+        .and_then(|element| element.dyn_into().ok())
+        .ok_or_else(|| format!("No canvas with id {canvas_id}"))?;
+    let bounds = Rect::from_min_size(
+        Pos2::ZERO,
+        [canvas.width() as f32, canvas.height() as f32].into(),
+    );
+    let rect = rect.intersect(bounds);
+    let rect = Rect::from_min_max(rect.min.round(), rect.max.round());
+    if !rect.is_positive() {
+        return Err("Empty plot area".to_owned());
+    }
+
+    // Copying the plot area to a canvas of its own
+    let cropped: HtmlCanvasElement = document
+        .create_element("canvas")
+        .map_err(js_error)?
+        .unchecked_into();
+    cropped.set_width(rect.width() as u32);
+    cropped.set_height(rect.height() as u32);
+    let context: CanvasRenderingContext2d = cropped
+        .get_context("2d")
+        .map_err(js_error)?
+        .ok_or("No 2D context")?
+        .unchecked_into();
+    let [x, y, w, h] = [rect.min.x, rect.min.y, rect.width(), rect.height()].map(f64::from);
+    context
+        .draw_image_with_html_canvas_element_and_sw_and_sh_and_dx_and_dy_and_dw_and_dh(
+            &canvas, x, y, w, h, 0.0, 0.0, w, h,
+        )
+        .map_err(js_error)?;
+
+    let url = cropped.to_data_url().map_err(js_error)?;
+    download_url(&document, &url, file_name)
+}
+
+/// Downloads `text` as file
+#[cfg(target_arch = "wasm32")]
+pub fn download_text(text: &str, file_name: &str) -> Result<(), String> {
+    let document = web_sys::window()
+        .and_then(|window| window.document())
+        .ok_or("No document")?;
+    download_url(&document, &data_url(text), file_name)
+}
+
+/// Clicking a temporary link starts the download
+#[cfg(target_arch = "wasm32")]
+fn download_url(document: &web_sys::Document, url: &str, file_name: &str) -> Result<(), String> {
+    use wasm_bindgen::JsCast;
+    use web_sys::HtmlAnchorElement;
+
+    let anchor: HtmlAnchorElement = document
+        .create_element("a")
+        .map_err(|e| format!("{e:?}"))?
+        .unchecked_into();
+    anchor.set_href(url);
+    anchor.set_download(file_name);
+    anchor.click();
+    Ok(())
+}
+
+/// Data URL of `text`, percent-encoded apart from the unreserved characters
+#[cfg(any(target_arch = "wasm32", test))]
+fn data_url(text: &str) -> String {
+    let mut url = String::from("data:text/plain;charset=utf-8,");
+    for byte in text.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-_.~".contains(&byte) {
+            url.push(byte as char);
+        } else {
+            url += &format!("%{byte:02X}");
+        }
+    }
+    url
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn png_round_trip() {
+        let image = ColorImage {
+            size: [2, 1],
+            pixels: vec![Color32::RED, Color32::from_rgb(10, 20, 30)],
+        };
+        let bytes = encode_png(&image).unwrap();
+
+        let mut reader = png::Decoder::new(bytes.as_slice()).read_info().unwrap();
+        let mut buffer = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buffer).unwrap();
+        assert_eq!([info.width, info.height], [2, 1]);
+        assert_eq!(buffer, [255, 0, 0, 255, 10, 20, 30, 255]);
+    }
+
+    #[test]
+    fn data_url_is_percent_encoded() {
+        assert_eq!(
+            data_url("{\"kp\": 1.5}\nä"),
+            "data:text/plain;charset=utf-8,%7B%22kp%22%3A%201.5%7D%0A%C3%A4"
+        );
+    }
+
+    #[test]
+    fn crop_is_clipped_to_the_image() {
+        let mut image = ColorImage::new([4, 3], Color32::BLACK);
+        image.pixels[4 + 3] = Color32::WHITE; // (3, 1)
+        let rect = Rect::from_min_max([3.0, 1.0].into(), [10.0, 2.0].into());
+        let cropped = crop(&image, rect);
+        assert_eq!(cropped.size, [1, 1]);
+        assert_eq!(cropped.pixels, [Color32::WHITE]);
+
+        let outside = Rect::from_min_max([5.0, 5.0].into(), [6.0, 6.0].into());
+        assert_eq!(crop(&image, outside).size, [0, 0]);
+    }
+}
@@ -1,247 +1,2164 @@
 use crate::default::*;
-use rand::rngs::ThreadRng;
-use rand_distr::{Distribution, Normal};
+use crate::events::EventLog;
+use crate::metrics::ErrorAccumulator;
+pub use pid_ball_core::{
+    Ball, BangBang, Controller, DerivativeMode, DerivativeSource, DerivativeStart, Inductor,
+    IntegralMode, LeadLag, Pid, ProportionalMode,
+};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rand_distr::{Distribution, Exp1, Normal};
+use serde::{Deserialize, Serialize};
+use std::any::Any;
+use std::collections::VecDeque;
 use web_time::Duration;
 
+/// Selectable control law
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ControllerKind {
+    Pid,
+    LeadLag,
+    BangBang,
+}
+
+impl ControllerKind {
+    pub const ALL: [ControllerKind; 3] = [
+        ControllerKind::Pid,
+        ControllerKind::LeadLag,
+        ControllerKind::BangBang,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ControllerKind::Pid => "PID",
+            ControllerKind::LeadLag => "Lead-lag",
+            ControllerKind::BangBang => "Bang-bang",
+        }
+    }
+}
+
+/// [`Controller`] that can be cloned behind a `Box`, e.g. for the Monte Carlo trials,
+/// and configured via downcasting
+pub trait BoxedController: Controller + Send {
+    fn boxed_clone(&self) -> Box<dyn BoxedController>;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<C: Controller + Clone + Send + 'static> BoxedController for C {
+    fn boxed_clone(&self) -> Box<dyn BoxedController> {
+        Box::new(self.clone())
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+impl Clone for Box<dyn BoxedController> {
+    fn clone(&self) -> Self {
+        self.boxed_clone()
+    }
+}
+
 /// Simulation of the floating ball
+#[derive(Clone)]
 pub struct Simulation {
+    /// Active control law
+    controller: Box<dyn BoxedController>,
+    /// Configuration of every control law, cloned into `controller` when switching to it
     pid: Pid,
+    lead_lag: LeadLag,
+    bang_bang: BangBang,
+    target: f32,
+    /// Overrides `target` every step unless it is constant
+    target_profile: TargetProfile,
     ball: Ball,
     ind: Inductor,
     sensor: Sensor,
-    gravitation: f32,
+    /// Downward acceleration [m/s^2], as the position is the height with up positive
+    gravity: f32,
+    /// Standard deviation of a random force on the ball, e.g. by air currents
+    process_noise: f32,
+    /// How the force of the inductor on the ball falls off with their distance
+    coupling: CouplingModel,
+    /// Squared distance [m^2] added to the squared distance to the inductor, which keeps the
+    /// force finite next to it and sets the scale of the near field
+    softening: f32,
+    /// The ball stays between `floor` and `ceiling` of the tube
+    floor: f32,
+    ceiling: f32,
+    /// Ratio of the velocities after and before hitting floor or ceiling
+    restitution: f32,
     hold_ball: bool,
+    /// The physics advances in `sub_steps` equal parts per step
+    sub_steps: u32,
+    /// The controller updates every `control_divider` steps (zero-order hold in between)
+    control_divider: u32,
+    /// Number of steps since the last controller update
+    control_counter: u32,
+    /// Every reset replays the noise of the current run instead of continuing it
+    freeze_noise: bool,
+    /// State of the noise generator at the start of the current run
+    noise_start: StdRng,
+    /// Every reset draws the position and velocity of the ball within these spreads
+    random_start: bool,
+    pos_spread: f32,
+    vel_spread: f32,
+    /// The inductor moves along with the target, keeping its default distance
+    follow_target: bool,
+    /// Maximum speed of the inductor when following the target [m/s]
+    follow_rate: f32,
+    /// Releasing the ball starts the controller and inductor balancing the gravitation
+    smooth_release: bool,
+    /// Every reset starts the integral at its steady-state value
+    preload: bool,
+    /// The loop stays open for this long after a reset, keeping the initial force
+    engage_delay: Duration,
+    /// Rejects outliers of the measurement before the controller
+    input_filter: Median,
+    /// Smooths the controller output before it reaches the inductor
+    output_filter: LowPass,
+    /// Negates the controller output for actuators that act against the command
+    invert_output: bool,
+    /// Changes of the target over time, sorted by time
+    profile: Vec<Setpoint>,
+    /// Index of the next setpoint of the profile
+    next_setpoint: usize,
+    /// Simulated time since the last reset
+    elapsed: Duration,
+    /// Integrals of the error between target and true position since the last reset
+    errors: ErrorAccumulator,
+    /// The inductor has been saturated on the last controller update
+    saturated: bool,
+    /// The inductor has clipped the force on the last controller update, by rate or magnitude
+    clipped: bool,
+    /// Events since the last [`Simulation::take_events`]
+    events: EventLog,
+    /// Noise-free copy with a much finer physics step, which approximates the continuous plant
+    reference: Option<Box<Simulation>>,
+    /// Last finite data before NaN or Inf came up, with which the simulation halts until reset
+    diverged: Option<Data>,
 }
 
 impl Default for Simulation {
     fn default() -> Self {
-        Self {
+        let mut sim = Self {
+            controller: Box::<Pid>::default(),
             pid: Default::default(),
+            lead_lag: Default::default(),
+            bang_bang: Default::default(),
+            target: TARGET,
+            target_profile: TargetProfile::Constant(TARGET),
             ball: Default::default(),
             ind: Default::default(),
             sensor: Default::default(),
-            gravitation: GRAVITATION,
+            gravity: GRAVITY,
+            process_noise: PROCESS_NOISE,
+            coupling: COUPLING,
+            softening: FORCE_SOFTENING,
+            floor: FLOOR,
+            ceiling: CEILING,
+            restitution: RESTITUTION,
             hold_ball: HOLD_BALL,
-        }
+            sub_steps: SUB_STEPS,
+            control_divider: CONTROL_DIVIDER,
+            control_counter: 0,
+            freeze_noise: FREEZE_NOISE,
+            noise_start: StdRng::seed_from_u64(0),
+            random_start: RANDOM_START,
+            pos_spread: POS_SPREAD,
+            vel_spread: VEL_SPREAD,
+            follow_target: FOLLOW_TARGET,
+            follow_rate: FOLLOW_RATE,
+            smooth_release: SMOOTH_RELEASE,
+            preload: PRELOAD,
+            engage_delay: Duration::from_secs_f32(ENGAGE_DELAY),
+            input_filter: Median::new(MEDIAN_WINDOW),
+            output_filter: LowPass::new(OUTPUT_CUTOFF),
+            invert_output: INVERT_OUTPUT,
+            profile: Vec::new(),
+            next_setpoint: 0,
+            elapsed: Duration::ZERO,
+            errors: ErrorAccumulator::default(),
+            saturated: false,
+            clipped: false,
+            events: EventLog::default(),
+            reference: None,
+            diverged: None,
+        };
+        sim.noise_start = sim.sensor.rng.clone();
+        sim.switch(CONTROLLER);
+        sim
     }
 }
 
 impl Simulation {
+    /// Simulation whose sensor noise is reproducible
+    pub fn from_seed(seed: u64) -> Self {
+        let mut sim = Self::default();
+        sim.seed(seed);
+        sim
+    }
+
+    /// Settings of the PID controller, whether it is active or not
+    pub fn pid(&self) -> &Pid {
+        &self.pid
+    }
+
+    /// Integrals of the error between target and true position since the last reset
+    pub fn errors(&self) -> &ErrorAccumulator {
+        &self.errors
+    }
+
+    /// Restarts the random number generator of the sensor noise
+    pub fn seed(&mut self, seed: u64) {
+        self.sensor.rng = StdRng::seed_from_u64(seed);
+        self.noise_start = self.sensor.rng.clone();
+    }
+
+    /// Captures everything but the random number generator of the noise
+    pub fn snapshot(&self) -> SimSnapshot {
+        SimSnapshot(Box::new(Simulation {
+            events: EventLog::default(),
+            ..self.clone()
+        }))
+    }
+
+    /// Returns to the state of `snapshot`, continuing with the current noise
+    pub fn restore(&mut self, snapshot: &SimSnapshot) {
+        let rng = self.sensor.rng.clone();
+        let noise_start = self.noise_start.clone();
+        let mut events = std::mem::take(&mut self.events);
+        *self = (*snapshot.0).clone();
+        self.sensor.rng = rng;
+        self.noise_start = noise_start;
+        std::mem::swap(&mut self.events, &mut events);
+        self.event("", "State restored".to_owned());
+    }
+
+    /// Moves the events since the last call into `log`
+    pub fn take_events(&mut self, log: &mut EventLog) {
+        log.append(&mut self.events);
+    }
+
+    fn event(&mut self, key: &'static str, text: String) {
+        self.events.push(self.elapsed.as_secs_f32(), key, text);
+    }
+
+    /// Activates a fresh controller of the given kind
+    fn switch(&mut self, kind: ControllerKind) {
+        self.controller = match kind {
+            ControllerKind::Pid => Box::new(self.pid.clone()),
+            ControllerKind::LeadLag => Box::new(self.lead_lag.clone()),
+            ControllerKind::BangBang => Box::new(self.bang_bang.clone()),
+        };
+        self.controller.reset();
+    }
+
+    /// Configures the PID controller, whether it is active or not
+    fn config_pid(&mut self, f: impl Fn(&mut Pid)) {
+        f(&mut self.pid);
+        if let Some(pid) = self.controller.as_any_mut().downcast_mut() {
+            f(pid);
+        }
+    }
+
+    /// Configures the lead-lag compensator, whether it is active or not
+    fn config_lead_lag(&mut self, f: impl Fn(&mut LeadLag)) {
+        f(&mut self.lead_lag);
+        if let Some(lead_lag) = self.controller.as_any_mut().downcast_mut() {
+            f(lead_lag);
+        }
+    }
+
+    /// Configures the bang-bang controller, whether it is active or not
+    fn config_bang_bang(&mut self, f: impl Fn(&mut BangBang)) {
+        f(&mut self.bang_bang);
+        if let Some(bang_bang) = self.controller.as_any_mut().downcast_mut() {
+            f(bang_bang);
+        }
+    }
+
     pub fn config(&mut self, msg: Message) {
+        if let Some(reference) = &mut self.reference {
+            match &msg {
+                // The reference stays noise-free and is rebuilt by `reset`
+                Message::Noise(_)
+                | Message::VelocityNoise(_)
+                | Message::ProcessNoise(_)
+                | Message::OutlierProbability(_)
+                | Message::Reset
+                | Message::Restart
+                | Message::Reference(_) => (),
+                Message::SubSteps(n) => {
+                    reference.config(Message::SubSteps(n * REFERENCE_SUB_STEPS))
+                }
+                msg => reference.config(msg.clone()),
+            }
+        }
         match msg {
-            Message::Kp(kp) => self.pid.kp = kp,
-            Message::Ki(ki) => self.pid.ki = ki,
-            Message::Kd(kd) => self.pid.kd = kd,
-            Message::Target(t) => self.pid.target = t,
+            // Bumpless, so tuning while running doesn't kick the ball
+            Message::Kp(kp) => {
+                self.config_pid(|pid| pid.set_kp(kp));
+                self.event("kp", format!("Kp = {kp}"));
+            }
+            Message::Ki(ki) => {
+                self.config_pid(|pid| pid.set_ki(ki));
+                self.event("ki", format!("Ki = {ki}"));
+            }
+            Message::Kd(kd) => {
+                self.config_pid(|pid| pid.set_kd(kd));
+                self.event("kd", format!("Kd = {kd}"));
+            }
+            Message::FeedForward(kff) => self.config_pid(|pid| pid.kff = kff),
+            Message::Kt(kt) => {
+                self.config_pid(|pid| pid.kt = kt);
+                self.event("kt", format!("Kt = {kt}"));
+            }
+            Message::ProportionalMode(m) => self.config_pid(|pid| pid.p_mode = m),
+            Message::DFilter(tau) => self.config_pid(|pid| pid.d_filter_tau = tau.max(0.0)),
+            Message::DerivativeMode(m) => self.config_pid(|pid| pid.d_mode = m),
+            Message::DerivativeStart(s) => self.config_pid(|pid| pid.d_start = s),
+            Message::DerivativeSource(s) => self.config_pid(|pid| pid.d_source = s),
+            Message::IntegralMode(m) => self.config_pid(|pid| pid.i_mode = m),
+            Message::ConditionalIntegration(b) => self.config_pid(|pid| pid.conditional = b),
+            Message::IntegralLimit(l) => {
+                let limit = l.is_finite().then_some(l.abs());
+                self.config_pid(|pid| pid.i_limit = limit);
+            }
+            Message::IntegralBand(b) => {
+                let band = b.is_finite().then_some(b.abs());
+                self.config_pid(|pid| pid.i_band = band);
+            }
+            Message::OutputLimit(l) => {
+                let limit = l.is_finite().then_some(l.abs());
+                self.config_pid(|pid| pid.output_limit = limit);
+            }
+            Message::ControllerKind(kind) => {
+                self.switch(kind);
+                self.event("", format!("Controller: {}", kind.name()));
+            }
+            Message::LeadLagGain(k) => self.config_lead_lag(|c| c.gain = k),
+            Message::LeadTime(t) => self.config_lead_lag(|c| c.lead = t.max(0.0)),
+            Message::LagTime(t) => self.config_lead_lag(|c| c.lag = t.max(0.0)),
+            Message::LeadLagBias(b) => self.config_lead_lag(|c| c.bias = b),
+            Message::BangBangForce(f) => self.config_bang_bang(|c| c.force = f),
+            Message::BangBangHysteresis(h) => self.config_bang_bang(|c| c.hysteresis = h.max(0.0)),
+            Message::Target(t) => {
+                self.target = t;
+                if let TargetProfile::Constant(target) = &mut self.target_profile {
+                    *target = t;
+                }
+                self.event("target", format!("Target = {t} m"));
+            }
+            Message::TargetProfile(profile) => {
+                if let TargetProfile::Constant(t) = profile {
+                    self.target = t;
+                }
+                self.target_profile = profile;
+                self.event("target_profile", format!("Target: {}", profile.name()));
+            }
+            Message::Profile(mut profile) => {
+                profile.sort_by(|a, b| a.time.total_cmp(&b.time));
+                self.profile = profile;
+                self.next_setpoint = 0;
+            }
+            Message::ErrorDeadZone(z) => self.config_pid(|pid| pid.dead_zone = z.max(0.0)),
             Message::Noise(s) => self.sensor.set_sigma(s),
-            Message::Gravitation(g) => self.gravitation = g,
-            Message::MaxForce(f) => self.ind.max_force = f,
+            Message::VelocityNoise(s) => self.sensor.vel_noise = s.max(0.0),
+            Message::NoiseKind(kind) => self.sensor.kind = kind,
+            Message::SensorTau(tau) => self.sensor.tau = tau.max(0.0),
+            Message::SensorDelay(t) => self.sensor.delay = Duration::from_secs_f32(t.max(0.0)),
+            Message::SensorResolution(r) => self.sensor.resolution = (r > 0.0).then_some(r),
+            Message::OutlierProbability(p) => self.sensor.outlier_probability = p.clamp(0.0, 1.0),
+            Message::OutlierMagnitude(m) => self.sensor.outlier_magnitude = m,
+            Message::FreezeNoise(b) => self.freeze_noise = b,
+            Message::Seed(seed) => self.seed(seed.unwrap_or_else(rand::random)),
+            Message::InitPos(p) => self.ball.initial_pos = p,
+            Message::InitVel(v) => self.ball.initial_vel = v,
+            Message::RandomStart(b) => self.random_start = b,
+            Message::PosSpread(s) => self.pos_spread = s.max(0.0),
+            Message::VelSpread(s) => self.vel_spread = s.max(0.0),
+            Message::Gravity(g) => self.gravity = g,
+            Message::ProcessNoise(s) => self.process_noise = s.max(0.0),
+            Message::Coupling(model) => self.coupling = model,
+            Message::ForceSoftening(s) => self.softening = s.max(f32::EPSILON),
+            Message::MaxVelocity(v) => self.ball.max_vel = v.max(0.0),
+            // A vanishing mass would accelerate infinitely
+            Message::Mass(m) => self.ball.mass = m.max(MIN_MASS),
+            Message::Floor(f) => self.floor = f,
+            Message::Ceiling(c) => self.ceiling = c,
+            Message::Restitution(r) => self.restitution = r.clamp(0.0, 1.0),
+            // Negative limits would cross and make clamping the force panic
+            Message::MaxForce(f) => {
+                self.ind.max_force_up = f.max(0.0);
+                self.ind.max_force_down = f.max(0.0);
+            }
+            Message::MaxForceUp(f) => self.ind.max_force_up = f.max(0.0),
+            Message::MaxForceDown(f) => self.ind.max_force_down = f.max(0.0),
             Message::MaxForceRate(f) => self.ind.max_force_rate = f,
-            Message::HoldBall(b) => self.hold_ball = b,
+            Message::InitialForce(f) => self.ind.initial_force = f,
+            Message::ActuatorGain(g) => self.ind.gain = g,
+            Message::Deadband(f) => self.ind.deadband = f.max(0.0),
+            Message::DeadTime(t) => self.ind.dead_time = Duration::from_secs_f32(t.max(0.0)),
+            Message::InvertOutput(b) => self.invert_output = b,
+            Message::OutputCutoff(f) => self.output_filter.cutoff = f.max(0.0),
+            Message::MedianWindow(n) => self.input_filter.set_window(n as usize),
+            Message::EngageDelay(t) => self.engage_delay = Duration::from_secs_f32(t.max(0.0)),
+            Message::HoldBall(b) => {
+                if self.hold_ball && !b && self.smooth_release {
+                    self.release();
+                }
+                self.hold_ball = b;
+            }
+            Message::Disturbance(vel) => {
+                // A held ball can't be pushed
+                if !self.hold_ball {
+                    self.ball.vel += vel;
+                    self.event("", format!("Push: {vel:+} m/s"));
+                }
+            }
+            Message::SmoothRelease(b) => self.smooth_release = b,
+            Message::Preload(b) => self.preload = b,
+            Message::SubSteps(n) => self.sub_steps = n.max(1),
+            Message::ControlDivider(n) => self.control_divider = n.max(1),
+            Message::FollowTarget(b) => self.follow_target = b,
+            Message::FollowRate(r) => self.follow_rate = r.max(0.0),
+            Message::Reference(b) => self.reference = b.then(|| self.reference()),
             Message::Restart => *self = Default::default(),
             Message::Reset => self.reset(),
         }
     }
     pub fn reset(&mut self) {
-        self.pid.reset();
+        self.controller.reset();
         self.ball.reset();
         self.ind.reset();
-        self.sensor.reset()
+        if self.preload {
+            // Steady state at the target, so the integral doesn't have to ramp up first
+            let force = self.balancing_force(self.target);
+            self.preload_integral(force);
+        }
+        self.sensor.reset();
+        self.input_filter.reset();
+        self.output_filter.reset();
+        if self.freeze_noise {
+            self.sensor.rng = self.noise_start.clone();
+        } else {
+            self.noise_start = self.sensor.rng.clone();
+        }
+        // Drawn from the (possibly reseeded) noise generator, so a seed fixes the start as well
+        if self.random_start {
+            let rng = &mut self.sensor.rng;
+            self.ball.pos += rng.gen_range(-self.pos_spread..=self.pos_spread);
+            self.ball.vel += rng.gen_range(-self.vel_spread..=self.vel_spread);
+        }
+        self.control_counter = 0;
+        self.next_setpoint = 0;
+        self.elapsed = Duration::ZERO;
+        self.errors.reset();
+        self.saturated = false;
+        self.clipped = false;
+        self.diverged = None;
+        if self.reference.is_some() {
+            self.reference = Some(self.reference());
+        }
+    }
+
+    /// Copy of the current state without sensor noise and outliers, whose physics takes
+    /// `REFERENCE_SUB_STEPS` times as many sub-steps
+    fn reference(&self) -> Box<Simulation> {
+        let mut reference = Box::new(Simulation {
+            reference: None,
+            events: EventLog::default(),
+            ..self.clone()
+        });
+        reference.sensor.set_sigma(0.0);
+        reference.sensor.vel_noise = 0.0;
+        reference.sensor.outlier_probability = 0.0;
+        reference.process_noise = 0.0;
+        reference.sub_steps = self.sub_steps * REFERENCE_SUB_STEPS;
+        reference
+    }
+
+    /// Discards what the controller accumulated while holding the ball and preloads the
+    /// inductor with the force that balances the gravitation at the current distance
+    fn release(&mut self) {
+        let force = self.balancing_force(self.ball.pos);
+        self.ind.preload(force);
+        self.controller.reset();
+        self.preload_integral(self.ind.force());
+    }
+
+    /// Keeps the ball inside the tube, reflecting its velocity by the restitution
+    fn collide(&mut self) {
+        let ball = &mut self.ball;
+        if ball.pos < self.floor {
+            ball.pos = self.floor;
+            ball.vel = ball.vel.max(-self.restitution * ball.vel);
+        } else if ball.pos > self.ceiling {
+            ball.pos = self.ceiling;
+            ball.vel = ball.vel.min(-self.restitution * ball.vel);
+        }
+    }
+
+    /// Force of the inductor that holds the ball at `pos` against the gravitation
+    fn balancing_force(&self, pos: f32) -> f32 {
+        let dis = (pos - self.ind.pos).abs();
+        let weight = self.ball.mass * self.gravity;
+        self.coupling.inverse(weight, dis, self.softening)
+    }
+
+    /// Starts the integral with the controller output that makes the inductor exert `force`
+    fn preload_integral(&mut self, force: f32) {
+        if self.ind.gain != 0.0 {
+            let sign = if self.invert_output { -1.0 } else { 1.0 };
+            self.controller.preload(sign * force / self.ind.gain);
+        }
+    }
+
+    /// Runs for `duration` without the GUI and returns the data of every sample
+    pub fn run(&mut self, duration: Duration, sampling_time: Duration) -> Vec<Data> {
+        if sampling_time.is_zero() {
+            return Vec::new();
+        }
+        let samples = duration.as_nanos() / sampling_time.as_nanos();
+        (0..samples).map(|_| self.step(1, sampling_time)).collect()
     }
 
     pub fn step(&mut self, steps: u32, sampling_time: Duration) -> Data {
+        // A zero sampling time would divide by zero in the controller and the inductor
+        if sampling_time.is_zero() || self.diverged.is_some() {
+            return self.data();
+        }
+        if let Some(reference) = &mut self.reference {
+            reference.step(steps, sampling_time);
+        }
+
+        // Whether the limits of the inductor have clipped on any of the steps
+        let mut magnitude_limited = false;
+        let mut rate_limited = false;
+        // Latest finite data, so NaN and Inf never leave the simulation
+        let mut finite = self.data();
         for _ in 0..steps {
+            let data = self.data();
+            if !data.is_finite() {
+                break;
+            }
+            finite = data;
+
+            // Following the setpoint profile
+            let now = self.elapsed.as_secs_f32();
+            while let Some(setpoint) = self.profile.get(self.next_setpoint) {
+                if setpoint.time > now {
+                    break;
+                }
+                let target = setpoint.target;
+                self.target = target;
+                self.next_setpoint += 1;
+                self.event("", format!("Setpoint: target = {target} m"));
+            }
+            if !matches!(self.target_profile, TargetProfile::Constant(_)) {
+                self.target = self.target_profile.target(now);
+            }
+            self.elapsed += sampling_time;
+
+            // Moving the inductor towards the target with a limited slew rate
+            if self.follow_target {
+                let goal = self.target + IND_POS - TARGET;
+                let max_delta = self.follow_rate * sampling_time.as_secs_f32();
+                let delta = (goal - self.ind.pos).clamp(-max_delta, max_delta);
+                self.ind.pos += delta;
+            }
+
             // Moving the ball
             if !self.hold_ball {
-                let dis = (self.ball.pos - self.ind.pos).abs();
-                let force = self.ind.force();
-                let force = force / (1.0 + dis.powi(2));
-                let force = force + self.gravitation;
-                self.ball.step(force, sampling_time);
+                // Drawn from the noise generator of the sensor, so a seed fixes it as well.
+                // Without process noise nothing is drawn and the sensor noise stays the same
+                let disturbance = if self.process_noise > 0.0 {
+                    NoiseKind::Normal.sample(self.process_noise, &mut self.sensor.rng)
+                } else {
+                    0.0
+                };
+                let dt = sampling_time / self.sub_steps;
+                for _ in 0..self.sub_steps {
+                    let dis = (self.ball.pos - self.ind.pos).abs();
+                    let force = self.coupling.apply(self.ind.force(), dis, self.softening);
+                    let force = force - self.ball.mass * self.gravity + disturbance;
+                    self.ball.step(force, dt);
+                    self.collide();
+                }
+            }
+            self.errors.add(
+                self.target - self.ball.pos,
+                sampling_time.as_secs_f32(),
+                self.elapsed.as_secs_f32(),
+            );
+
+            // Open loop until the controller engages
+            if self.elapsed <= self.engage_delay {
+                continue;
+            }
+            if self.elapsed - sampling_time <= self.engage_delay && !self.engage_delay.is_zero() {
+                self.event("", "Controller engaged".to_owned());
+            }
+
+            // The controller only acts every n-th step
+            self.control_counter += 1;
+            if self.control_counter < self.control_divider {
+                continue;
             }
+            self.control_counter = 0;
+            let control_time = sampling_time * self.control_divider;
 
             // Measuring the position of the ball
-            let pos = self.sensor.pos(&self.ball);
+            let pos = self.sensor.pos(&self.ball, control_time);
+            let pos = self.input_filter.filter(pos);
+            // Only measured when used, so the position noise stays the same otherwise
+            if self.pid.d_source == DerivativeSource::FromVelocitySensor {
+                let vel = self.sensor.vel(&self.ball);
+                self.controller.measure_velocity(vel);
+            }
 
             // Adapting the current on the induction
-            self.pid.update(pos, sampling_time);
-            let force = self.pid.total();
-            self.ind.set_force(force, sampling_time);
+            let output = self
+                .controller
+                .update(pos, self.target, self.clipped, control_time);
+            let output = self.output_filter.filter(output, control_time);
+            let sign = if self.invert_output { -1.0 } else { 1.0 };
+            self.clipped = self
+                .ind
+                .set_force(sign * output, self.elapsed, control_time);
+            magnitude_limited |= self.ind.magnitude_limited();
+            rate_limited |= self.ind.rate_limited();
+            // The anti-windup compares in terms of the controller output
+            self.controller
+                .back_calculate(sign * self.ind.force(), sign * self.ind.command());
+
+            if self.ind.saturated() != self.saturated {
+                self.saturated = !self.saturated;
+                let text = if self.saturated {
+                    "Actuator saturated"
+                } else {
+                    "Actuator back in range"
+                };
+                self.event("", text.to_owned());
+            }
+        }
+        if !self.data().is_finite() {
+            self.diverged = Some(finite);
+            self.event("", "Simulation diverged, halted until reset".to_owned());
         }
+        Data {
+            magnitude_limited,
+            rate_limited,
+            ..self.data()
+        }
+    }
+
+    fn data(&self) -> Data {
+        if let Some(data) = self.diverged {
+            return Data {
+                diverged: true,
+                ..data
+            };
+        }
+        let [p, i, d] = self.controller.terms();
         Data {
             pos: self.ball.pos,
             vel: self.ball.vel,
+            target: self.target,
             force: self.ind.force(),
+            command: self.ind.command(),
+            p,
+            i,
+            d,
+            reference: self.reference.as_ref().map(|reference| reference.ball.pos),
+            magnitude_limited: self.ind.magnitude_limited(),
+            rate_limited: self.ind.rate_limited(),
+            diverged: false,
         }
     }
 }
 
+#[derive(Clone)]
 pub enum Message {
     Kp(f32),
     Ki(f32),
     Kd(f32),
+    Kt(f32),
+    /// Constant added to the output of the PID controller [N]
+    FeedForward(f32),
+    ProportionalMode(ProportionalMode),
+    /// Time constant of the low-pass on the derivative term [s], zero disables it
+    DFilter(f32),
+    DerivativeMode(DerivativeMode),
+    DerivativeStart(DerivativeStart),
+    /// Differentiating the positions or measuring the velocity with a dedicated sensor
+    DerivativeSource(DerivativeSource),
+    IntegralMode(IntegralMode),
+    /// Freezes the integral while the inductor clips the force
+    ConditionalIntegration(bool),
+    /// Magnitude limit of the integral, infinity disables it
+    IntegralLimit(f32),
+    /// The integral only accumulates while the error is within this band [m] and holds
+    /// outside, infinity disables it
+    IntegralBand(f32),
+    /// Magnitude limit of the command of the PID controller [N], before the inductor
+    /// clips the force. Infinity disables it
+    OutputLimit(f32),
+    /// Switches the control law, starting it from scratch
+    ControllerKind(ControllerKind),
+    LeadLagGain(f32),
+    LeadTime(f32),
+    LagTime(f32),
+    LeadLagBias(f32),
+    /// Output of the bang-bang controller [N]
+    BangBangForce(f32),
+    /// Band around the target in which the bang-bang controller doesn't switch [m]
+    BangBangHysteresis(f32),
     Reset,
     Target(f32),
+    /// Target over time, restarting with every reset
+    Profile(Vec<Setpoint>),
+    /// Time-varying target, which takes precedence over the setpoint profile
+    TargetProfile(TargetProfile),
+    /// Band around the target in which the error counts as zero
+    ErrorDeadZone(f32),
     Noise(f32),
-    Gravitation(f32),
+    /// Standard deviation of the noise of the velocity sensor [m/s]
+    VelocityNoise(f32),
+    /// Time constant of the first-order lag of the sensor [s]
+    SensorTau(f32),
+    /// Transport delay of the sensor [s], rounded to whole measurements
+    SensorDelay(f32),
+    /// Step size of the analog-digital converter [m], zero disables the quantization
+    SensorResolution(f32),
+    /// Chance of every measurement to be a gross outlier
+    OutlierProbability(f32),
+    /// Deviation of an outlier [m]
+    OutlierMagnitude(f32),
+    /// Repeats the noise of the current run after every reset
+    FreezeNoise(bool),
+    /// Distribution of the sensor noise, whose standard deviation stays the same
+    NoiseKind(NoiseKind),
+    /// Restarts the noise with this seed, or with a random one for `None`
+    Seed(Option<u64>),
+    /// Starting position of the ball [m]. Takes effect on the next reset, so the ball
+    /// isn't teleported while running
+    InitPos(f32),
+    /// Starting velocity of the ball [m/s]. Takes effect on the next reset
+    InitVel(f32),
+    /// Randomizes the start of the ball on every reset
+    RandomStart(bool),
+    /// Maximum deviation of the starting position [m]
+    PosSpread(f32),
+    /// Maximum deviation of the starting velocity [m/s]
+    VelSpread(f32),
+    /// Downward acceleration [m/s^2], negative values pull up
+    Gravity(f32),
+    /// Standard deviation of a random force on the ball [N], drawn once per step
+    ProcessNoise(f32),
+    /// How the inductor force falls off with the distance to the ball
+    Coupling(CouplingModel),
+    /// Softening of the distance term of the inductor force [m^2], must be positive
+    ForceSoftening(f32),
+    /// Mass of the ball [kg], at least `MIN_MASS`
+    Mass(f32),
+    /// Bottom of the tube [m], negative infinity removes it
+    Floor(f32),
+    /// Top of the tube [m], infinity removes it
+    Ceiling(f32),
+    /// Ratio of the velocities after and before a collision, from 0 (inelastic) to 1 (elastic)
+    Restitution(f32),
+    /// Magnitude limit of the ball's velocity, infinity disables it
+    MaxVelocity(f32),
+    /// Symmetric limit of the force, upwards and downwards [N]
     MaxForce(f32),
+    /// Limit of the upward (positive) force [N]
+    MaxForceUp(f32),
+    /// Limit of the downward (negative) force [N], zero for an electromagnet
+    MaxForceDown(f32),
     MaxForceRate(f32),
+    /// Takes effect on the next reset
+    InitialForce(f32),
+    ActuatorGain(f32),
+    /// Requested forces of a smaller magnitude count as zero [N]
+    Deadband(f32),
+    /// Transport delay between the controller and the force of the inductor [s]
+    DeadTime(f32),
+    /// Negates the controller output before the inductor
+    InvertOutput(bool),
+    /// Seconds of open loop after every reset
+    EngageDelay(f32),
+    /// Cutoff frequency of the low-pass on the controller output [Hz], zero disables it
+    OutputCutoff(f32),
+    /// Number of measurements the median filter takes into account, one disables it
+    MedianWindow(u32),
     HoldBall(bool),
+    /// Adds to the velocity of the ball once, bypassing the controller [m/s]
+    Disturbance(f32),
+    /// Balances the gravitation when the held ball is released
+    SmoothRelease(bool),
+    /// Starts the integral at its steady-state value after every reset
+    Preload(bool),
+    SubSteps(u32),
+    ControlDivider(u32),
+    FollowTarget(bool),
+    FollowRate(f32),
+    /// Runs a noise-free reference with a much finer physics step next to the simulation
+    Reference(bool),
     Restart,
 }
 
+/// Target as a function of the simulated time since the last reset
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum TargetProfile {
+    /// Leaves the target to [`Message::Target`] and the setpoint profile
+    Constant(f32),
+    /// Linear from `from` to `to` within `duration` seconds, then holds `to`
+    Ramp { from: f32, to: f32, duration: f32 },
+    Sine {
+        center: f32,
+        amplitude: f32,
+        freq_hz: f32,
+    },
+    /// `low` for the first half of every period, `high` for the second
+    Square { low: f32, high: f32, period_s: f32 },
+}
+
+impl TargetProfile {
+    /// One profile of every kind with default parameters around `target`
+    pub fn all(target: f32) -> [TargetProfile; 4] {
+        [
+            TargetProfile::Constant(target),
+            TargetProfile::Ramp {
+                from: target,
+                to: target + TARGET_AMPLITUDE,
+                duration: TARGET_PERIOD,
+            },
+            TargetProfile::Sine {
+                center: target,
+                amplitude: TARGET_AMPLITUDE,
+                freq_hz: 1.0 / TARGET_PERIOD,
+            },
+            TargetProfile::Square {
+                low: target - TARGET_AMPLITUDE,
+                high: target + TARGET_AMPLITUDE,
+                period_s: TARGET_PERIOD,
+            },
+        ]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            TargetProfile::Constant(_) => "Constant",
+            TargetProfile::Ramp { .. } => "Ramp",
+            TargetProfile::Sine { .. } => "Sine",
+            TargetProfile::Square { .. } => "Square",
+        }
+    }
+
+    /// Target after `time` seconds
+    pub fn target(&self, time: f32) -> f32 {
+        match *self {
+            TargetProfile::Constant(target) => target,
+            TargetProfile::Ramp { from, to, duration } => {
+                let progress = if duration > 0.0 {
+                    (time / duration).min(1.0)
+                } else {
+                    1.0
+                };
+                from + (to - from) * progress
+            }
+            TargetProfile::Sine {
+                center,
+                amplitude,
+                freq_hz,
+            } => center + amplitude * (std::f32::consts::TAU * freq_hz * time).sin(),
+            TargetProfile::Square {
+                low,
+                high,
+                period_s,
+            } => {
+                if period_s > 0.0 && time.rem_euclid(period_s) >= period_s / 2.0 {
+                    high
+                } else {
+                    low
+                }
+            }
+        }
+    }
+}
+
+/// State of a [`Simulation`] to return to
+#[derive(Clone)]
+pub struct SimSnapshot(Box<Simulation>);
+
+/// The target changes to `target` once `time` seconds have been simulated
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct Setpoint {
+    pub time: f32,
+    pub target: f32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Data {
     pub pos: f32,
     pub vel: f32,
+    pub target: f32,
     pub force: f32,
+    /// Force requested from the inductor before its limits
+    pub command: f32,
+    /// Terms of the controller
+    pub p: f32,
+    pub i: f32,
+    pub d: f32,
+    /// Position of the ball in the reference simulation, if it runs
+    pub reference: Option<f32>,
+    /// The magnitude or the rate limit of the inductor has clipped the force
+    pub magnitude_limited: bool,
+    pub rate_limited: bool,
+    /// NaN or Inf has come up, so the simulation repeats its last finite data until reset
+    pub diverged: bool,
 }
 
-#[derive(Debug)]
-pub struct Ball {
-    pos: f32,
-    vel: f32,
+impl Data {
+    /// Neither NaN nor infinite
+    pub fn is_finite(&self) -> bool {
+        [
+            self.pos,
+            self.vel,
+            self.force,
+            self.command,
+            self.p,
+            self.i,
+            self.d,
+        ]
+        .iter()
+        .all(|v| v.is_finite())
+    }
 }
 
-impl Default for Ball {
-    fn default() -> Self {
+/// Median of the last `window` inputs, which rejects isolated outliers
+#[derive(Clone)]
+pub struct Median {
+    window: usize,
+    samples: VecDeque<f32>,
+}
+
+impl Median {
+    pub fn new(window: usize) -> Self {
         Self {
-            pos: BALL_POS,
-            vel: BALL_VEL,
+            window: window.max(1),
+            samples: VecDeque::new(),
         }
     }
-}
 
-impl Ball {
-    pub fn reset(&mut self) {
-        *self = Default::default();
+    pub fn set_window(&mut self, window: usize) {
+        self.window = window.max(1);
+        while self.samples.len() > self.window {
+            self.samples.pop_front();
+        }
     }
-    pub fn step(&mut self, force: f32, delta_time: Duration) {
-        let dt = delta_time.as_secs_f32();
-        let delta_vel = 0.5 * dt * force;
-        self.vel += delta_vel;
-        self.pos += self.vel * dt;
-        self.vel += delta_vel;
+
+    /// Median of `input` and its predecessors, the mean of both middle ones for an even count
+    pub fn filter(&mut self, input: f32) -> f32 {
+        if self.samples.len() == self.window {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(input);
+        let mut sorted: Vec<f32> = self.samples.iter().copied().collect();
+        sorted.sort_by(f32::total_cmp);
+        let mid = sorted.len() / 2;
+        if sorted.len() % 2 == 0 {
+            (sorted[mid - 1] + sorted[mid]) / 2.0
+        } else {
+            sorted[mid]
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.samples.clear();
     }
 }
 
-pub struct Inductor {
-    pos: f32,
-    force: f32,
-    max_force: f32,
-    max_force_rate: f32,
+/// First-order low-pass filter
+#[derive(Clone)]
+pub struct LowPass {
+    /// Cutoff frequency [Hz], zero passes everything
+    pub cutoff: f32,
+    /// Last output, `None` until the first input
+    state: Option<f32>,
 }
 
-impl Default for Inductor {
-    fn default() -> Self {
+impl LowPass {
+    pub fn new(cutoff: f32) -> Self {
         Self {
-            pos: IND_POS,
-            force: 0.0,
-            max_force: MAX_FORCE,
-            max_force_rate: MAX_FORCE_RATE,
+            cutoff,
+            state: None,
         }
     }
-}
 
-impl Inductor {
-    pub fn reset(&mut self) {
-        self.force = 0.0;
+    /// Filters `input` that arrives `dt` after the last one
+    pub fn filter(&mut self, input: f32, dt: Duration) -> f32 {
+        let output = match self.state {
+            Some(state) if self.cutoff > 0.0 => {
+                let tau = 1.0 / (2.0 * std::f32::consts::PI * self.cutoff);
+                let dt = dt.as_secs_f32();
+                state + (input - state) * dt / (tau + dt)
+            }
+            _ => input,
+        };
+        self.state = Some(output);
+        output
     }
 
-    fn force(&self) -> f32 {
-        self.force
+    pub fn reset(&mut self) {
+        self.state = None;
     }
+}
 
-    fn set_force(&mut self, force: f32, sampling_time: Duration) {
-        let dt = sampling_time.as_secs_f32();
-        let delta = force - self.force;
-        let delta_rate = delta / dt;
-        let delta = if delta_rate.abs() <= self.max_force_rate {
-            delta
-        } else {
-            self.max_force_rate * delta.signum() * dt
-        };
-        self.force += delta;
-        self.force = self.force.clamp(-self.max_force, self.max_force);
-    }
+/// Distribution of the sensor noise
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NoiseKind {
+    Normal,
+    Uniform,
+    Laplace,
 }
 
-impl Default for Pid {
-    fn default() -> Self {
-        Self {
-            p: 0.0,
-            i: 0.0,
-            d: 0.0,
-            kp: KP,
-            ki: KI,
-            kd: KD,
-            prev_pos: None,
-            target: TARGET,
+impl NoiseKind {
+    pub const ALL: [NoiseKind; 3] = [NoiseKind::Normal, NoiseKind::Uniform, NoiseKind::Laplace];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            NoiseKind::Normal => "Normal",
+            NoiseKind::Uniform => "Uniform",
+            NoiseKind::Laplace => "Laplace",
         }
     }
-}
-
-pub struct Pid {
-    p: f32,
-    i: f32,
-    d: f32,
 
-    pub kp: f32,
-    pub ki: f32,
-    pub kd: f32,
+    /// Draws zero-mean noise with the standard deviation `sigma`, whatever the distribution
+    pub fn sample(self, sigma: f32, rng: &mut impl Rng) -> f32 {
+        match self {
+            NoiseKind::Normal => Normal::new(0.0, sigma).unwrap().sample(rng),
+            NoiseKind::Uniform => {
+                let half_width = sigma * 3f32.sqrt();
+                rng.gen_range(-half_width..=half_width)
+            }
+            // Difference of two exponentials with the scale `sigma / √2`
+            NoiseKind::Laplace => {
+                let (a, b): (f32, f32) = (rng.sample(Exp1), rng.sample(Exp1));
+                sigma * std::f32::consts::FRAC_1_SQRT_2 * (a - b)
+            }
+        }
+    }
+}
 
-    prev_pos: Option<f32>,
-    pub target: f32,
+/// Force on the ball per force of the inductor, depending on their distance
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum CouplingModel {
+    /// `1 / (softening + distance²)` like a point source
+    InverseSquare,
+    /// `1 / (√softening + distance)` like a line source
+    InverseLinear,
+    /// `exp(-k · distance)` with the decay `k` [1/m], independent of the softening
+    Exponential { k: f32 },
 }
 
-impl Pid {
-    pub fn reset(&mut self) {
-        self.p = 0.0;
-        self.i = 0.0;
-        self.d = 0.0;
-        self.prev_pos = None;
+impl CouplingModel {
+    pub const ALL: [CouplingModel; 3] = [
+        CouplingModel::InverseSquare,
+        CouplingModel::InverseLinear,
+        CouplingModel::Exponential { k: COUPLING_DECAY },
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            CouplingModel::InverseSquare => "Inverse square",
+            CouplingModel::InverseLinear => "Inverse linear",
+            CouplingModel::Exponential { .. } => "Exponential",
+        }
     }
 
-    fn update(&mut self, pos: f32, sample_time: Duration) {
-        let dt = sample_time.as_secs_f32();
-        let error = self.target - pos;
-        self.p = self.kp * error;
-        self.i += self.ki * error;
-        if let Some(prev_pos) = self.prev_pos {
-            self.d = self.kd * (prev_pos - pos) / dt;
+    /// Force on the ball at `distance` [m] when the inductor exerts `force`
+    ///
+    /// `softening` [m^2] keeps the inverse models finite at zero distance.
+    pub fn apply(&self, force: f32, distance: f32, softening: f32) -> f32 {
+        match *self {
+            CouplingModel::InverseSquare => force / (softening + distance.powi(2)),
+            CouplingModel::InverseLinear => force / (softening.sqrt() + distance),
+            CouplingModel::Exponential { k } => force * (-k * distance).exp(),
         }
-        self.prev_pos = Some(pos);
     }
 
-    fn total(&self) -> f32 {
-        self.p + self.i + self.d
+    /// Force of the inductor that exerts `force` on the ball at `distance` [m]
+    pub fn inverse(&self, force: f32, distance: f32, softening: f32) -> f32 {
+        match *self {
+            CouplingModel::InverseSquare => force * (softening + distance.powi(2)),
+            CouplingModel::InverseLinear => force * (softening.sqrt() + distance),
+            CouplingModel::Exponential { k } => force * (k * distance).exp(),
+        }
     }
 }
 
+#[derive(Clone)]
 pub struct Sensor {
     /// Random number generator for the noise
-    rng: ThreadRng,
-    /// Normal distribution of the noise
-    normal: Normal<f32>,
+    rng: StdRng,
+    /// Distribution of the noise
+    kind: NoiseKind,
+    /// Standard deviation of the noise
+    sigma: f32,
+    /// Standard deviation of the noise of the velocity sensor [m/s]
+    vel_noise: f32,
+    /// Time constant of the first-order lag, zero disables it
+    tau: f32,
+    /// Output of the first-order lag, `None` until the first measurement
+    lagged: Option<f32>,
+    /// Transport delay, rounded to whole measurements
+    delay: Duration,
+    /// Measurements that are still on their way, the oldest first
+    delayed: VecDeque<f32>,
+    /// Least significant bit of the analog-digital converter, `None` disables the quantization
+    resolution: Option<f32>,
+    /// Chance of a measurement to be off by `±outlier_magnitude`
+    outlier_probability: f32,
+    outlier_magnitude: f32,
 }
 
 impl Default for Sensor {
     fn default() -> Self {
-        Self {
-            rng: rand::thread_rng(),
-            normal: Normal::new(0.0, NOISE).unwrap(),
-        }
+        Self::new(None)
     }
 }
 
 impl Sensor {
-    pub fn pos(&mut self, ball: &Ball) -> f32 {
-        let noise = self.normal.sample(&mut self.rng);
-        ball.pos + noise
+    /// Sensor with reproducible noise for `Some(seed)`, and different noise every time otherwise
+    pub fn new(seed: Option<u64>) -> Self {
+        Self {
+            rng: match seed {
+                Some(seed) => StdRng::seed_from_u64(seed),
+                None => StdRng::from_entropy(),
+            },
+            kind: NOISE_KIND,
+            sigma: NOISE,
+            vel_noise: VEL_NOISE,
+            tau: SENSOR_TAU,
+            lagged: None,
+            delay: Duration::from_secs_f32(SENSOR_DELAY),
+            delayed: VecDeque::new(),
+            resolution: (SENSOR_RESOLUTION > 0.0).then_some(SENSOR_RESOLUTION),
+            outlier_probability: OUTLIER_PROBABILITY,
+            outlier_magnitude: OUTLIER_MAGNITUDE,
+        }
+    }
+
+    /// Measures the position of the ball, `dt` after the last measurement
+    pub fn pos(&mut self, ball: &Ball, dt: Duration) -> f32 {
+        // The analog response lags behind the ball, the noise is added afterwards
+        let pos = match self.lagged {
+            Some(lagged) if self.tau > 0.0 => {
+                let dt = dt.as_secs_f32();
+                lagged + (ball.pos - lagged) * dt / (self.tau + dt)
+            }
+            _ => ball.pos,
+        };
+        self.lagged = Some(pos);
+        // Reports the measurement from `delay` ago, or the first one while warming up
+        let samples = (self.delay.as_secs_f32() / dt.as_secs_f32()).round() as usize;
+        self.delayed.push_back(pos);
+        while self.delayed.len() > samples + 1 {
+            self.delayed.pop_front();
+        }
+        let pos = self.delayed[0];
+        let noise = self.kind.sample(self.sigma, &mut self.rng);
+        // Only draws when enabled, so the noise stays the same without outliers
+        let outlier = if self.outlier_probability > 0.0
+            && self.rng.gen_bool(self.outlier_probability.min(1.0) as f64)
+        {
+            if self.rng.gen() {
+                self.outlier_magnitude
+            } else {
+                -self.outlier_magnitude
+            }
+        } else {
+            0.0
+        };
+        let pos = pos + noise + outlier;
+        // The converter digitizes the noisy signal. Rounding half up keeps the steps even
+        // across zero, unlike `f32::round`
+        match self.resolution {
+            Some(lsb) => (pos / lsb + 0.5).floor() * lsb,
+            None => pos,
+        }
+    }
+
+    /// Measures the velocity of the ball with a dedicated sensor, without lag or delay
+    pub fn vel(&mut self, ball: &Ball) -> f32 {
+        // Only draws with noise, so the position noise stays the same without
+        let noise = if self.vel_noise > 0.0 {
+            self.kind.sample(self.vel_noise, &mut self.rng)
+        } else {
+            0.0
+        };
+        ball.vel + noise
     }
 
     pub fn set_sigma(&mut self, sigma: f32) {
-        self.normal = Normal::new(0.0, sigma).unwrap();
+        self.sigma = sigma.max(0.0);
+    }
+
+    pub fn reset(&mut self) {
+        self.lagged = None;
+        self.delayed.clear();
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    pub fn reset(&mut self) {}
+    /// Golden trajectory. Regenerate with `UPDATE_GOLDEN=1 cargo test golden_trajectory`
+    const GOLDEN: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden_trajectory.csv");
+    const SEED: u64 = 42;
+    const TOLERANCE: f32 = 1e-4;
+
+    /// Runs a seeded simulation with a target step and returns `[time, pos, vel, force]` rows
+    fn trajectory() -> Vec<[f32; 4]> {
+        let mut sim = Simulation::default();
+        sim.seed(SEED);
+        let sampling_time = Duration::from_secs_f32(1.0 / SAMPLING_RATE as f32);
+        (1..=3 * SAMPLING_RATE)
+            .map(|n| {
+                if n == 3 * SAMPLING_RATE / 2 {
+                    sim.config(Message::Target(0.4));
+                }
+                let data = sim.step(1, sampling_time);
+                let time = n as f32 * sampling_time.as_secs_f32();
+                [time, data.pos, data.vel, data.force]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn zero_sampling_time() {
+        let mut sim = Simulation::default();
+        let data = sim.step(100, Duration::ZERO);
+        assert!(data.is_finite());
+        assert_eq!(data.pos, BALL_POS);
+        assert_eq!(data.vel, BALL_VEL);
+        assert_eq!(data.force, 0.0);
+    }
+
+    #[test]
+    fn overflowing_gain_halts() {
+        let sampling_time = Duration::from_millis(10);
+        let mut sim = Simulation::default();
+        sim.config(Message::MaxForce(f32::INFINITY));
+        // The integral overflows after a few steps
+        sim.config(Message::Ki(f32::MAX));
+        let data = sim.run(Duration::from_secs(1), sampling_time);
+        assert!(data.iter().all(Data::is_finite));
+        let last = data.last().unwrap();
+        assert!(last.diverged);
+        // Halted with the last finite state
+        assert_eq!(sim.step(10, sampling_time), *last);
+        let mut events = EventLog::default();
+        sim.take_events(&mut events);
+        assert!(events.iter().any(|event| event.text.contains("diverged")));
+
+        sim.config(Message::Ki(KI));
+        sim.config(Message::Reset);
+        let data = sim.step(1, sampling_time);
+        assert!(!data.diverged && data.is_finite());
+    }
+
+    #[test]
+    fn edge_case_sampling_rates() {
+        for rate in [1, 3, 1_000, 1_000_000, 1_000_000_000, u32::MAX] {
+            let mut sim = Simulation::default();
+            let sampling_time = Duration::from_secs_f32(1.0 / rate as f32);
+            for _ in 0..1_000 {
+                let data = sim.step(1, sampling_time);
+                assert!(data.pos.is_finite(), "{rate} Hz: pos {}", data.pos);
+                assert!(data.vel.is_finite(), "{rate} Hz: vel {}", data.vel);
+                assert!(data.force.is_finite(), "{rate} Hz: force {}", data.force);
+            }
+        }
+    }
+
+    #[test]
+    fn sub_stepped_free_fall() {
+        let mut sim = Simulation::default();
+        sim.config(Message::Floor(f32::NEG_INFINITY));
+        sim.config(Message::SubSteps(10));
+        sim.config(Message::MaxForce(0.0));
+        let sampling_time = Duration::from_millis(10);
+        for n in 1..=50 {
+            let data = sim.step(1, sampling_time);
+            let t = n as f32 * sampling_time.as_secs_f32();
+            let pos = BALL_POS - 0.5 * GRAVITY * t * t;
+            assert!((data.pos - pos).abs() < 1e-4, "{} != {pos}", data.pos);
+        }
+    }
+
+    #[test]
+    fn free_fall_is_independent_of_the_mass() {
+        let sampling_time = Duration::from_millis(10);
+        for (mass, gravity) in [(0.5, GRAVITY), (3.0, GRAVITY), (2.0, 3.71)] {
+            let mut sim = Simulation::default();
+            sim.config(Message::Floor(f32::NEG_INFINITY));
+            sim.config(Message::MaxForce(0.0));
+            sim.config(Message::Mass(mass));
+            sim.config(Message::Gravity(gravity));
+            let h0 = sim.step(0, sampling_time).pos;
+            for n in 1..=100 {
+                let data = sim.step(1, sampling_time);
+                let t = n as f32 * sampling_time.as_secs_f32();
+                let h = h0 - 0.5 * gravity * t * t;
+                assert!(
+                    (data.pos - h).abs() < 1e-4,
+                    "{mass} kg: {} != {h}",
+                    data.pos
+                );
+                assert!(
+                    (data.vel + gravity * t).abs() < 1e-4,
+                    "{mass} kg: {}",
+                    data.vel
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn sub_steps_reduce_integration_error() {
+        // Constant inductor force, so only the distance dependent coupling varies
+        let fall = |sub_steps: u32| {
+            let mut sim = Simulation::default();
+            sim.config(Message::Ceiling(f32::INFINITY));
+            sim.config(Message::SubSteps(sub_steps));
+            sim.config(Message::Kp(0.0));
+            sim.config(Message::Ki(0.0));
+            sim.config(Message::Kd(0.0));
+            sim.config(Message::InitialForce(MAX_FORCE));
+            sim.config(Message::Reset);
+            sim.config(Message::ControlDivider(u32::MAX)); // The controller never acts
+            sim.step(10, Duration::from_millis(50)).pos
+        };
+        let exact = fall(10_000);
+        let coarse = (fall(1) - exact).abs();
+        let fine = (fall(10) - exact).abs();
+        assert!(
+            fine < coarse / 5.0,
+            "{fine} isn't much better than {coarse}"
+        );
+    }
+
+    #[test]
+    fn seeded_steps_are_identical() {
+        let sampling_time = Duration::from_millis(10);
+        let run = || {
+            let mut sim = Simulation::default();
+            sim.config(Message::Noise(0.01));
+            sim.config(Message::OutlierProbability(0.05));
+            sim.config(Message::RandomStart(true));
+            sim.config(Message::Seed(Some(SEED)));
+            sim.config(Message::Reset);
+            (0..200)
+                .map(|_| {
+                    let data = sim.step(1, sampling_time);
+                    [data.pos, data.vel, data.force, data.p, data.i, data.d].map(f32::to_bits)
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(run(), run());
+    }
+
+    #[test]
+    fn frozen_noise_repeats() {
+        let mut sim = Simulation::default();
+        sim.config(Message::Noise(0.1));
+        sim.config(Message::FreezeNoise(true));
+        let sampling_time = Duration::from_millis(10);
+        let mut run = || {
+            sim.config(Message::Reset);
+            (0..100)
+                .map(|_| sim.step(1, sampling_time).pos)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(run(), run());
+    }
+
+    #[test]
+    fn freezing_holds_the_current_noise() {
+        let mut sim = Simulation::default();
+        sim.config(Message::Noise(0.1));
+        let sampling_time = Duration::from_millis(10);
+        let run = |sim: &mut Simulation| {
+            sim.config(Message::Reset);
+            (0..100)
+                .map(|_| sim.step(1, sampling_time).pos)
+                .collect::<Vec<_>>()
+        };
+        let current = run(&mut sim);
+        // Freezing after the run replays it, also when toggled off and on in between
+        sim.config(Message::FreezeNoise(true));
+        assert_eq!(run(&mut sim), current);
+        sim.config(Message::FreezeNoise(false));
+        sim.config(Message::FreezeNoise(true));
+        assert_eq!(run(&mut sim), current);
+        // Unfrozen, every run faces new noise
+        sim.config(Message::FreezeNoise(false));
+        let fresh = run(&mut sim);
+        assert_ne!(fresh, current);
+        sim.config(Message::FreezeNoise(true));
+        assert_eq!(run(&mut sim), fresh);
+        // Seeding while frozen switches to the noise of the seed
+        sim.config(Message::Seed(Some(SEED)));
+        let seeded = run(&mut sim);
+        assert_ne!(seeded, fresh);
+        assert_eq!(run(&mut sim), seeded);
+    }
+
+    #[test]
+    fn maglev_levitates() {
+        let mut sim = Simulation::default();
+        sim.seed(SEED);
+        for msg in [
+            Message::Gravity(GRAVITY),
+            Message::Coupling(CouplingModel::InverseSquare),
+            Message::MaxForce(MAX_FORCE),
+            Message::MaxForceDown(0.0),
+            Message::Target(TARGET),
+        ] {
+            sim.config(msg);
+        }
+        let sampling_time = Duration::from_secs_f32(1.0 / SAMPLING_RATE as f32);
+        for _ in 0..10 * SAMPLING_RATE {
+            let data = sim.step(1, sampling_time);
+            assert!(data.force >= 0.0, "magnet pushed the ball");
+        }
+        for _ in 0..SAMPLING_RATE {
+            let data = sim.step(1, sampling_time);
+            assert!((data.pos - TARGET).abs() < 0.01, "{}", data.pos);
+        }
+    }
+
+    #[test]
+    fn negative_force_limits_dont_panic() {
+        let sampling_time = Duration::from_millis(10);
+        for msg in [
+            Message::MaxForce(-2.0 * MAX_FORCE),
+            Message::MaxForceUp(-2.0 * MAX_FORCE),
+            Message::MaxForceDown(-2.0 * MAX_FORCE),
+        ] {
+            let mut sim = Simulation::default();
+            sim.config(Message::Preload(true));
+            sim.config(msg);
+            sim.config(Message::Reset);
+            for _ in 0..100 {
+                let data = sim.step(1, sampling_time);
+                assert!(data.force.is_finite());
+            }
+        }
+    }
+
+    #[test]
+    fn smooth_release_avoids_jolt() {
+        let jolt = |smooth: bool| {
+            let mut sim = Simulation::default();
+            sim.seed(SEED);
+            sim.config(Message::SmoothRelease(smooth));
+            sim.config(Message::Target(BALL_POS));
+            sim.config(Message::HoldBall(true));
+            let sampling_time = Duration::from_secs_f32(1.0 / SAMPLING_RATE as f32);
+            sim.step(2 * SAMPLING_RATE, sampling_time);
+            sim.config(Message::HoldBall(false));
+            (0..SAMPLING_RATE / 2)
+                .map(|_| sim.step(1, sampling_time).vel.abs())
+                .fold(0.0, f32::max)
+        };
+        let (smooth, abrupt) = (jolt(true), jolt(false));
+        assert!(
+            smooth < abrupt / 2.0,
+            "{smooth} isn't smoother than {abrupt}"
+        );
+    }
+
+    #[test]
+    fn velocity_sensor_amplifies_less_noise() {
+        let sampling_time = Duration::from_secs_f32(1.0 / SAMPLING_RATE as f32);
+        // Spread of the derivative term while the ball rests without force
+        let spread = |source| {
+            let mut sim = Simulation::from_seed(SEED);
+            sim.config(Message::Gravity(0.0));
+            sim.config(Message::MaxForce(0.0));
+            sim.config(Message::Noise(0.001));
+            sim.config(Message::VelocityNoise(0.001));
+            sim.config(Message::DerivativeSource(source));
+            let data = sim.run(Duration::from_secs(5), sampling_time);
+            let d: Vec<f32> = data[1..].iter().map(|data| data.d).collect();
+            let mean = d.iter().sum::<f32>() / d.len() as f32;
+            (d.iter().map(|d| (d - mean).powi(2)).sum::<f32>() / d.len() as f32).sqrt()
+        };
+        let differentiated = spread(DerivativeSource::FromPosition);
+        let measured = spread(DerivativeSource::FromVelocitySensor);
+        // The differences of two samples have √2 times the noise, divided by the sampling time
+        let amplification = 2f32.sqrt() * SAMPLING_RATE as f32;
+        let ratio = differentiated / measured;
+        assert!(
+            (ratio / amplification - 1.0).abs() < 0.2,
+            "{differentiated} vs. {measured}"
+        );
+    }
+
+    #[test]
+    fn coupling_models_match_closed_form() {
+        let (force, softening) = (10.0, 0.25);
+        for distance in [0.0, 0.3, 1.0, 2.5f32] {
+            let expected = [
+                (
+                    CouplingModel::InverseSquare,
+                    force / (0.25 + distance * distance),
+                ),
+                (CouplingModel::InverseLinear, force / (0.5 + distance)),
+                (
+                    CouplingModel::Exponential { k: 2.0 },
+                    force * (-2.0 * distance).exp(),
+                ),
+            ];
+            for (model, expected) in expected {
+                let applied = model.apply(force, distance, softening);
+                assert!((applied - expected).abs() < 1e-5, "{}", model.name());
+                let inverse = model.inverse(applied, distance, softening);
+                assert!((inverse - force).abs() < 1e-4, "{}", model.name());
+            }
+        }
+    }
+
+    #[test]
+    fn balancing_force_respects_softening_and_mass() {
+        let sampling_time = Duration::from_secs_f32(1.0 / SAMPLING_RATE as f32);
+        for (softening, mass) in [(0.25, 1.0), (1.0, 2.0), (4.0, 0.5)] {
+            let mut sim = Simulation::default();
+            sim.config(Message::Noise(0.0));
+            sim.config(Message::EngageDelay(1.0));
+            sim.config(Message::ForceSoftening(softening));
+            sim.config(Message::Mass(mass));
+            sim.ind.preload(sim.balancing_force(sim.ball.pos));
+            let data = sim.step(SAMPLING_RATE / 2, sampling_time);
+            assert!(
+                (data.pos - BALL_POS).abs() < 1e-4,
+                "{softening}: {}",
+                data.pos
+            );
+        }
+    }
+
+    #[test]
+    fn profile_matches_manual_target_step() {
+        let sampling_time = Duration::from_secs_f32(1.0 / SAMPLING_RATE as f32);
+        // `trajectory` changes the target right before the step that ends at 1.5 s
+        let time = sampling_time * (3 * SAMPLING_RATE / 2 - 1);
+        let mut sim = Simulation::default();
+        sim.seed(SEED);
+        sim.config(Message::Profile(vec![Setpoint {
+            time: time.as_secs_f32(),
+            target: 0.4,
+        }]));
+        for expected in trajectory() {
+            let data = sim.step(1, sampling_time);
+            assert_eq!([data.pos, data.vel, data.force], expected[1..]);
+        }
+    }
+
+    #[test]
+    fn inverted_output_with_negated_gains() {
+        let run = |invert: bool, sign: f32| {
+            let mut sim = Simulation::default();
+            sim.seed(SEED);
+            sim.config(Message::InvertOutput(invert));
+            sim.config(Message::Kp(sign * KP));
+            sim.config(Message::Ki(sign * KI));
+            sim.config(Message::Kd(sign * KD));
+            let sampling_time = Duration::from_secs_f32(1.0 / SAMPLING_RATE as f32);
+            sim.step(2 * SAMPLING_RATE, sampling_time).pos
+        };
+        assert_eq!(run(false, 1.0), run(true, -1.0));
+    }
+
+    /// Integral after 0.2 s of rising from the starting position at `rate` Hz
+    fn integral_after_rise(i_mode: IntegralMode, rate: u32) -> f32 {
+        let mut sim = Simulation::default();
+        sim.config(Message::Noise(0.0));
+        sim.config(Message::IntegralMode(i_mode));
+        // Same integral action at the default sampling rate
+        let ki = match i_mode {
+            IntegralMode::PerSample => KI,
+            IntegralMode::PerSecond => KI * SAMPLING_RATE as f32,
+        };
+        sim.config(Message::Ki(ki));
+        let sampling_time = Duration::from_secs_f32(1.0 / rate as f32);
+        sim.step(rate / 5, sampling_time).i
+    }
+
+    #[test]
+    fn integral_per_second_is_independent_of_the_sampling_rate() {
+        let slow = integral_after_rise(IntegralMode::PerSecond, 100);
+        let fast = integral_after_rise(IntegralMode::PerSecond, 1_000);
+        assert!((slow - fast).abs() < 0.05 * slow.abs(), "{slow} != {fast}");
+    }
+
+    #[test]
+    fn integral_per_sample_depends_on_the_sampling_rate() {
+        // Demonstrates why the legacy mode surprises on rate changes
+        let slow = integral_after_rise(IntegralMode::PerSample, 100);
+        let fast = integral_after_rise(IntegralMode::PerSample, 1_000);
+        assert!((slow - fast).abs() > 0.5 * slow.abs(), "{slow} ~ {fast}");
+    }
+
+    #[test]
+    fn sensor_lag() {
+        let mut sensor = Sensor {
+            tau: 0.1,
+            ..Default::default()
+        };
+        sensor.set_sigma(0.0);
+        let dt = Duration::from_millis(10);
+        let mut ball = Ball::default();
+        assert_eq!(
+            sensor.pos(&ball, dt),
+            ball.pos,
+            "starts at the first measurement"
+        );
+        ball.pos += 1.0;
+        // After one time constant about 63 % of the step has passed
+        let pos = (0..10).map(|_| sensor.pos(&ball, dt)).last().unwrap();
+        assert!((pos - (BALL_POS + 0.63)).abs() < 0.02, "{pos}");
+
+        sensor.reset();
+        assert_eq!(sensor.pos(&ball, dt), ball.pos);
+    }
+
+    #[test]
+    fn sensor_delay() {
+        let mut sensor = Sensor {
+            delay: Duration::from_millis(30),
+            ..Default::default()
+        };
+        sensor.set_sigma(0.0);
+        let dt = Duration::from_millis(10);
+        let mut ball = Ball::default();
+        let measured: Vec<f32> = (0..8)
+            .map(|n| {
+                ball.pos = n as f32;
+                sensor.pos(&ball, dt)
+            })
+            .collect();
+        // The first measurement while warming up, afterwards three samples behind
+        assert_eq!(measured, [0.0, 0.0, 0.0, 0.0, 1.0, 2.0, 3.0, 4.0]);
+
+        sensor.reset();
+        assert_eq!(sensor.pos(&ball, dt), ball.pos);
+    }
+
+    #[test]
+    fn sensor_quantization() {
+        let lsb = 0.01;
+        let mut sensor = Sensor {
+            resolution: Some(lsb),
+            ..Default::default()
+        };
+        sensor.set_sigma(0.0);
+        let dt = Duration::from_millis(10);
+        let mut ball = Ball::default();
+        // Ramp from -5 to 5 LSB in tenths of an LSB
+        let measured: Vec<f32> = (-50..=50)
+            .map(|n| {
+                ball.pos = n as f32 * 0.1 * lsb;
+                sensor.pos(&ball, dt)
+            })
+            .collect();
+        for pair in measured.windows(2) {
+            let step = pair[1] - pair[0];
+            assert!(step.abs() < 1e-6 || (step - lsb).abs() < 1e-6, "{step}");
+        }
+        // Every level except the ends lasts one LSB of the ramp
+        let levels = measured.windows(2).filter(|p| p[1] != p[0]).count();
+        assert_eq!(levels, 10);
+        assert_eq!(measured.iter().filter(|&&m| m == 0.0).count(), 10);
+        assert!((measured[0] + 5.0 * lsb).abs() < 1e-6);
+    }
+
+    #[test]
+    fn engage_delay_keeps_the_loop_open() {
+        let mut sim = Simulation::default();
+        sim.seed(SEED);
+        sim.config(Message::EngageDelay(0.5));
+        sim.config(Message::Floor(f32::NEG_INFINITY));
+        let sampling_time = Duration::from_millis(10);
+        for n in 1..=50 {
+            let data = sim.step(1, sampling_time);
+            assert_eq!(data.force, INITIAL_FORCE);
+            let t = n as f32 * sampling_time.as_secs_f32();
+            let pos = BALL_POS - 0.5 * GRAVITY * t * t;
+            assert!((data.pos - pos).abs() < 1e-2, "{} != {pos}", data.pos);
+        }
+        assert_ne!(sim.step(1, sampling_time).force, INITIAL_FORCE);
+    }
+
+    #[test]
+    fn seeded_runs_are_reproducible() {
+        let run = |seed: u64| {
+            let mut sim = Simulation::from_seed(seed);
+            sim.config(Message::Noise(0.01));
+            sim.run(Duration::from_secs(1), Duration::from_millis(10))
+        };
+        let data = run(SEED);
+        assert_eq!(data.len(), 100);
+        assert_eq!(data, run(SEED));
+        assert_ne!(data, run(SEED + 1));
+    }
+
+    #[test]
+    fn random_start_is_reproducible() {
+        let start = |seed: u64| {
+            let mut sim = Simulation::default();
+            sim.config(Message::RandomStart(true));
+            sim.seed(seed);
+            sim.config(Message::Reset);
+            let data = sim.data();
+            assert!((data.pos - BALL_POS).abs() <= POS_SPREAD);
+            assert!((data.vel - BALL_VEL).abs() <= VEL_SPREAD);
+            (data.pos, data.vel)
+        };
+        assert_eq!(start(SEED), start(SEED));
+        assert_ne!(start(SEED), start(SEED + 1));
+    }
+
+    #[test]
+    fn preloaded_integral_reduces_droop() {
+        let droop = |preload: bool| {
+            let mut sim = Simulation::default();
+            sim.seed(SEED);
+            sim.config(Message::Preload(preload));
+            sim.config(Message::Target(BALL_POS));
+            sim.config(Message::Reset);
+            let sampling_time = Duration::from_secs_f32(1.0 / SAMPLING_RATE as f32);
+            (0..2 * SAMPLING_RATE)
+                .map(|_| BALL_POS - sim.step(1, sampling_time).pos)
+                .fold(0.0, f32::max)
+        };
+        let (preloaded, legacy) = (droop(true), droop(false));
+        assert!(
+            preloaded < legacy / 2.0,
+            "{preloaded} isn't better than {legacy}"
+        );
+    }
+
+    #[test]
+    fn switching_back_to_pid_keeps_its_configuration() {
+        let run = |switch: bool| {
+            let mut sim = Simulation::default();
+            sim.seed(SEED);
+            if switch {
+                sim.config(Message::ControllerKind(ControllerKind::LeadLag));
+            }
+            sim.config(Message::Kp(100.0));
+            sim.config(Message::ControllerKind(ControllerKind::Pid));
+            let sampling_time = Duration::from_secs_f32(1.0 / SAMPLING_RATE as f32);
+            sim.step(SAMPLING_RATE, sampling_time).pos
+        };
+        assert_eq!(run(true), run(false));
+    }
+
+    #[test]
+    fn lead_lag_holds_the_ball() {
+        let mut sim = Simulation::default();
+        sim.seed(SEED);
+        sim.config(Message::ControllerKind(ControllerKind::LeadLag));
+        sim.config(Message::LeadLagBias(12.0));
+        let sampling_time = Duration::from_secs_f32(1.0 / SAMPLING_RATE as f32);
+        let pos = sim.step(10 * SAMPLING_RATE, sampling_time).pos;
+        assert!((pos - TARGET).abs() < 0.05, "{pos}");
+    }
+
+    #[test]
+    fn bang_bang_pushes_towards_the_target() {
+        let mut sim = Simulation::default();
+        sim.seed(SEED);
+        sim.config(Message::Noise(0.0));
+        sim.config(Message::ControllerKind(ControllerKind::BangBang));
+        // The rate limit would delay every switch by a fifth of a second
+        sim.config(Message::MaxForceRate(f32::INFINITY));
+        let sampling_time = Duration::from_secs_f32(1.0 / SAMPLING_RATE as f32);
+        // Up from below the target until it has passed the hysteresis
+        let mut data = sim.step(1, sampling_time);
+        while data.pos <= TARGET + 0.5 * BANG_BANG_HYSTERESIS {
+            assert_eq!(data.command, BANG_BANG_FORCE);
+            data = sim.step(1, sampling_time);
+        }
+        assert_eq!(sim.step(1, sampling_time).command, -BANG_BANG_FORCE);
+    }
+
+    #[test]
+    fn feed_forward_unloads_the_integral() {
+        let integral = |kff: f32| {
+            let mut sim = Simulation::default();
+            sim.seed(SEED);
+            sim.config(Message::FeedForward(kff));
+            let sampling_time = Duration::from_secs_f32(1.0 / SAMPLING_RATE as f32);
+            sim.step(20 * SAMPLING_RATE, sampling_time).i
+        };
+        // Force that holds the ball at the target against the gravitation
+        let dis = IND_POS - TARGET;
+        let weight = MASS * GRAVITY * (FORCE_SOFTENING + dis * dis);
+        assert!((integral(0.0) - weight).abs() < 0.5, "{}", integral(0.0));
+        assert!(integral(weight).abs() < 0.5, "{}", integral(weight));
+    }
+
+    #[test]
+    fn disturbance_kicks_the_ball_once() {
+        let sampling_time = Duration::from_secs_f32(1.0 / SAMPLING_RATE as f32);
+        let mut sim = Simulation::default();
+        sim.config(Message::ControlDivider(u32::MAX)); // Only the push moves the ball
+        sim.config(Message::Gravity(0.0));
+        sim.config(Message::Disturbance(1.0));
+        assert_eq!(sim.step(1, sampling_time).vel, BALL_VEL + 1.0);
+        assert_eq!(sim.step(1, sampling_time).vel, BALL_VEL + 1.0);
+
+        sim.config(Message::HoldBall(true));
+        sim.config(Message::Disturbance(1.0));
+        sim.config(Message::HoldBall(false));
+        assert_eq!(sim.step(1, sampling_time).vel, BALL_VEL + 1.0);
+    }
+
+    #[test]
+    fn snapshot_round_trip() {
+        let sampling_time = Duration::from_secs_f32(1.0 / SAMPLING_RATE as f32);
+        let mut sim = Simulation::default();
+        sim.seed(SEED);
+        sim.step(SAMPLING_RATE / 2, sampling_time);
+        let snapshot = sim.snapshot();
+        let before = sim.step(0, sampling_time);
+
+        let first = sim.step(SAMPLING_RATE, sampling_time);
+        sim.config(Message::Kp(0.0));
+        sim.restore(&snapshot);
+        let restored = sim.step(0, sampling_time);
+        assert_eq!((restored.pos, restored.vel), (before.pos, before.vel));
+
+        // Same gains and physics, but the noise continues instead of repeating
+        let second = sim.step(SAMPLING_RATE, sampling_time);
+        assert_ne!(first.pos, second.pos);
+        assert!((first.pos - second.pos).abs() < 0.01);
+    }
+
+    #[test]
+    fn gain_changes_are_bumpless() {
+        let sampling_time = Duration::from_secs_f32(1.0 / SAMPLING_RATE as f32);
+        let mut sim = Simulation::from_seed(SEED);
+        sim.config(Message::Noise(0.0));
+        sim.config(Message::IntegralMode(IntegralMode::PerSecond));
+        sim.config(Message::Ki(20.0));
+        let data = sim.run(Duration::from_secs(10), sampling_time);
+        let output = |data: &Data| data.p + data.i + data.d;
+        let mut before = output(data.last().unwrap());
+        for msg in [Message::Ki(60.0), Message::Kp(300.0), Message::Kd(40.0)] {
+            sim.config(msg);
+            let after = output(&sim.step(1, sampling_time));
+            assert!((after - before).abs() < 0.01, "{before} -> {after}");
+            before = after;
+        }
+    }
+
+    #[test]
+    fn dead_time_delays_the_force() {
+        let sampling_time = Duration::from_millis(10);
+        let mut sim = Simulation::default();
+        // Takes effect on the first step that is at least the dead time later
+        sim.config(Message::DeadTime(0.045));
+        sim.config(Message::MaxForceRate(f32::INFINITY));
+        let forces: Vec<f32> = (0..10).map(|_| sim.step(1, sampling_time).force).collect();
+        assert!(forces[..5].iter().all(|&force| force == INITIAL_FORCE));
+        assert_ne!(forces[5], INITIAL_FORCE, "{forces:?}");
+    }
+
+    #[test]
+    fn initial_conditions_apply_on_reset() {
+        let mut sim = Simulation::default();
+        sim.config(Message::InitPos(0.9));
+        sim.config(Message::InitVel(-0.2));
+        assert_eq!(sim.ball.pos, BALL_POS, "no teleport while running");
+        assert_eq!(sim.ball.vel, BALL_VEL);
+
+        sim.reset();
+        assert_eq!(sim.ball.pos, 0.9);
+        assert_eq!(sim.ball.vel, -0.2);
+    }
+
+    #[test]
+    fn errors_accumulate_until_reset() {
+        let mut sim = Simulation::default();
+        sim.config(Message::HoldBall(true));
+        sim.config(Message::Target(BALL_POS + 0.1));
+        sim.step(100, Duration::from_millis(10));
+        // 0.1 m for 1 s
+        assert!(
+            (sim.errors().iae - 0.1).abs() < 1e-4,
+            "{}",
+            sim.errors().iae
+        );
+        assert!(
+            (sim.errors().ise - 0.01).abs() < 1e-5,
+            "{}",
+            sim.errors().ise
+        );
+
+        sim.config(Message::Reset);
+        assert_eq!(*sim.errors(), ErrorAccumulator::default());
+    }
+
+    #[test]
+    fn zero_process_noise_is_deterministic() {
+        let sampling_time = Duration::from_secs_f32(1.0 / SAMPLING_RATE as f32);
+        let run = |process_noise: Option<f32>| {
+            let mut sim = Simulation::from_seed(SEED);
+            if let Some(sigma) = process_noise {
+                sim.config(Message::ProcessNoise(sigma));
+            }
+            sim.run(Duration::from_secs(2), sampling_time)
+        };
+        assert!(run(None) == run(Some(0.0)));
+        assert!(run(None) != run(Some(1.0)));
+    }
+
+    #[test]
+    fn low_pass() {
+        let dt = Duration::from_millis(1);
+        let mut filter = LowPass::new(0.0);
+        assert_eq!(filter.filter(1.0, dt), 1.0);
+        assert_eq!(
+            filter.filter(-1.0, dt),
+            -1.0,
+            "a zero cutoff passes everything"
+        );
+
+        // A high frequency is attenuated, a constant passes after settling
+        let mut filter = LowPass::new(1.0);
+        let peak = (0..1_000)
+            .map(|n| filter.filter(if n % 2 == 0 { 1.0 } else { -1.0 }, dt).abs())
+            .fold(0.0, f32::max);
+        assert!(peak <= 1.0);
+        let tail = (0..100)
+            .map(|n| filter.filter(if n % 2 == 0 { 1.0 } else { -1.0 }, dt).abs())
+            .fold(0.0, f32::max);
+        assert!(tail < 0.01, "{tail}");
+        filter.reset();
+        assert_eq!(filter.filter(2.0, dt), 2.0);
+    }
+
+    #[test]
+    fn outliers() {
+        let mut sensor = Sensor {
+            rng: StdRng::seed_from_u64(SEED),
+            outlier_probability: 0.1,
+            outlier_magnitude: 1.0,
+            ..Default::default()
+        };
+        sensor.set_sigma(0.0);
+        let ball = Ball::default();
+        let dt = Duration::from_millis(10);
+        let outliers = (0..10_000)
+            .map(|_| sensor.pos(&ball, dt))
+            .filter(|&pos| {
+                assert!([BALL_POS - 1.0, BALL_POS, BALL_POS + 1.0].contains(&pos));
+                pos != BALL_POS
+            })
+            .count();
+        assert!((900..1_100).contains(&outliers), "{outliers}");
+    }
+
+    #[test]
+    fn noise_kinds_share_the_variance() {
+        let sigma = 0.1;
+        let mut rng = StdRng::seed_from_u64(SEED);
+        for kind in NoiseKind::ALL {
+            let samples: Vec<f32> = (0..100_000).map(|_| kind.sample(sigma, &mut rng)).collect();
+            let mean = samples.iter().sum::<f32>() / samples.len() as f32;
+            let variance =
+                samples.iter().map(|s| (s - mean).powi(2)).sum::<f32>() / samples.len() as f32;
+            assert!(mean.abs() < 0.002, "{}: mean {mean}", kind.name());
+            let ratio = variance / sigma.powi(2);
+            assert!((ratio - 1.0).abs() < 0.03, "{}: {ratio}", kind.name());
+        }
+        // The uniform noise is bounded, unlike the others
+        let bound = sigma * 3f32.sqrt();
+        assert!((0..10_000).all(|_| NoiseKind::Uniform.sample(sigma, &mut rng).abs() <= bound));
+    }
+
+    #[test]
+    fn median_rejects_an_outlier() {
+        let mut median = Median::new(3);
+        let filtered: Vec<f32> = [0.5, 0.5, 10.0, 0.5, 0.5]
+            .into_iter()
+            .map(|pos| median.filter(pos))
+            .collect();
+        assert_eq!(filtered, [0.5; 5]);
+
+        let mut median = Median::new(1);
+        assert_eq!(
+            median.filter(10.0),
+            10.0,
+            "a window of one passes everything"
+        );
+    }
+
+    #[test]
+    fn reference_is_a_noise_free_fine_run() {
+        let sampling_time = Duration::from_secs_f32(1.0 / SAMPLING_RATE as f32);
+        let mut sim = Simulation::default();
+        sim.seed(SEED);
+        sim.config(Message::Reference(true));
+        let mut fine = Simulation::default();
+        fine.config(Message::Noise(0.0));
+        fine.config(Message::SubSteps(REFERENCE_SUB_STEPS));
+        for sim in [&mut sim, &mut fine] {
+            sim.step(SAMPLING_RATE / 2, sampling_time);
+            sim.config(Message::Target(0.4));
+        }
+        let data = sim.step(SAMPLING_RATE, sampling_time);
+        let fine = fine.step(SAMPLING_RATE, sampling_time);
+        assert_eq!(data.reference, Some(fine.pos));
+        assert_ne!(data.pos, fine.pos);
+
+        sim.config(Message::Reference(false));
+        assert_eq!(sim.step(1, sampling_time).reference, None);
+    }
+
+    #[test]
+    fn integral_limit_holds_while_saturated() {
+        let sampling_time = Duration::from_secs_f32(1.0 / SAMPLING_RATE as f32);
+        let mut sim = Simulation::default();
+        sim.seed(SEED);
+        sim.config(Message::MaxForce(1.0));
+        sim.config(Message::IntegralLimit(5.0));
+        sim.config(Message::Target(0.9));
+        for _ in 0..2 * SAMPLING_RATE {
+            let data = sim.step(1, sampling_time);
+            assert!(data.i.abs() <= 5.0, "{}", data.i);
+        }
+        assert_eq!(sim.step(1, sampling_time).i, 5.0);
+    }
+
+    #[test]
+    fn conditional_integration_stops_windup() {
+        let sampling_time = Duration::from_secs_f32(1.0 / SAMPLING_RATE as f32);
+        let mut sim = Simulation::default();
+        sim.seed(SEED);
+        sim.config(Message::MaxForce(0.1));
+        sim.config(Message::ConditionalIntegration(true));
+        sim.config(Message::Target(100.0));
+        let pinned = sim.step(SAMPLING_RATE / 10, sampling_time).i;
+        for _ in 0..SAMPLING_RATE {
+            assert_eq!(sim.step(1, sampling_time).i, pinned);
+        }
+    }
+
+    #[test]
+    fn ball_rests_on_the_floor() {
+        let drop = |restitution: f32| {
+            let mut sim = Simulation::default();
+            sim.config(Message::MaxForce(0.0));
+            sim.config(Message::Restitution(restitution));
+            let sampling_time = Duration::from_secs_f32(1.0 / SAMPLING_RATE as f32);
+            (0..2 * SAMPLING_RATE)
+                .map(|_| sim.step(1, sampling_time))
+                .inspect(|data| assert!(data.pos >= FLOOR))
+                .last()
+                .unwrap()
+        };
+        let data = drop(0.0);
+        assert_eq!(data.pos, FLOOR);
+        assert!(data.vel.abs() < 0.1, "{}", data.vel);
+
+        let bounced = |restitution| {
+            let mut sim = Simulation::default();
+            sim.config(Message::MaxForce(0.0));
+            sim.config(Message::Restitution(restitution));
+            let sampling_time = Duration::from_secs_f32(1.0 / SAMPLING_RATE as f32);
+            (0..SAMPLING_RATE)
+                .map(|_| sim.step(1, sampling_time).vel)
+                .fold(0.0, f32::max)
+        };
+        assert_eq!(bounced(0.0), 0.0);
+        assert!(bounced(0.8) > 1.0, "an elastic ball bounces back up");
+    }
+
+    #[test]
+    fn target_profiles() {
+        let ramp = TargetProfile::Ramp {
+            from: 0.4,
+            to: 0.6,
+            duration: 2.0,
+        };
+        assert_eq!(ramp.target(0.0), 0.4);
+        assert!((ramp.target(1.0) - 0.5).abs() < 1e-6);
+        assert_eq!(ramp.target(3.0), 0.6);
+        let square = TargetProfile::Square {
+            low: 0.4,
+            high: 0.6,
+            period_s: 2.0,
+        };
+        assert_eq!(square.target(0.5), 0.4);
+        assert_eq!(square.target(1.5), 0.6);
+        assert_eq!(square.target(2.5), 0.4);
+
+        let sine = TargetProfile::Sine {
+            center: 0.5,
+            amplitude: 0.1,
+            freq_hz: 1.0,
+        };
+        let mut sim = Simulation::default();
+        sim.config(Message::TargetProfile(sine));
+        let sampling_time = Duration::from_secs_f32(1.0 / SAMPLING_RATE as f32);
+        for n in 0..SAMPLING_RATE {
+            let time = n as f32 * sampling_time.as_secs_f32();
+            let target = sim.step(1, sampling_time).target;
+            assert!((target - sine.target(time)).abs() < 1e-5, "{target}");
+        }
+
+        sim.config(Message::TargetProfile(TargetProfile::Constant(0.45)));
+        assert_eq!(sim.step(1, sampling_time).target, 0.45);
+    }
+
+    #[test]
+    fn golden_trajectory() {
+        let trajectory = trajectory();
+        if std::env::var_os("UPDATE_GOLDEN").is_some() {
+            let mut csv = String::from("time,pos,vel,force\n");
+            for row in &trajectory {
+                let row: Vec<String> = row.iter().map(f32::to_string).collect();
+                csv += &row.join(",");
+                csv.push('\n');
+            }
+            std::fs::write(GOLDEN, csv).unwrap();
+        }
+
+        let golden = std::fs::read_to_string(GOLDEN).unwrap();
+        let golden: Vec<Vec<f32>> = golden
+            .lines()
+            .skip(1) // header
+            .map(|line| line.split(',').map(|v| v.parse().unwrap()).collect())
+            .collect();
+        assert_eq!(golden.len(), trajectory.len());
+        for (n, (expected, actual)) in golden.iter().zip(&trajectory).enumerate() {
+            for (e, a) in expected.iter().zip(actual) {
+                assert!(
+                    (e - a).abs() <= TOLERANCE * e.abs().max(1.0),
+                    "sample {n} deviates from golden trajectory: {expected:?} != {actual:?}"
+                );
+            }
+        }
+    }
 }
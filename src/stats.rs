@@ -0,0 +1,110 @@
+/// Running statistics that are updated incrementally with Welford's algorithm
+#[derive(Clone, Copy)]
+pub struct RunningStats {
+    count: u64,
+    mean: f64,
+    /// Sum of squared differences from the current mean
+    m2: f64,
+    min: f32,
+    max: f32,
+}
+
+impl Default for RunningStats {
+    fn default() -> Self {
+        Self {
+            count: 0,
+            mean: 0.0,
+            m2: 0.0,
+            min: f32::INFINITY,
+            max: f32::NEG_INFINITY,
+        }
+    }
+}
+
+impl RunningStats {
+    pub fn push(&mut self, val: f32) {
+        self.count += 1;
+        let delta = val as f64 - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (val as f64 - self.mean);
+        self.min = self.min.min(val);
+        self.max = self.max.max(val);
+    }
+
+    pub fn reset(&mut self) {
+        *self = Default::default();
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn mean(&self) -> Option<f32> {
+        (self.count > 0).then_some(self.mean as f32)
+    }
+
+    /// Population variance of every value pushed so far
+    pub fn variance(&self) -> Option<f32> {
+        (self.count > 0).then_some((self.m2 / self.count as f64) as f32)
+    }
+
+    pub fn std_dev(&self) -> Option<f32> {
+        self.variance().map(f32::sqrt)
+    }
+
+    pub fn min(&self) -> Option<f32> {
+        (self.count > 0).then_some(self.min)
+    }
+
+    pub fn max(&self) -> Option<f32> {
+        (self.count > 0).then_some(self.max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty() {
+        let stats = RunningStats::default();
+        assert_eq!(stats.count(), 0);
+        assert_eq!(stats.mean(), None);
+        assert_eq!(stats.variance(), None);
+        assert_eq!(stats.min(), None);
+        assert_eq!(stats.max(), None);
+    }
+
+    #[test]
+    fn matches_two_pass() {
+        let vals = [2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0];
+        let mut stats = RunningStats::default();
+        vals.iter().for_each(|&v| stats.push(v));
+
+        assert_eq!(stats.count(), 8);
+        assert_eq!(stats.mean(), Some(5.0));
+        assert_eq!(stats.variance(), Some(4.0));
+        assert_eq!(stats.std_dev(), Some(2.0));
+        assert_eq!(stats.min(), Some(2.0));
+        assert_eq!(stats.max(), Some(9.0));
+    }
+
+    #[test]
+    fn large_offset() {
+        // Naive sum of squares loses all precision here
+        let mut stats = RunningStats::default();
+        for v in [1e6 + 4.0, 1e6 + 7.0, 1e6 + 13.0, 1e6 + 16.0] {
+            stats.push(v);
+        }
+        assert_eq!(stats.variance(), Some(22.5));
+    }
+
+    #[test]
+    fn reset() {
+        let mut stats = RunningStats::default();
+        stats.push(1.0);
+        stats.reset();
+        assert_eq!(stats.count(), 0);
+        assert_eq!(stats.mean(), None);
+    }
+}
@@ -0,0 +1,456 @@
+use crate::default::SPEED;
+use crate::events::EventLog;
+use crate::sim::{Data, Message, Simulation};
+use web_time::{Duration, Instant};
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+
+/// Real time of the simulation
+struct Time {
+    /// Point in time when the simulated time has started
+    start: Instant,
+    /// Duration of time that has elapsed in the simulation
+    sim: Duration,
+    /// Simulated seconds per real second since `start`
+    speed: f32,
+}
+
+impl Default for Time {
+    fn default() -> Self {
+        Self {
+            start: Instant::now(),
+            sim: Duration::ZERO,
+            speed: SPEED,
+        }
+    }
+}
+
+impl Time {
+    /// Calculates the number of steps the simulation should step forward
+    ///
+    /// After a gap of more than `max_steps` samples, e.g. in a background tab, the
+    /// simulation only steps `max_steps` and drops the rest of the backlog. Catching it up
+    /// over the next frames would keep the simulation lagging behind the real time.
+    ///
+    /// The simulated time runs `speed` times as fast as the real time, which is `now`.
+    fn step(&mut self, now: Instant, sampling_time: Duration, speed: f32, max_steps: u32) -> u32 {
+        if sampling_time.is_zero() {
+            return 0; // Would result in infinite steps
+        }
+        // A new speed only applies from now on
+        if speed != self.speed {
+            self.speed = speed;
+            self.resume(now);
+        }
+        // Whole steps in integer nanoseconds, so rounding doesn't accumulate
+        let real = now
+            .saturating_duration_since(self.start)
+            .mul_f64(self.speed as f64);
+        let sim_dt = real.saturating_sub(self.sim); // Delta of real time and simulated time
+        let steps = sim_dt.as_nanos() / sampling_time.as_nanos();
+        let steps = steps.min(max_steps as u128) as u32;
+        self.sim += sampling_time * steps;
+        if steps == max_steps {
+            self.resume(now);
+        }
+
+        steps
+    }
+
+    /// Real time from `now` until the next step is due
+    #[cfg(not(target_arch = "wasm32"))]
+    fn due(&self, now: Instant, sampling_time: Duration) -> Duration {
+        let next = (self.sim + sampling_time).div_f64(self.speed as f64);
+        next.saturating_sub(now.saturating_duration_since(self.start))
+    }
+
+    /// Continues `now` after a pause as if the simulated time had started the simulated time
+    /// ago, so the real time of the pause isn't caught up
+    fn resume(&mut self, now: Instant) {
+        let real = self.sim.div_f64(self.speed as f64);
+        self.start = now.checked_sub(real).unwrap_or(now);
+    }
+}
+
+/// How the simulation steps in real time
+#[derive(Clone, Copy, PartialEq)]
+pub struct Pace {
+    pub sampling_time: Duration,
+    /// Simulated seconds per real second
+    pub speed: f32,
+    /// Longest backlog of samples that is caught up, e.g. after the thread has been suspended
+    pub max_steps: u32,
+}
+
+/// Data of a single step in real time
+#[derive(Clone, Copy)]
+pub struct Sample {
+    pub data: Data,
+    pub sampling_time: Duration,
+}
+
+/// The simulation with its real time, owned by the thread of the [`Stepper`] on native
+struct Runner {
+    sim: Simulation,
+    time: Time,
+    /// `None` while stopped
+    pace: Option<Pace>,
+}
+
+impl Runner {
+    fn new(sim: Simulation) -> Self {
+        Self {
+            sim,
+            time: Time::default(),
+            pace: None,
+        }
+    }
+
+    /// Changes the pace `now`, which starts at the simulated time `elapsed` unless already
+    /// running
+    fn run(&mut self, now: Instant, pace: Option<Pace>, elapsed: Duration) {
+        if let (None, Some(pace)) = (self.pace, pace) {
+            self.time.sim = elapsed;
+            self.time.speed = pace.speed;
+            self.time.resume(now);
+        }
+        self.pace = pace;
+    }
+
+    /// Real time from `now` until the next step is due, `None` while there is nothing to step
+    #[cfg(not(target_arch = "wasm32"))]
+    fn due(&self, now: Instant) -> Option<Duration> {
+        let pace = self.pace.filter(|pace| !pace.sampling_time.is_zero())?;
+        Some(self.time.due(now, pace.sampling_time))
+    }
+
+    /// Steps one by one until the simulated time has caught up with the real time `now`
+    ///
+    /// Stops once the simulation has diverged.
+    fn step(&mut self, now: Instant) -> Vec<Sample> {
+        let Some(pace) = self.pace else {
+            return Vec::new();
+        };
+        let steps = self
+            .time
+            .step(now, pace.sampling_time, pace.speed, pace.max_steps);
+        let mut samples = Vec::with_capacity(steps as usize);
+        for _ in 0..steps {
+            let data = self.sim.step(1, pace.sampling_time);
+            samples.push(Sample {
+                data,
+                sampling_time: pace.sampling_time,
+            });
+            if data.diverged {
+                self.pace = None;
+                break;
+            }
+        }
+        samples
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+enum Command {
+    Config(Message),
+    Run(Option<Pace>, Duration),
+    With(Box<dyn FnOnce(&mut Simulation) + Send>),
+}
+
+/// Steps the simulation at its sampling rate, independent of the frames of the GUI
+///
+/// On native the simulation runs on a thread of its own, which takes messages and sends the
+/// data of every step over channels. On wasm without threads the simulation steps whenever
+/// the samples are polled, i.e. once per frame.
+///
+/// Everything is processed in order, so [`Stepper::with`] sees the simulation with every
+/// message before applied and every sample before sent.
+pub struct Stepper {
+    #[cfg(not(target_arch = "wasm32"))]
+    commands: Sender<Command>,
+    #[cfg(not(target_arch = "wasm32"))]
+    samples: Receiver<Sample>,
+    #[cfg(target_arch = "wasm32")]
+    runner: Runner,
+    /// Pace that has been passed on last
+    pace: Option<Pace>,
+}
+
+impl Default for Stepper {
+    fn default() -> Self {
+        Self::new(Simulation::default())
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Stepper {
+    pub fn new(sim: Simulation) -> Self {
+        let (commands, command_rx) = mpsc::channel();
+        let (sample_tx, samples) = mpsc::channel();
+        let mut runner = Runner::new(sim);
+        // Ends as soon as the stepper is dropped
+        std::thread::Builder::new()
+            .name("simulation".to_owned())
+            .spawn(move || loop {
+                // Sleeping until the next step is due, unless a command comes first
+                let command = match runner.due(Instant::now()) {
+                    Some(timeout) => match command_rx.recv_timeout(timeout) {
+                        Ok(command) => Some(command),
+                        Err(RecvTimeoutError::Timeout) => None,
+                        Err(RecvTimeoutError::Disconnected) => return,
+                    },
+                    None => match command_rx.recv() {
+                        Ok(command) => Some(command),
+                        Err(_) => return,
+                    },
+                };
+                match command {
+                    Some(Command::Config(msg)) => runner.sim.config(msg),
+                    Some(Command::Run(pace, elapsed)) => runner.run(Instant::now(), pace, elapsed),
+                    Some(Command::With(f)) => f(&mut runner.sim),
+                    None => {}
+                }
+                for sample in runner.step(Instant::now()) {
+                    if sample_tx.send(sample).is_err() {
+                        return;
+                    }
+                }
+            })
+            .expect("Failed to spawn the simulation thread");
+        Self {
+            commands,
+            samples,
+            pace: None,
+        }
+    }
+
+    /// Runs `f` on the simulation and returns its result
+    pub fn with<R: Send + 'static>(
+        &mut self,
+        f: impl FnOnce(&mut Simulation) -> R + Send + 'static,
+    ) -> R {
+        let (result_tx, result) = mpsc::channel();
+        self.send(Command::With(Box::new(move |sim| {
+            // Only fails if the stepper has panicked in the meantime
+            let _ = result_tx.send(f(sim));
+        })));
+        result.recv().expect("The simulation thread has panicked")
+    }
+
+    pub fn config(&mut self, msg: Message) {
+        self.send(Command::Config(msg));
+    }
+
+    /// Steps in real time with `pace` from the simulated time `elapsed` on, `None` stops
+    ///
+    /// A new pace of a running simulation continues at its own simulated time.
+    pub fn run(&mut self, pace: Option<Pace>, elapsed: Duration) {
+        if pace != self.pace {
+            self.pace = pace;
+            self.send(Command::Run(pace, elapsed));
+        }
+    }
+
+    /// Samples that have been stepped in real time since the last call
+    pub fn samples(&mut self) -> Vec<Sample> {
+        self.samples.try_iter().collect()
+    }
+
+    fn send(&self, command: Command) {
+        self.commands
+            .send(command)
+            .expect("The simulation thread has panicked");
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+impl Stepper {
+    pub fn new(sim: Simulation) -> Self {
+        Self {
+            runner: Runner::new(sim),
+            pace: None,
+        }
+    }
+
+    /// Runs `f` on the simulation and returns its result
+    pub fn with<R: Send + 'static>(
+        &mut self,
+        f: impl FnOnce(&mut Simulation) -> R + Send + 'static,
+    ) -> R {
+        f(&mut self.runner.sim)
+    }
+
+    pub fn config(&mut self, msg: Message) {
+        self.runner.sim.config(msg);
+    }
+
+    /// Steps in real time with `pace` from the simulated time `elapsed` on, `None` stops
+    ///
+    /// A new pace of a running simulation continues at its own simulated time.
+    pub fn run(&mut self, pace: Option<Pace>, elapsed: Duration) {
+        if pace != self.pace {
+            self.pace = pace;
+            self.runner.run(Instant::now(), pace, elapsed);
+        }
+    }
+
+    /// Samples that have been stepped in real time since the last call
+    pub fn samples(&mut self) -> Vec<Sample> {
+        self.runner.step(Instant::now())
+    }
+}
+
+impl Stepper {
+    /// Steps `steps` times right away, see [`Simulation::step`]
+    pub fn step(&mut self, steps: u32, sampling_time: Duration) -> Data {
+        self.with(move |sim| sim.step(steps, sampling_time))
+    }
+
+    /// Copy of the simulation in its current state
+    pub fn simulation(&mut self) -> Simulation {
+        self.with(|sim| sim.clone())
+    }
+
+    pub fn take_events(&mut self, log: &mut EventLog) {
+        let mut events = self.with(|sim| {
+            let mut events = EventLog::default();
+            sim.take_events(&mut events);
+            events
+        });
+        log.append(&mut events);
+    }
+
+    /// Stops and drops the samples that haven't been taken yet
+    pub fn discard(&mut self) {
+        self.run(None, Duration::ZERO);
+        // Every sample stepped before stopping has been sent by now
+        self.with(|_| ());
+        self.samples();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::default::*;
+
+    /// Time that has started `ago` before the returned point in time
+    fn time(ago: Duration, speed: f32) -> (Time, Instant) {
+        let start = Instant::now();
+        let time = Time {
+            start,
+            sim: Duration::ZERO,
+            speed,
+        };
+        (time, start + ago)
+    }
+
+    #[test]
+    fn zero_sampling_time() {
+        let (mut time, now) = time(Duration::from_secs(1), SPEED);
+        assert_eq!(
+            time.step(now, Duration::ZERO, SPEED, MAX_STEPS_PER_FRAME),
+            0
+        );
+        assert_eq!(time.sim, Duration::ZERO);
+    }
+
+    #[test]
+    fn resume_skips_the_pause() {
+        let (mut time, now) = time(Duration::from_secs(10), SPEED);
+        time.sim = Duration::from_secs(1);
+        time.resume(now);
+        let sampling_time = Duration::from_millis(100);
+        assert_eq!(time.step(now, sampling_time, SPEED, MAX_STEPS_PER_FRAME), 0);
+        assert_eq!(time.sim, Duration::from_secs(1));
+        let later = now + Duration::from_millis(250);
+        assert_eq!(
+            time.step(later, sampling_time, SPEED, MAX_STEPS_PER_FRAME),
+            2
+        );
+    }
+
+    #[test]
+    fn speed_scales_the_steps() {
+        let steps = |speed: f32| {
+            let (mut time, now) = time(Duration::from_secs(1), speed);
+            time.step(now, Duration::from_millis(100), speed, MAX_STEPS_PER_FRAME)
+        };
+        assert_eq!(steps(1.0), 10);
+        assert_eq!(steps(2.0), 20);
+    }
+
+    #[test]
+    fn frame_gaps_are_clamped() {
+        let sampling_time = Duration::from_millis(1);
+        let (mut time, now) = time(Duration::from_secs(5), SPEED);
+        assert_eq!(time.step(now, sampling_time, SPEED, 100), 100);
+        assert_eq!(time.sim, Duration::from_millis(100));
+        // The rest of the backlog is dropped instead of caught up
+        assert_eq!(time.step(now, sampling_time, SPEED, 100), 0);
+        let later = now + Duration::from_millis(20);
+        assert_eq!(time.step(later, sampling_time, SPEED, 100), 20);
+    }
+
+    #[test]
+    fn steps_dont_drift() {
+        let sampling_time = Duration::from_secs_f32(1.0 / 3.0);
+        let (mut time, now) = time(Duration::from_secs(1000), SPEED);
+        let steps = time.step(now, sampling_time, SPEED, u32::MAX);
+        assert_eq!(time.sim, sampling_time * steps);
+        assert!(Duration::from_secs(1000).saturating_sub(time.sim) < sampling_time);
+    }
+
+    #[test]
+    fn steps_at_the_sampling_rate() {
+        let sampling_time = Duration::from_millis(10);
+        let pace = Pace {
+            sampling_time,
+            speed: SPEED,
+            max_steps: MAX_STEPS_PER_FRAME,
+        };
+        let mut runner = Runner::new(Simulation::from_seed(42));
+        let start = Instant::now();
+        runner.run(start, Some(pace), Duration::ZERO);
+        assert_eq!(runner.due(start), Some(sampling_time));
+        let mut samples = runner.step(start + Duration::from_millis(255));
+        assert_eq!(samples.len(), 25);
+        assert_eq!(
+            runner.due(start + Duration::from_millis(255)),
+            Some(Duration::from_millis(5))
+        );
+        samples.extend(runner.step(start + Duration::from_millis(500)));
+        assert_eq!(samples.len(), 50);
+        // Stopped, nothing is due
+        runner.run(start + Duration::from_millis(500), None, Duration::ZERO);
+        assert_eq!(runner.due(start + Duration::from_secs(1)), None);
+        assert!(runner.step(start + Duration::from_secs(1)).is_empty());
+
+        // The same as stepping right away
+        let mut sim = Simulation::from_seed(42);
+        for sample in samples {
+            assert_eq!(sample.sampling_time, sampling_time);
+            assert_eq!(sample.data, sim.step(1, sampling_time));
+        }
+    }
+
+    #[test]
+    fn messages_apply_in_order() {
+        let mut stepper = Stepper::default();
+        stepper.config(Message::Target(0.3));
+        let data = stepper.step(1, Duration::from_millis(10));
+        assert_eq!(data.target, 0.3);
+
+        // Stopped samples are dropped
+        let pace = Pace {
+            sampling_time: Duration::from_micros(10),
+            speed: SPEED,
+            max_steps: MAX_STEPS_PER_FRAME,
+        };
+        stepper.run(Some(pace), Duration::ZERO);
+        std::thread::sleep(Duration::from_millis(10));
+        stepper.discard();
+        assert!(stepper.samples().is_empty());
+    }
+}
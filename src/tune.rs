@@ -0,0 +1,224 @@
+use crate::default::*;
+use crate::sim::{ControllerKind, DerivativeMode, Message, Simulation, TargetProfile};
+use web_time::Duration;
+
+/// Gains of a PID controller with the integral gain per second
+pub struct PidGains {
+    pub kp: f32,
+    pub ki: f32,
+    pub kd: f32,
+}
+
+/// Named gains to start tuning from
+pub struct Preset {
+    pub name: &'static str,
+    /// What to expect from the preset
+    pub description: &'static str,
+    pub gains: PidGains,
+    pub d_mode: DerivativeMode,
+}
+
+impl Preset {
+    pub const ALL: [Preset; 4] = [
+        Preset {
+            name: "Gentle",
+            description: "Creeps to the target without overshoot, settles within seconds",
+            gains: PidGains {
+                kp: 120.0,
+                ki: 60.0,
+                kd: 25.0,
+            },
+            d_mode: DerivativeMode::OnMeasurement,
+        },
+        Preset {
+            name: "Default",
+            description: "The gains at startup",
+            gains: PidGains {
+                kp: KP,
+                ki: KI * SAMPLING_RATE as f32,
+                kd: KD,
+            },
+            d_mode: D_MODE,
+        },
+        Preset {
+            name: "Aggressive",
+            description: "Follows target changes within a fraction of a second, \
+                but kicks with the derivative on the error",
+            gains: PidGains {
+                kp: 200.0,
+                ki: 300.0,
+                kd: 25.0,
+            },
+            d_mode: DerivativeMode::OnError,
+        },
+        Preset {
+            name: "P-only",
+            description: "Without damping the ball oscillates, which shows what I and D are for",
+            gains: PidGains {
+                kp: KP,
+                ki: 0.0,
+                kd: 0.0,
+            },
+            d_mode: D_MODE,
+        },
+    ];
+}
+
+/// Tunes a PID controller with the classic Ziegler–Nichols rules
+///
+/// Copies of `sim` run with a proportional controller only, whose gain rises until the ball
+/// oscillates with sustained amplitude. The lowest such gain is the ultimate gain `Ku` and
+/// the period of the oscillation the ultimate period `Tu`.
+pub fn ziegler_nichols(sim: &Simulation, sampling_time: Duration) -> Result<PidGains, String> {
+    // Rising geometrically until the first oscillation
+    let mut lower = 0.0;
+    let mut upper = TUNE_KP_START;
+    let mut period = loop {
+        if let Some(period) = oscillation(sim, upper, sampling_time) {
+            break period;
+        }
+        if upper > TUNE_KP_MAX {
+            return Err(format!("No sustained oscillation up to Kp = {TUNE_KP_MAX}"));
+        }
+        lower = upper;
+        upper *= TUNE_KP_FACTOR;
+    };
+    // Narrowing down the ultimate gain in between
+    for _ in 0..TUNE_BISECTIONS {
+        let kp = 0.5 * (lower + upper);
+        match oscillation(sim, kp, sampling_time) {
+            Some(p) => (upper, period) = (kp, p),
+            None => lower = kp,
+        }
+    }
+
+    let (ku, tu) = (upper, period);
+    Ok(PidGains {
+        kp: 0.6 * ku,
+        ki: 1.2 * ku / tu,
+        kd: 0.075 * ku * tu,
+    })
+}
+
+/// Period of the sustained oscillation with a proportional controller of gain `kp`
+fn oscillation(sim: &Simulation, kp: f32, sampling_time: Duration) -> Option<f32> {
+    let mut sim = sim.clone();
+    // Holding the current target without noise, so only the controller makes the ball move
+    let target = sim.step(0, sampling_time).target;
+    for msg in [
+        Message::ControllerKind(ControllerKind::Pid),
+        Message::Kp(kp),
+        Message::Ki(0.0),
+        Message::Kd(0.0),
+        Message::Noise(0.0),
+        Message::VelocityNoise(0.0),
+        Message::ProcessNoise(0.0),
+        Message::OutlierProbability(0.0),
+        Message::Reference(false),
+        Message::HoldBall(false),
+        Message::Profile(Vec::new()),
+        Message::TargetProfile(TargetProfile::Constant(target)),
+        Message::Reset,
+    ] {
+        sim.config(msg);
+    }
+    let data = sim.run(Duration::from_secs_f32(TUNE_DURATION), sampling_time);
+    if data.iter().any(|data| data.diverged) {
+        return None;
+    }
+
+    // Judging the second half, after the transient
+    let pos: Vec<f32> = data[data.len() / 2..].iter().map(|d| d.pos).collect();
+    let mean = pos.iter().sum::<f32>() / pos.len() as f32;
+    let crossings: Vec<usize> = pos
+        .windows(2)
+        .enumerate()
+        .filter(|(_, pair)| (pair[0] < mean) != (pair[1] < mean))
+        .map(|(i, _)| i)
+        .collect();
+    if crossings.len() < TUNE_MIN_CROSSINGS {
+        return None;
+    }
+    // Sustained if the amplitude doesn't decay from the first to the second half
+    let amplitude = |pos: &[f32]| pos.iter().map(|p| (p - mean).abs()).fold(0.0, f32::max);
+    let (first, second) = pos.split_at(pos.len() / 2);
+    if amplitude(second) < TUNE_MIN_AMPLITUDE || amplitude(second) < 0.9 * amplitude(first) {
+        return None;
+    }
+
+    // Two crossings per period
+    let samples = crossings[crossings.len() - 1] - crossings[0];
+    let half_periods = (crossings.len() - 1) as f32;
+    Some(2.0 * samples as f32 * sampling_time.as_secs_f32() / half_periods)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SEED: u64 = 42;
+
+    #[test]
+    fn tuned_gains_hold_the_target() {
+        let sampling_time = Duration::from_secs_f32(1.0 / SAMPLING_RATE as f32);
+        let mut sim = Simulation::from_seed(SEED);
+        let gains = ziegler_nichols(&sim, sampling_time).unwrap();
+        assert!(gains.kp > 0.0 && gains.ki > 0.0 && gains.kd > 0.0);
+
+        sim.config(Message::Kp(gains.kp));
+        sim.config(Message::IntegralMode(crate::sim::IntegralMode::PerSecond));
+        sim.config(Message::Ki(gains.ki));
+        sim.config(Message::Kd(gains.kd));
+        sim.config(Message::Reset);
+        let data = sim.run(Duration::from_secs(20), sampling_time);
+        for data in &data[data.len() - SAMPLING_RATE as usize..] {
+            assert!((data.pos - TARGET).abs() < 0.01, "{}", data.pos);
+        }
+    }
+
+    #[test]
+    fn noise_doesnt_skew_the_tuning() {
+        let sampling_time = Duration::from_secs_f32(1.0 / SAMPLING_RATE as f32);
+        let gains = ziegler_nichols(&Simulation::from_seed(SEED), sampling_time).unwrap();
+        let mut sim = Simulation::from_seed(SEED);
+        sim.config(Message::ProcessNoise(5.0));
+        sim.config(Message::Noise(0.05));
+        let noisy = ziegler_nichols(&sim, sampling_time).unwrap();
+        assert_eq!(
+            [noisy.kp, noisy.ki, noisy.kd],
+            [gains.kp, gains.ki, gains.kd]
+        );
+    }
+
+    #[test]
+    fn presets_behave_as_described() {
+        let sampling_time = Duration::from_secs_f32(1.0 / SAMPLING_RATE as f32);
+        for preset in &Preset::ALL {
+            let mut sim = Simulation::from_seed(SEED);
+            let gains = &preset.gains;
+            for msg in [
+                Message::IntegralMode(crate::sim::IntegralMode::PerSecond),
+                Message::Kp(gains.kp),
+                Message::Ki(gains.ki),
+                Message::Kd(gains.kd),
+                Message::DerivativeMode(preset.d_mode),
+                Message::Reset,
+            ] {
+                sim.config(msg);
+            }
+            let data = sim.run(Duration::from_secs(20), sampling_time);
+            let held = data[data.len() - SAMPLING_RATE as usize..]
+                .iter()
+                .all(|data| (data.pos - TARGET).abs() < 0.01);
+            assert_eq!(held, preset.name != "P-only", "{}", preset.name);
+        }
+    }
+
+    #[test]
+    fn without_force_nothing_oscillates() {
+        let sampling_time = Duration::from_secs_f32(1.0 / SAMPLING_RATE as f32);
+        let mut sim = Simulation::from_seed(SEED);
+        sim.config(Message::MaxForce(0.0));
+        assert!(ziegler_nichols(&sim, sampling_time).is_err());
+    }
+}